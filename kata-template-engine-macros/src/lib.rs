@@ -0,0 +1,270 @@
+//! Templates of `kata-template-engine` checked at compile time.
+//!
+//! A template with a syntax error or a placeholder without a matching value fails the build
+//! instead of rendering with a "Variable X not replaced" warning:
+//!
+//! ```compile_fail
+//! use kata_template_engine_macros::template;
+//!
+//! let greeting = template!("Hello, ${nmae}!", name = "John");
+//! ```
+//!
+//! ```compile_fail
+//! use kata_template_engine_macros::template;
+//!
+//! let greeting = template!("Hello, ${name!", name = "John");
+//! ```
+//!
+//! ```compile_fail
+//! use kata_template_engine_macros::TemplateContext;
+//!
+//! #[derive(TemplateContext)]
+//! #[template(source = "Hello, ${name} ${surname}!")]
+//! struct Greeting {
+//!     name: String,
+//! }
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use kata_template_engine::Template;
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Ident, LitStr, Token};
+
+/// Renders a template literal with named values, as in
+/// `template!("Hello, ${name}", name = user.name)`, and returns the
+/// `Result<ParsedTemplate, TemplateError>` of `Renderer::render`.
+///
+/// The template is parsed at compile time: the build fails if it has a syntax error, if it reads
+/// a variable that is not one of the named values, or if a named value is not used. The values
+/// can be of any type implementing `serde::Serialize`.
+#[proc_macro]
+pub fn template(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as TemplateInput);
+    expand_template(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implements `kata_template_engine::TemplateContext` for a struct with named fields, using the
+/// template given by a `#[template(path = "...")]` attribute, relative to the directory of the
+/// crate manifest, or by a `#[template(source = "...")]` attribute.
+///
+/// The template is parsed at compile time: the build fails if it has a syntax error or if it
+/// reads a variable that is not a field of the struct. The fields can be of any type
+/// implementing `serde::Serialize`.
+#[proc_macro_derive(TemplateContext, attributes(template))]
+pub fn derive_template_context(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_template_context(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The arguments of `template!`: the template literal followed by `name = value` pairs.
+struct TemplateInput {
+    text: LitStr,
+    values: Vec<(Ident, Expr)>,
+}
+
+impl Parse for TemplateInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let text = input.parse()?;
+        let mut values = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            values.push((name, input.parse()?));
+        }
+        Ok(TemplateInput { text, values })
+    }
+}
+
+fn expand_template(input: TemplateInput) -> syn::Result<proc_macro2::TokenStream> {
+    let template = parse_template(&input.text.value(), input.text.span())?;
+    let variables = template.variables();
+    let mut names = BTreeSet::new();
+    for (name, _) in &input.values {
+        let key = field_name(name);
+        if !names.insert(key.clone()) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("value `{}` is given more than once", key),
+            ));
+        }
+    }
+    check_variables(&template, &names, input.text.span(), "named value")?;
+    for (name, _) in &input.values {
+        let key = field_name(name);
+        if !variables.contains(&key) {
+            return Err(syn::Error::new(
+                name.span(),
+                format!("value `{}` is not used by the template", key),
+            ));
+        }
+    }
+    let text = &input.text;
+    let inserts = input.values.iter().map(|(name, value)| {
+        let key = field_name(name);
+        insert_value(&key, quote!(&(#value)))
+    });
+    Ok(quote! {
+        {
+            let mut __context = ::kata_template_engine::__private::serde_json::Map::new();
+            #(#inserts)*
+            ::kata_template_engine::Renderer::new().render(
+                &::kata_template_engine::Template::parse(#text)
+                    .expect("the template is checked at compile time"),
+                &::kata_template_engine::__private::serde_json::Value::Object(__context),
+            )
+        }
+    })
+}
+
+fn expand_template_context(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            _ => Vec::new(),
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "TemplateContext can only be derived for structs",
+            ))
+        }
+    };
+    let (text, template, span) = read_template_attribute(&input)?;
+    let parsed = parse_template(&text, span)?;
+    let names = fields
+        .iter()
+        .filter_map(|field| field.ident.as_ref().map(field_name))
+        .collect();
+    check_variables(&parsed, &names, span, "field")?;
+    let inserts = fields.iter().filter_map(|field| {
+        let ident = field.ident.as_ref()?;
+        Some(insert_value(&field_name(ident), quote!(&self.#ident)))
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::kata_template_engine::TemplateContext for #name #type_generics
+            #where_clause
+        {
+            const TEMPLATE: &'static str = #template;
+
+            fn to_context(&self) -> ::kata_template_engine::__private::serde_json::Value {
+                let mut __context = ::kata_template_engine::__private::serde_json::Map::new();
+                #(#inserts)*
+                ::kata_template_engine::__private::serde_json::Value::Object(__context)
+            }
+        }
+    })
+}
+
+/// Reads the `#[template(...)]` attribute, returning the template text, the expression the
+/// generated code uses for it, and the span to point errors at.
+fn read_template_attribute(
+    input: &DeriveInput,
+) -> syn::Result<(String, proc_macro2::TokenStream, Span)> {
+    let attribute = input
+        .attrs
+        .iter()
+        .find(|attribute| attribute.path().is_ident("template"))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                &input.ident,
+                "missing #[template(path = \"...\")] or #[template(source = \"...\")] attribute",
+            )
+        })?;
+    let arguments =
+        attribute.parse_args_with(Punctuated::<syn::MetaNameValue, Token![,]>::parse_terminated)?;
+    let [argument] = arguments.iter().collect::<Vec<_>>()[..] else {
+        return Err(syn::Error::new_spanned(
+            attribute,
+            "expected either path = \"...\" or source = \"...\"",
+        ));
+    };
+    let value = match &argument.value {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(value),
+            ..
+        }) => value,
+        value => return Err(syn::Error::new_spanned(value, "expected a string literal")),
+    };
+    if argument.path.is_ident("source") {
+        return Ok((value.value(), quote!(#value), value.span()));
+    }
+    if !argument.path.is_ident("path") {
+        return Err(syn::Error::new_spanned(
+            &argument.path,
+            "expected either path or source",
+        ));
+    }
+    let directory = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = Path::new(&directory).join(value.value());
+    let text = fs::read_to_string(&path).map_err(|error| {
+        syn::Error::new(
+            value.span(),
+            format!("cannot read {}: {}", path.display(), error),
+        )
+    })?;
+    let path = path.to_string_lossy();
+    // `include_str!` makes the build depend on the file, so editing it triggers a new check.
+    Ok((text, quote!(include_str!(#path)), value.span()))
+}
+
+fn parse_template(text: &str, span: Span) -> syn::Result<Template> {
+    Template::parse(text).map_err(|error| syn::Error::new(span, error.to_string()))
+}
+
+/// Checks that every variable the template reads is given a value, unless its front matter
+/// gives it a default.
+fn check_variables(
+    template: &Template,
+    names: &BTreeSet<String>,
+    span: Span,
+    kind: &str,
+) -> syn::Result<()> {
+    let defaults = &template.front_matter().defaults;
+    match template
+        .variables()
+        .into_iter()
+        .find(|variable| !names.contains(variable) && !defaults.contains_key(variable))
+    {
+        Some(variable) => Err(syn::Error::new(
+            span,
+            format!(
+                "variable `{}` of the template has no matching {}",
+                variable, kind
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn insert_value(key: &str, value: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    quote! {
+        __context.insert(
+            #key.to_string(),
+            ::kata_template_engine::__private::serde_json::to_value(#value)
+                .expect(concat!("value ", #key, " cannot be converted to JSON")),
+        );
+    }
+}
+
+/// The name of the template variable matching an identifier, without the `r#` of raw ones.
+fn field_name(ident: &Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+}
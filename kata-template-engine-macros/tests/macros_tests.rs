@@ -0,0 +1,79 @@
+use kata_template_engine::TemplateContext;
+use kata_template_engine_macros::{template, TemplateContext};
+use serde_json::json;
+
+#[derive(TemplateContext)]
+#[template(path = "tests/templates/order.txt")]
+struct Order {
+    name: String,
+    items: Vec<serde_json::Value>,
+}
+
+#[derive(TemplateContext)]
+#[template(source = "{% if admin %}Welcome back, ${name}{% else %}Hi ${name}{% endif %}")]
+struct Greeting<'a> {
+    name: &'a str,
+    admin: bool,
+    #[allow(dead_code)]
+    last_login: Option<String>,
+}
+
+#[test]
+fn given_named_values_it_renders_the_template() {
+    let user = json!({"name": "John", "surname": "Doe"});
+    let parsed_template = template!(
+        "Hello, ${name} ${surname}!",
+        name = user["name"],
+        surname = "Doe"
+    )
+    .unwrap();
+    assert_eq!(parsed_template.text, "Hello, John Doe!");
+    assert!(parsed_template.warnings.is_empty());
+}
+
+#[test]
+fn given_values_used_in_expressions_it_renders_them_with_their_types() {
+    let parsed_template = template!(
+        "${price * quantity} for ${len(items)} items",
+        price = 3,
+        quantity = 4,
+        items = ["a", "b"]
+    )
+    .unwrap();
+    assert_eq!(parsed_template.text, "12 for 2 items");
+}
+
+#[test]
+fn given_a_struct_deriving_template_context_it_renders_its_template_file() {
+    let order = Order {
+        name: "John".to_string(),
+        items: vec![json!({"title": "Book", "price": 12})],
+    };
+    let parsed_template = order.render().unwrap();
+    assert_eq!(parsed_template.text, "Hello, John!\n- Book (12 €)\n");
+}
+
+#[test]
+fn given_a_struct_with_fields_the_template_does_not_read_it_renders_its_inline_template() {
+    let greeting = Greeting {
+        name: "John",
+        admin: true,
+        last_login: None,
+    };
+    assert_eq!(greeting.render().unwrap().text, "Welcome back, John");
+    assert_eq!(greeting.to_context()["last_login"], json!(null));
+}
+
+#[derive(TemplateContext)]
+#[template(source = "---\ndefaults:\n  greet: Hello\n---\n${greet}, ${name}")]
+struct DefaultGreeting {
+    name: String,
+}
+
+#[test]
+fn given_a_front_matter_default_the_struct_does_not_need_a_field_for_it() {
+    let greeting = DefaultGreeting {
+        name: "John".to_string(),
+    };
+    assert_eq!(greeting.render().unwrap().text, "Hello, John");
+}
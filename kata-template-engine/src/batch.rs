@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::{Renderer, Template, TemplateError};
+
+/// The format of a file of records, one context per record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// One JSON object per line. Empty lines are skipped.
+    JsonLines,
+    /// A header row with the variable names followed by one row per record, with as many
+    /// fields as the header.
+    Csv,
+}
+
+/// Where the texts rendered for each record are written.
+pub enum BatchOutput<'a> {
+    /// All the texts, one after the other, in the order of the records.
+    Stream(&'a mut dyn Write),
+    /// One file per record inside `directory`, named by rendering `path` with the record.
+    /// Files that already exist are only overwritten if `force` is set, and a record whose
+    /// path is the path of an earlier record of the batch fails.
+    Files {
+        directory: PathBuf,
+        path: Template,
+        force: bool,
+    },
+}
+
+/// A warning or a failure of a single record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordIssue {
+    /// The number of the record, starting at 1.
+    pub record: usize,
+    pub message: String,
+}
+
+/// The outcome of a batch: how many records were rendered and what went wrong with the others.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BatchReport {
+    pub rendered: usize,
+    pub warnings: Vec<RecordIssue>,
+    pub failures: Vec<RecordIssue>,
+}
+
+/// Renders a template once per record of a JSON Lines or CSV file.
+///
+/// A record that cannot be read or rendered is reported as a failure in the `BatchReport` and
+/// the batch goes on with the next record. The warnings of every render are reported along
+/// with the number of their record.
+///
+/// # Errors
+///
+/// This function returns an error only if writing to a `BatchOutput::Stream` fails, since the
+/// outputs of the following records would be lost too.
+pub fn render_batch(
+    renderer: &Renderer,
+    template: &Template,
+    records: impl Read,
+    format: RecordFormat,
+    mut output: BatchOutput,
+) -> Result<BatchReport, TemplateError> {
+    let mut report = BatchReport::default();
+    // The record that wrote each file, so that later records do not overwrite it.
+    let mut written = HashMap::new();
+    for (index, record) in read_records(records, format).enumerate() {
+        let number = index + 1;
+        let failure = |message: String| RecordIssue {
+            record: number,
+            message,
+        };
+        let context = match record {
+            Ok(context) => context,
+            Err(message) => {
+                report.failures.push(failure(message));
+                continue;
+            }
+        };
+        let parsed_template = match renderer.render(template, &context) {
+            Ok(parsed_template) => parsed_template,
+            Err(error) => {
+                report.failures.push(failure(error.to_string()));
+                continue;
+            }
+        };
+        match &mut output {
+            BatchOutput::Stream(writer) => writer
+                .write_all(parsed_template.text.as_bytes())
+                .map_err(TemplateError::IoError)?,
+            BatchOutput::Files {
+                directory,
+                path,
+                force,
+            } => {
+                let file = match output_file(renderer, directory, path, &context) {
+                    Ok(file) => file,
+                    Err(message) => {
+                        report.failures.push(failure(message));
+                        continue;
+                    }
+                };
+                if let Some(record) = written.get(&file) {
+                    report.failures.push(failure(format!(
+                        "Output file {} is already written by record {}",
+                        file.display(),
+                        record
+                    )));
+                    continue;
+                }
+                if !*force && file.exists() {
+                    report.failures.push(failure(format!(
+                        "Output file {} already exists",
+                        file.display()
+                    )));
+                    continue;
+                }
+                if let Err(message) = write_file(&file, &parsed_template.text) {
+                    report.failures.push(failure(message));
+                    continue;
+                }
+                written.insert(file, number);
+            }
+        }
+        report.rendered += 1;
+        report.warnings.extend(
+            parsed_template
+                .warnings
+                .into_iter()
+                .map(|warning| RecordIssue {
+                    record: number,
+                    message: warning,
+                }),
+        );
+    }
+    Ok(report)
+}
+
+/// Renders the path of the output file of a record, inside `directory`.
+fn output_file(
+    renderer: &Renderer,
+    directory: &Path,
+    path: &Template,
+    context: &Value,
+) -> Result<PathBuf, String> {
+    let rendered_path = renderer
+        .render(path, context)
+        .map_err(|error| format!("Cannot render the output path: {}", error))?;
+    if let Some(warning) = rendered_path.warnings.first() {
+        return Err(format!("Cannot render the output path: {}", warning));
+    }
+    let relative = Path::new(&rendered_path.text);
+    if relative.is_absolute() || relative.components().any(|c| c == Component::ParentDir) {
+        return Err(format!(
+            "Output path {} is outside the output directory",
+            rendered_path.text
+        ));
+    }
+    Ok(directory.join(relative))
+}
+
+fn write_file(file: &Path, text: &str) -> Result<(), String> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    fs::write(file, text).map_err(|error| format!("Cannot write {}: {}", file.display(), error))
+}
+
+/// Reads the records of a file as JSON contexts, lazily so that big files are not loaded at once.
+pub fn read_records<'r>(
+    records: impl Read + 'r,
+    format: RecordFormat,
+) -> Box<dyn Iterator<Item = Result<Value, String>> + 'r> {
+    match format {
+        RecordFormat::JsonLines => Box::new(
+            BufReader::new(records)
+                .lines()
+                .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+                .map(|line| {
+                    let line = line.map_err(|error| error.to_string())?;
+                    match serde_json::from_str::<Value>(&line) {
+                        Ok(Value::Object(entries)) => Ok(Value::Object(entries)),
+                        Ok(_) => Err("Record is not a JSON object".to_string()),
+                        Err(error) => Err(format!("JSON error: {}", error)),
+                    }
+                }),
+        ),
+        RecordFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .flexible(true)
+                .from_reader(records);
+            let headers = reader.headers().cloned();
+            Box::new(reader.into_records().map(move |row| {
+                let headers = headers
+                    .as_ref()
+                    .map_err(|error| format!("CSV error: {}", error))?;
+                let row = row.map_err(|error| format!("CSV error: {}", error))?;
+                if row.len() != headers.len() {
+                    return Err(format!(
+                        "CSV error: record has {} fields, but the header has {}",
+                        row.len(),
+                        headers.len()
+                    ));
+                }
+                let entries: Map<String, Value> = headers
+                    .iter()
+                    .zip(row.iter())
+                    .map(|(header, field)| (header.to_string(), Value::from(field)))
+                    .collect();
+                Ok(Value::Object(entries))
+            }))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Template, TemplateError};
+
+/// The version of the layout of cache files, changed whenever compiled templates are stored
+/// differently.
+pub const CACHE_FORMAT_VERSION: u32 = 2;
+
+/// The first bytes of every cache file.
+const MAGIC: &[u8; 4] = b"KTPL";
+
+/// Compiled templates stored in a compact binary file, so that tools registering hundreds of
+/// templates do not parse them again at every start.
+///
+/// The file starts with a header holding `CACHE_FORMAT_VERSION` and the version of the engine,
+/// and a file written by any other version is ignored. Each template is stored along with a
+/// hash of its text, and is parsed again as soon as its text changes:
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use kata_template_engine::{cache::TemplateCache, Renderer, TemplateError};
+/// let mut cache = TemplateCache::load(Path::new("templates.cache"))?;
+/// let mut renderer = Renderer::new();
+/// renderer.add_cached_template("greeting", "Hello, ${name}", &mut cache)?;
+/// if cache.is_modified() {
+///     cache.save(Path::new("templates.cache"))?;
+/// }
+/// # Ok::<(), TemplateError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct TemplateCache {
+    entries: BTreeMap<String, CachedTemplate>,
+    /// Whether templates were compiled since the cache was loaded.
+    modified: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedTemplate {
+    /// The hash of the text the template was compiled from.
+    hash: u64,
+    template: Template,
+}
+
+impl TemplateCache {
+    pub fn new() -> TemplateCache {
+        TemplateCache::default()
+    }
+
+    /// Loads the cache file at `path`. A missing file, a file written by another version of
+    /// the format or of the engine, and a damaged file all give an empty cache, filled again as
+    /// templates are compiled.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::IoError` if the file exists but cannot be read.
+    pub fn load(path: &Path) -> Result<TemplateCache, TemplateError> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(TemplateCache::from_bytes(&bytes).unwrap_or_default()),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(TemplateCache::new()),
+            Err(error) => Err(TemplateError::IoError(error)),
+        }
+    }
+
+    /// Reads a cache from the bytes of a cache file, or returns `None` if they were written by
+    /// another version of the format or of the engine, or are damaged.
+    pub fn from_bytes(bytes: &[u8]) -> Option<TemplateCache> {
+        let rest = bytes.strip_prefix(MAGIC)?;
+        let (format_version, rest) = rest.split_first_chunk::<4>()?;
+        if u32::from_le_bytes(*format_version) != CACHE_FORMAT_VERSION {
+            return None;
+        }
+        let (length, rest) = rest.split_first_chunk::<2>()?;
+        let (engine_version, body) = rest.split_at_checked(u16::from_le_bytes(*length).into())?;
+        if engine_version != env!("CARGO_PKG_VERSION").as_bytes() {
+            return None;
+        }
+        let entries = ciborium::from_reader(body).ok()?;
+        Some(TemplateCache {
+            entries,
+            modified: false,
+        })
+    }
+
+    /// Writes the header and the compiled templates of the cache, as stored in cache files.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let engine_version = env!("CARGO_PKG_VERSION").as_bytes();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(CACHE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend((engine_version.len() as u16).to_le_bytes());
+        bytes.extend(engine_version);
+        ciborium::into_writer(&self.entries, &mut bytes).expect("writing to a vector");
+        bytes
+    }
+
+    /// Writes the cache file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::IoError` if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<(), TemplateError> {
+        fs::write(path, self.to_bytes()).map_err(TemplateError::IoError)
+    }
+
+    /// Whether templates were compiled since the cache was created or loaded, so that it is
+    /// worth saving.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Returns the template compiled from `template_text` under `name`, from the cache if it
+    /// holds one compiled from the same text, or else parsed and added to the cache.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the errors of `Template::parse` if the text cannot be parsed.
+    pub fn compile(&mut self, name: &str, template_text: &str) -> Result<Template, TemplateError> {
+        let hash = fnv1a(template_text.as_bytes());
+        if let Some(cached) = self.entries.get(name).filter(|cached| cached.hash == hash) {
+            return Ok(cached.template.clone());
+        }
+        let template = Template::parse(template_text)?;
+        self.entries.insert(
+            name.to_string(),
+            CachedTemplate {
+                hash,
+                template: template.clone(),
+            },
+        );
+        self.modified = true;
+        Ok(template)
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike the hasher of the standard library is the same on
+/// every platform and version of Rust.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
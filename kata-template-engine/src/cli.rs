@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use serde_json::Value;
+
+use crate::batch::{read_records, render_batch, BatchOutput, RecordFormat};
+use crate::coverage::Coverage;
+use crate::scaffold::{self, ScaffoldOptions};
+use crate::snapshot::{test_snapshots, SnapshotOptions};
+use crate::watch::{include_path, RenderWatch, POLL_INTERVAL};
+use crate::{Renderer, Template, TemplateError};
+
+const USAGE: &str = "Usage:
+  template render <template> [<variables.json>] [--source-map <source map.json>]
+                  [--expand] [--indent] [--env] [--watch]
+  template batch <template> <records.jsonl|records.csv> [--format jsonl|csv]
+                 [--output-dir <directory> --output-path <path template> [--force]]
+  template scaffold <template directory> <output directory> [<variables.json>]
+                    [--force] [--skip-binary]
+  template test <template directory> [--update]
+  template coverage <template> <records.jsonl|records.csv> [--format jsonl|csv]
+                    [--report text|json]";
+
+/// The options of each command, the other ones being rejected.
+const RENDER_OPTIONS: [&str; 5] = ["--source-map", "--expand", "--indent", "--env", "--watch"];
+const BATCH_OPTIONS: [&str; 4] = ["--format", "--output-dir", "--output-path", "--force"];
+const SCAFFOLD_OPTIONS: [&str; 2] = ["--force", "--skip-binary"];
+const TEST_OPTIONS: [&str; 1] = ["--update"];
+const COVERAGE_OPTIONS: [&str; 2] = ["--format", "--report"];
+
+/// Options that take a value, as in `--format csv`. Any other option is a flag.
+const VALUE_OPTIONS: [&str; 5] = [
+    "--format",
+    "--output-dir",
+    "--output-path",
+    "--report",
+    "--source-map",
+];
+
+enum CliError {
+    Usage(String),
+    Failed(String),
+}
+
+struct Arguments {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Arguments {
+    fn parse(args: &[String], known_options: &[&str]) -> Result<Arguments, CliError> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+            } else if !known_options.contains(&arg.as_str()) {
+                return Err(CliError::Usage(format!("Unknown option {}", arg)));
+            } else if VALUE_OPTIONS.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("Missing value for {}", arg)))?;
+                options.insert(arg.clone(), Some(value.clone()));
+            } else {
+                options.insert(arg.clone(), None);
+            }
+        }
+        Ok(Arguments {
+            positional,
+            options,
+        })
+    }
+
+    fn value(&self, option: &str) -> Option<&str> {
+        self.options.get(option).and_then(|value| value.as_deref())
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<&str, CliError> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| CliError::Usage(format!("Missing {}", name)))
+    }
+}
+
+/// Runs the `template` command line with `args`, not including the program name, and returns
+/// the exit code: 0 on success, 1 if rendering failed and 2 if the arguments are wrong.
+pub fn run(args: &[String], stdout: &mut dyn Write, stderr: &mut dyn Write) -> i32 {
+    let result = match args.first().map(String::as_str) {
+        Some("render") => Arguments::parse(&args[1..], &RENDER_OPTIONS)
+            .and_then(|args| render(&args, stdout, stderr)),
+        Some("batch") => Arguments::parse(&args[1..], &BATCH_OPTIONS)
+            .and_then(|args| batch(&args, stdout, stderr)),
+        Some("scaffold") => Arguments::parse(&args[1..], &SCAFFOLD_OPTIONS)
+            .and_then(|args| scaffold(&args, stdout, stderr)),
+        Some("test") => {
+            Arguments::parse(&args[1..], &TEST_OPTIONS).and_then(|args| test(&args, stdout, stderr))
+        }
+        Some("coverage") => Arguments::parse(&args[1..], &COVERAGE_OPTIONS)
+            .and_then(|args| coverage(&args, stdout, stderr)),
+        Some("help") | Some("--help") => {
+            let _ = writeln!(stdout, "{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(CliError::Usage(format!("Unknown command {}", command))),
+        None => Err(CliError::Usage("Missing command".to_string())),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(CliError::Failed(message)) => {
+            let _ = writeln!(stderr, "error: {}", message);
+            1
+        }
+        Err(CliError::Usage(message)) => {
+            let _ = writeln!(stderr, "error: {}\n{}", message, USAGE);
+            2
+        }
+    }
+}
+
+fn render(
+    args: &Arguments,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> Result<(), CliError> {
+    let template_path = args.positional(0, "template")?;
+    if args.options.contains_key("--watch") {
+        if args.options.contains_key("--source-map") {
+            return Err(CliError::Usage(
+                "--watch and --source-map cannot go together".to_string(),
+            ));
+        }
+        watch(args, template_path, stdout, stderr)
+    }
+    let context = match args.positional.get(1) {
+        Some(path) => read_variables(path)?,
+        None => Value::Object(Default::default()),
+    };
+    let mut renderer = render_renderer(args);
+    let parsed_template = match args.value("--source-map") {
+        Some(source_map_path) => {
+            // Registering the template under its path names it in the source map.
+            renderer
+                .add_template(template_path, &read_file(Path::new(template_path))?)
+                .map_err(|error| CliError::Failed(format!("{}: {}", template_path, error)))?;
+            let (parsed_template, source_map) = renderer
+                .render_template_with_source_map(template_path, &context)
+                .map_err(|error| CliError::Failed(error.to_string()))?;
+            fs::write(source_map_path, source_map.to_json()).map_err(|error| {
+                CliError::Failed(format!("Cannot write {}: {}", source_map_path, error))
+            })?;
+            parsed_template
+        }
+        None => renderer
+            .render(&read_template(template_path)?, &context)
+            .map_err(|error| CliError::Failed(error.to_string()))?,
+    };
+    write!(stdout, "{}", parsed_template.text)
+        .map_err(|error| CliError::Failed(error.to_string()))?;
+    for warning in parsed_template.warnings {
+        let _ = writeln!(stderr, "warning: {}", warning);
+    }
+    Ok(())
+}
+
+fn render_renderer(args: &Arguments) -> Renderer {
+    let mut renderer = Renderer::new();
+    renderer.set_recursive_expansion(args.options.contains_key("--expand"));
+    renderer.set_value_indentation(args.options.contains_key("--indent"));
+    if args.options.contains_key("--env") {
+        renderer.enable_env();
+    }
+    renderer
+}
+
+/// Renders the template again whenever one of the files it read changes, printing the output
+/// of the first render and then the diff of each output from the previous one, until stopped.
+fn watch(
+    args: &Arguments,
+    template_path: &str,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> ! {
+    let variables_path = args.positional.get(1).map(Path::new);
+    let mut watch = RenderWatch::new(Path::new(template_path), variables_path);
+    loop {
+        // A new renderer forgets the includes that were removed since the previous render.
+        match watch.render(&mut render_renderer(args)) {
+            Ok(render) => {
+                match render.diff {
+                    None => {
+                        let _ = write!(stdout, "{}", render.text);
+                    }
+                    Some(diff) if diff.is_empty() => {
+                        let _ = writeln!(stderr, "Output unchanged");
+                    }
+                    Some(diff) => {
+                        let _ = write!(stdout, "{}", diff);
+                    }
+                }
+                for warning in render.warnings {
+                    let _ = writeln!(stderr, "warning: {}", warning);
+                }
+            }
+            Err(error) => {
+                let _ = writeln!(stderr, "error: {}", error);
+            }
+        }
+        let _ = stdout.flush();
+        let _ = writeln!(stderr, "Watching for changes...");
+        while watch.changed().is_empty() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+fn batch(args: &Arguments, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), CliError> {
+    let template = read_template(args.positional(0, "template")?)?;
+    let (records, format) = open_records(args)?;
+    let renderer = Renderer::new();
+    let output = match (args.value("--output-dir"), args.value("--output-path")) {
+        (Some(directory), Some(path)) => BatchOutput::Files {
+            directory: PathBuf::from(directory),
+            path: Template::parse(path).map_err(|error| CliError::Failed(error.to_string()))?,
+            force: args.options.contains_key("--force"),
+        },
+        (None, None) => BatchOutput::Stream(stdout),
+        _ => {
+            return Err(CliError::Usage(
+                "--output-dir and --output-path go together".to_string(),
+            ))
+        }
+    };
+    let report = render_batch(&renderer, &template, records, format, output)
+        .map_err(|error| CliError::Failed(error.to_string()))?;
+    for warning in &report.warnings {
+        let _ = writeln!(
+            stderr,
+            "warning: record {}: {}",
+            warning.record, warning.message
+        );
+    }
+    for failure in &report.failures {
+        let _ = writeln!(
+            stderr,
+            "error: record {}: {}",
+            failure.record, failure.message
+        );
+    }
+    let _ = writeln!(
+        stderr,
+        "Rendered {} records, {} warnings, {} failures",
+        report.rendered,
+        report.warnings.len(),
+        report.failures.len()
+    );
+    if !report.failures.is_empty() {
+        return Err(CliError::Failed(format!(
+            "{} records failed",
+            report.failures.len()
+        )));
+    }
+    Ok(())
+}
+
+fn scaffold(
+    args: &Arguments,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> Result<(), CliError> {
+    let source = args.positional(0, "template directory")?;
+    let destination = args.positional(1, "output directory")?;
+    let context = match args.positional.get(2) {
+        Some(path) => read_variables(path)?,
+        None => Value::Object(Default::default()),
+    };
+    let options = ScaffoldOptions {
+        force: args.options.contains_key("--force"),
+        skip_binary_files: args.options.contains_key("--skip-binary"),
+    };
+    let report = scaffold::scaffold(
+        &Renderer::new(),
+        Path::new(source),
+        Path::new(destination),
+        &context,
+        options,
+    )
+    .map_err(|error| match error {
+        TemplateError::OutputExists { .. } => {
+            CliError::Failed(format!("{} (use --force to overwrite)", error))
+        }
+        error => CliError::Failed(error.to_string()),
+    })?;
+    for path in report.rendered.iter().chain(&report.copied) {
+        let _ = writeln!(stdout, "{}", path.display());
+    }
+    for warning in &report.warnings {
+        let _ = writeln!(stderr, "warning: {}", warning);
+    }
+    Ok(())
+}
+
+fn test(args: &Arguments, stdout: &mut dyn Write, stderr: &mut dyn Write) -> Result<(), CliError> {
+    let directory = args.positional(0, "template directory")?;
+    let options = SnapshotOptions {
+        update: args.options.contains_key("--update"),
+    };
+    let report = test_snapshots(&mut Renderer::new(), Path::new(directory), options)
+        .map_err(|error| CliError::Failed(error.to_string()))?;
+    for failure in &report.failures {
+        let _ = writeln!(
+            stdout,
+            "FAILED {}\n{}",
+            failure.snapshot.display(),
+            failure.message
+        );
+    }
+    for path in &report.updated {
+        let _ = writeln!(stdout, "updated {}", path.display());
+    }
+    for warning in &report.warnings {
+        let _ = writeln!(stderr, "warning: {}", warning);
+    }
+    let _ = writeln!(
+        stderr,
+        "{} passed, {} failed, {} updated",
+        report.passed.len(),
+        report.failures.len(),
+        report.updated.len()
+    );
+    if !report.failures.is_empty() {
+        return Err(CliError::Failed(format!(
+            "{} snapshots failed (use --update to accept the new output)",
+            report.failures.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Renders the template once per record and reports which of its regions, and of the regions
+/// of the templates it includes, were never rendered.
+fn coverage(
+    args: &Arguments,
+    stdout: &mut dyn Write,
+    stderr: &mut dyn Write,
+) -> Result<(), CliError> {
+    let template_path = Path::new(args.positional(0, "template")?);
+    let (records, format) = open_records(args)?;
+    let json = match args.value("--report") {
+        Some("json") => true,
+        Some("text") | None => false,
+        Some(report) => return Err(CliError::Usage(format!("Unknown report {}", report))),
+    };
+    let mut renderer = Renderer::new();
+    let mut coverage = Coverage::new();
+    // The template is registered under its path, and the templates it includes are read from
+    // the files next to it, as in watch mode.
+    let name = template_path.display().to_string();
+    let mut pending = vec![(name.clone(), template_path.to_path_buf())];
+    while let Some((name, path)) = pending.pop() {
+        if coverage.contains(&name) {
+            continue;
+        }
+        let includes = coverage
+            .add_template_file(&mut renderer, &name, &path)
+            .map_err(|error| CliError::Failed(error.to_string()))?;
+        for include in includes {
+            let path = include_path(template_path, &include);
+            pending.push((include, path));
+        }
+    }
+    let mut failures = 0;
+    for (index, record) in read_records(records, format).enumerate() {
+        let rendered = record.and_then(|context| {
+            coverage
+                .render_template(&renderer, &name, &context)
+                .map_err(|error| error.to_string())
+        });
+        if let Err(message) = rendered {
+            let _ = writeln!(stderr, "error: record {}: {}", index + 1, message);
+            failures += 1;
+        }
+    }
+    let report = match json {
+        true => format!("{:#}\n", coverage.to_json()),
+        false => coverage.to_text(),
+    };
+    write!(stdout, "{}", report).map_err(|error| CliError::Failed(error.to_string()))?;
+    if failures > 0 {
+        return Err(CliError::Failed(format!("{} records failed", failures)));
+    }
+    Ok(())
+}
+
+/// Opens the records file given after the template, in the format of `--format` or else of its
+/// extension.
+fn open_records(args: &Arguments) -> Result<(File, RecordFormat), CliError> {
+    let records_path = args.positional(1, "records")?;
+    let format = match args.value("--format") {
+        Some("jsonl") => RecordFormat::JsonLines,
+        Some("csv") => RecordFormat::Csv,
+        Some(format) => return Err(CliError::Usage(format!("Unknown format {}", format))),
+        None if records_path.ends_with(".csv") => RecordFormat::Csv,
+        None => RecordFormat::JsonLines,
+    };
+    let records = File::open(records_path)
+        .map_err(|error| CliError::Failed(format!("Cannot read {}: {}", records_path, error)))?;
+    Ok((records, format))
+}
+
+fn read_template(path: &str) -> Result<Template, CliError> {
+    let text = read_file(Path::new(path))?;
+    Template::parse(&text).map_err(|error| CliError::Failed(format!("{}: {}", path, error)))
+}
+
+fn read_variables(path: &str) -> Result<Value, CliError> {
+    let text = read_file(Path::new(path))?;
+    serde_json::from_str(&text)
+        .map_err(|error| CliError::Failed(format!("{}: JSON error: {}", path, error)))
+}
+
+fn read_file(path: &Path) -> Result<String, CliError> {
+    fs::read_to_string(path)
+        .map_err(|error| CliError::Failed(format!("Cannot read {}: {}", path.display(), error)))
+}
@@ -0,0 +1,33 @@
+use serde_json::Value;
+
+use crate::{ParsedTemplate, Renderer, Template, TemplateError};
+
+/// A type whose fields are the variables of a template, usually implemented with
+/// `#[derive(TemplateContext)]` from the `kata-template-engine-macros` crate, which checks at
+/// compile time that the template parses and that every variable it reads is a field.
+pub trait TemplateContext {
+    /// The text of the template.
+    const TEMPLATE: &'static str;
+
+    /// Returns the context the template is rendered with.
+    fn to_context(&self) -> Value;
+
+    /// Renders the template with the default renderer.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the errors of `Renderer::render`, such as a function that fails.
+    fn render(&self) -> Result<ParsedTemplate, TemplateError> {
+        self.render_with(&Renderer::new())
+    }
+
+    /// Renders the template with `renderer`, to use its registered functions, templates and
+    /// limits.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the errors of `Renderer::render`.
+    fn render_with(&self, renderer: &Renderer) -> Result<ParsedTemplate, TemplateError> {
+        renderer.render(&Template::parse(Self::TEMPLATE)?, &self.to_context())
+    }
+}
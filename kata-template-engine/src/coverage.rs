@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::template::{Node, Selector};
+use crate::{ParsedTemplate, Renderer, Span, Template, TemplateError};
+
+/// The longest excerpt of the text of a region shown in reports, in characters.
+const MAX_EXCERPT_LENGTH: usize = 40;
+
+/// A part of a template that coverage follows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    /// A `${...}` placeholder or a Mustache `{{...}}` variable.
+    Placeholder,
+    /// The block of an `{% if %}` or `{% elif %}` tag, up to the next tag of the same block.
+    Branch,
+    /// The block of an `{% else %}` tag, up to the end of its `{% endif %}` tag.
+    Else,
+    /// The block of a `{% for %}` loop, hit once per element.
+    Loop,
+    /// A Mustache `{{#section}}` block, hit once per element, or a `{{^section}}` block, hit
+    /// when the section is empty.
+    Section,
+    /// A case of an ICU message, such as `one` or `=0`.
+    Case(String),
+}
+
+impl fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RegionKind::Placeholder => write!(f, "placeholder"),
+            RegionKind::Branch => write!(f, "branch"),
+            RegionKind::Else => write!(f, "else branch"),
+            RegionKind::Loop => write!(f, "loop"),
+            RegionKind::Section => write!(f, "section"),
+            RegionKind::Case(selector) => write!(f, "case `{}`", selector),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub kind: RegionKind,
+    /// The span of the region in the template text. The cases of an ICU message share the span
+    /// of the message.
+    pub span: Span,
+    /// The line and the column where the region starts, counting from 1.
+    pub line: usize,
+    pub column: usize,
+    /// The start of the first line of the region, as shown in reports.
+    pub excerpt: String,
+    /// How many times the region was rendered.
+    pub hits: usize,
+}
+
+/// The regions of a template file and how many times each was rendered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// The name the template is registered under.
+    pub name: String,
+    pub path: PathBuf,
+    /// The regions, in the order of the text.
+    pub regions: Vec<Region>,
+    /// The index of each region in `regions`.
+    index: HashMap<(Span, RegionKind), usize>,
+}
+
+impl FileCoverage {
+    /// Returns the regions that were never rendered, in the order of the text.
+    pub fn uncovered(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(|region| region.hits == 0)
+    }
+}
+
+/// Records which placeholders, branches, loops, sections and message cases of template files
+/// are rendered across many renders, so that the parts real data never reaches can be found:
+///
+/// ```no_run
+/// # use std::path::Path;
+/// # use serde_json::json;
+/// # use kata_template_engine::{coverage::Coverage, Renderer, TemplateError};
+/// let mut renderer = Renderer::new();
+/// let mut coverage = Coverage::new();
+/// coverage.add_template_file(&mut renderer, "invoice", Path::new("invoice.txt"))?;
+/// for context in [json!({"total": 10}), json!({"total": 0, "overdue": true})] {
+///     coverage.render_template(&renderer, "invoice", &context)?;
+/// }
+/// print!("{}", coverage.to_text());
+/// # Ok::<(), TemplateError>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: BTreeMap<String, FileCoverage>,
+    renders: usize,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Reads the template file at `path`, registers it on the renderer under `name`, as a
+    /// Mustache template if its extension is `.mustache`, and follows the coverage of its
+    /// regions. Returns the names of the templates it includes, so that their files can be
+    /// added too.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the file cannot be read, or if the template cannot be
+    /// parsed, wrapped in `TemplateError::InFile`.
+    pub fn add_template_file(
+        &mut self,
+        renderer: &mut Renderer,
+        name: &str,
+        path: &Path,
+    ) -> Result<Vec<String>, TemplateError> {
+        let in_file = |error| TemplateError::InFile {
+            path: path.to_path_buf(),
+            error: Box::new(error),
+        };
+        let text =
+            fs::read_to_string(path).map_err(|error| in_file(TemplateError::IoError(error)))?;
+        let template = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("mustache") => Template::parse_mustache(&text),
+            _ => Template::parse(&text),
+        }
+        .map_err(in_file)?;
+        let mut found = Vec::new();
+        collect_regions(template.nodes(), &mut found);
+        found.sort_by_key(|(_, span)| span.start);
+        let regions: Vec<Region> = found
+            .into_iter()
+            .map(|(kind, span)| {
+                let line_start = text[..span.start].rfind('\n').map_or(0, |index| index + 1);
+                Region {
+                    kind,
+                    span,
+                    line: text[..line_start].matches('\n').count() + 1,
+                    column: text[line_start..span.start].chars().count() + 1,
+                    excerpt: excerpt(&text[span.start..span.end]),
+                    hits: 0,
+                }
+            })
+            .collect();
+        let index = regions
+            .iter()
+            .enumerate()
+            .map(|(index, region)| ((region.span, region.kind.clone()), index))
+            .collect();
+        let includes = template.includes();
+        renderer.insert_template(name, template);
+        self.files.insert(
+            name.to_string(),
+            FileCoverage {
+                name: name.to_string(),
+                path: path.to_path_buf(),
+                regions,
+                index,
+            },
+        );
+        Ok(includes)
+    }
+
+    /// Whether a template file was added under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.files.contains_key(name)
+    }
+
+    /// Renders the template registered under `name` like `Renderer::render_template`, counting
+    /// the regions it renders. The regions rendered before a render fails are counted too.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the same errors as `Renderer::render_template`.
+    pub fn render_template(
+        &mut self,
+        renderer: &Renderer,
+        name: &str,
+        context: &Value,
+    ) -> Result<ParsedTemplate, TemplateError> {
+        self.renders += 1;
+        renderer.render_template_with_coverage(name, context, self)
+    }
+
+    /// The number of renders, failed ones included.
+    pub fn renders(&self) -> usize {
+        self.renders
+    }
+
+    /// Returns the added template files, sorted by name.
+    pub fn files(&self) -> Vec<&FileCoverage> {
+        self.files.values().collect()
+    }
+
+    /// Writes a report listing, for each template file, how many of its regions were rendered
+    /// and where the others are.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Coverage of {} renders\n", self.renders);
+        for file in self.files.values() {
+            let covered = file.regions.len() - file.uncovered().count();
+            text.push_str(&format!(
+                "{}: {} of {} regions covered ({}%)\n",
+                file.path.display(),
+                covered,
+                file.regions.len(),
+                percent(covered, file.regions.len())
+            ));
+            for region in file.uncovered() {
+                text.push_str(&format!(
+                    "  {}:{}:{}: {} never rendered: {}\n",
+                    file.path.display(),
+                    region.line,
+                    region.column,
+                    region.kind,
+                    region.excerpt
+                ));
+            }
+        }
+        text
+    }
+
+    /// Returns the report of `to_text` as JSON, with the span of each region never rendered.
+    pub fn to_json(&self) -> Value {
+        let files: Vec<Value> = self
+            .files
+            .values()
+            .map(|file| {
+                let uncovered: Vec<Value> = file
+                    .uncovered()
+                    .map(|region| {
+                        json!({
+                            "kind": region.kind.to_string(),
+                            "line": region.line,
+                            "column": region.column,
+                            "span": {"start": region.span.start, "end": region.span.end},
+                            "excerpt": region.excerpt,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "name": file.name,
+                    "path": file.path.display().to_string(),
+                    "regions": file.regions.len(),
+                    "covered": file.regions.len() - uncovered.len(),
+                    "uncovered": uncovered,
+                })
+            })
+            .collect();
+        json!({"renders": self.renders, "files": files})
+    }
+
+    /// Counts a render of a region of the template registered under `template`. Regions of
+    /// templates that were not added, such as messages of catalogs, are not followed.
+    pub(crate) fn hit(&mut self, template: &str, span: Span, kind: RegionKind) {
+        let Some(file) = self.files.get_mut(template) else {
+            return;
+        };
+        if let Some(index) = file.index.get(&(span, kind)) {
+            file.regions[*index].hits += 1;
+        }
+    }
+}
+
+fn collect_regions(nodes: &[Node], regions: &mut Vec<(RegionKind, Span)>) {
+    for node in nodes {
+        match node {
+            Node::Placeholder { span, .. } | Node::Variable { span, .. } => {
+                regions.push((RegionKind::Placeholder, *span))
+            }
+            Node::If {
+                branches,
+                otherwise,
+                span,
+            } => {
+                for branch in branches {
+                    regions.push((RegionKind::Branch, branch.span));
+                    collect_regions(&branch.nodes, regions);
+                }
+                if let (Some(otherwise), Some(last)) = (otherwise, branches.last()) {
+                    regions.push((RegionKind::Else, Span::new(last.span.end, span.end)));
+                    collect_regions(otherwise, regions);
+                }
+            }
+            Node::For { nodes, span, .. } => {
+                regions.push((RegionKind::Loop, *span));
+                collect_regions(nodes, regions);
+            }
+            Node::Section { nodes, span, .. } => {
+                regions.push((RegionKind::Section, *span));
+                collect_regions(nodes, regions);
+            }
+            Node::Choice { cases, span, .. } => {
+                for case in cases {
+                    regions.push((RegionKind::Case(selector_name(&case.selector)), *span));
+                    collect_regions(&case.nodes, regions);
+                }
+            }
+            Node::Text(..) | Node::Include { .. } | Node::Message { .. } | Node::Partial { .. } => {
+            }
+        }
+    }
+}
+
+pub(crate) fn selector_name(selector: &Selector) -> String {
+    match selector {
+        Selector::Exact(number) => format!("={}", number),
+        Selector::Category(category) => category.to_string(),
+        Selector::Key(key) => key.clone(),
+    }
+}
+
+fn excerpt(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(MAX_EXCERPT_LENGTH) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+fn percent(covered: usize, total: usize) -> usize {
+    match total {
+        0 => 100,
+        total => covered * 100 / total,
+    }
+}
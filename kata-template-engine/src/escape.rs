@@ -0,0 +1,198 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+/// Quotes the text of a placeholder value for the language of the output, as in
+/// `renderer.register_escaper("latex", LatexEscaper)`.
+pub trait Escaper: Send + Sync {
+    /// Returns the text written to the output in place of `text`.
+    fn escape(&self, text: &str) -> String;
+}
+
+/// How the values of placeholders are escaped before they are written to the output, chosen
+/// by the `escape` of the front matter of a template or by a placeholder ending with an
+/// `escape` filter, as in `${path | escape("shell")}`. The literal text of the template is
+/// never escaped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Escape {
+    /// Values are written as they are.
+    #[default]
+    None,
+    /// `&`, `<`, `>`, `"` and `'` are replaced by HTML character references.
+    Html,
+    /// Values are single-quoted for POSIX shells, as in `'it'\''s'`.
+    Shell,
+    /// Values are written as standard SQL string literals, as in `'it''s'`.
+    Sql,
+    /// Values are escaped to be written between the quotes of a JSON string.
+    Json,
+    /// Values are written as CSV fields, quoted when they contain a comma, a quote or a line
+    /// break.
+    Csv,
+    /// Values are percent-encoded to be written as a component of a URL.
+    Url,
+    /// Values are escaped by the escaper registered on the renderer under this name.
+    Custom(String),
+}
+
+impl Escape {
+    /// The name of the escaper, as written in front matters and `escape` filters.
+    pub fn name(&self) -> &str {
+        match self {
+            Escape::None => "none",
+            Escape::Html => "html",
+            Escape::Shell => "shell",
+            Escape::Sql => "sql",
+            Escape::Json => "json",
+            Escape::Csv => "csv",
+            Escape::Url => "url",
+            Escape::Custom(name) => name,
+        }
+    }
+
+    /// The built-in escaper of this escape, or `None` for a custom one.
+    pub(crate) fn builtin(&self) -> Option<&'static dyn Escaper> {
+        match self {
+            Escape::None => Some(&NoEscaper),
+            Escape::Html => Some(&HtmlEscaper),
+            Escape::Shell => Some(&ShellEscaper),
+            Escape::Sql => Some(&SqlEscaper),
+            Escape::Json => Some(&JsonEscaper),
+            Escape::Csv => Some(&CsvEscaper),
+            Escape::Url => Some(&UrlEscaper),
+            Escape::Custom(_) => None,
+        }
+    }
+}
+
+impl From<String> for Escape {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "none" => Escape::None,
+            "html" => Escape::Html,
+            "shell" => Escape::Shell,
+            "sql" => Escape::Sql,
+            "json" => Escape::Json,
+            "csv" => Escape::Csv,
+            "url" => Escape::Url,
+            _ => Escape::Custom(name),
+        }
+    }
+}
+
+impl From<Escape> for String {
+    fn from(escape: Escape) -> Self {
+        escape.name().to_string()
+    }
+}
+
+struct NoEscaper;
+
+impl Escaper for NoEscaper {
+    fn escape(&self, text: &str) -> String {
+        text.to_string()
+    }
+}
+
+struct HtmlEscaper;
+
+impl Escaper for HtmlEscaper {
+    fn escape(&self, text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+struct ShellEscaper;
+
+impl Escaper for ShellEscaper {
+    fn escape(&self, text: &str) -> String {
+        format!("'{}'", text.replace('\'', r"'\''"))
+    }
+}
+
+struct SqlEscaper;
+
+impl Escaper for SqlEscaper {
+    fn escape(&self, text: &str) -> String {
+        format!("'{}'", text.replace('\'', "''"))
+    }
+}
+
+struct JsonEscaper;
+
+impl Escaper for JsonEscaper {
+    fn escape(&self, text: &str) -> String {
+        let quoted = serde_json::Value::from(text).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    }
+}
+
+struct CsvEscaper;
+
+impl Escaper for CsvEscaper {
+    fn escape(&self, text: &str) -> String {
+        if text.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+struct UrlEscaper;
+
+impl Escaper for UrlEscaper {
+    fn escape(&self, text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for byte in text.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    escaped.push(byte as char)
+                }
+                byte => write!(escaped, "%{:02X}", byte).expect("writing to a string"),
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(escape: Escape, text: &str) -> String {
+        escape.builtin().unwrap().escape(text)
+    }
+
+    #[test]
+    fn given_each_builtin_escaper_it_quotes_the_special_characters_of_its_language() {
+        assert_eq!(escape(Escape::Shell, "it's $HOME"), r"'it'\''s $HOME'");
+        assert_eq!(escape(Escape::Sql, "O'Brien"), "'O''Brien'");
+        assert_eq!(escape(Escape::Json, "say \"hi\"\n"), r#"say \"hi\"\n"#);
+        assert_eq!(escape(Escape::Csv, "plain"), "plain");
+        assert_eq!(escape(Escape::Csv, "a, \"b\""), "\"a, \"\"b\"\"\"");
+        assert_eq!(escape(Escape::Url, "a b/ñ?x=1"), "a%20b%2F%C3%B1%3Fx%3D1");
+    }
+
+    #[test]
+    fn given_a_name_it_converts_to_the_matching_escape_and_back() {
+        assert_eq!(Escape::from("csv".to_string()), Escape::Csv);
+        assert_eq!(
+            Escape::from("latex".to_string()),
+            Escape::Custom("latex".to_string())
+        );
+        assert_eq!(String::from(Escape::Url), "url");
+    }
+}
@@ -0,0 +1,576 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::limits::MAX_NESTING_DEPTH;
+use crate::{Span, TemplateError};
+
+/// An expression written inside a `${...}` placeholder or a `{% if %}` condition.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Expression {
+    Literal(Value, Span),
+    /// A variable, or a dotted path into a variable such as `user.address.city`.
+    Variable(String, Span),
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    Unary {
+        operator: UnaryOperator,
+        operand: Box<Expression>,
+        span: Span,
+    },
+    Binary {
+        operator: BinaryOperator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Literal(_, span) => *span,
+            Expression::Variable(_, span) => *span,
+            Expression::Call { span, .. } => *span,
+            Expression::Unary { span, .. } => *span,
+            Expression::Binary { span, .. } => *span,
+        }
+    }
+
+    /// Returns the variables and dotted paths the expression reads, in order of appearance.
+    pub(crate) fn variable_paths(&self) -> Vec<&str> {
+        let mut paths = Vec::new();
+        self.collect_variable_paths(&mut paths);
+        paths
+    }
+
+    fn collect_variable_paths<'e>(&'e self, paths: &mut Vec<&'e str>) {
+        match self {
+            Expression::Literal(..) => {}
+            Expression::Variable(path, _) => paths.push(path),
+            Expression::Call { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_variable_paths(paths);
+                }
+            }
+            Expression::Unary { operand, .. } => operand.collect_variable_paths(paths),
+            Expression::Binary { left, right, .. } => {
+                left.collect_variable_paths(paths);
+                right.collect_variable_paths(paths);
+            }
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Negate => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "not"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match *self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Remainder => "%",
+            BinaryOperator::Concat => "~",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessOrEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterOrEqual => ">=",
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Literal(Value),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 16] = [
+    "==", "!=", "<=", ">=", "<", ">", "(", ")", ",", "+", "-", "*", "/", "%", "~", "|",
+];
+
+const OR: [(&str, BinaryOperator); 1] = [("or", BinaryOperator::Or)];
+const AND: [(&str, BinaryOperator); 1] = [("and", BinaryOperator::And)];
+const COMPARISON: [(&str, BinaryOperator); 6] = [
+    ("==", BinaryOperator::Equal),
+    ("!=", BinaryOperator::NotEqual),
+    ("<", BinaryOperator::Less),
+    ("<=", BinaryOperator::LessOrEqual),
+    (">", BinaryOperator::Greater),
+    (">=", BinaryOperator::GreaterOrEqual),
+];
+const CONCAT: [(&str, BinaryOperator); 1] = [("~", BinaryOperator::Concat)];
+const ADDITIVE: [(&str, BinaryOperator); 2] =
+    [("+", BinaryOperator::Add), ("-", BinaryOperator::Subtract)];
+const MULTIPLICATIVE: [(&str, BinaryOperator); 3] = [
+    ("*", BinaryOperator::Multiply),
+    ("/", BinaryOperator::Divide),
+    ("%", BinaryOperator::Remainder),
+];
+
+/// Parses the source of a placeholder or a condition into an `Expression`.
+///
+/// `offset` is the position of `source` inside the template text, so that the spans of the
+/// expression point into the template and not into the placeholder.
+pub(crate) fn parse_expression(source: &str, offset: usize) -> Result<Expression, TemplateError> {
+    let tokens = tokenize(source, offset)?;
+    let end = offset + source.len();
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end,
+        depth: 0,
+    };
+    let expression = parser.parse_or()?;
+    if let Some((token, span)) = parser.peek() {
+        return Err(syntax_error(
+            format!("unexpected {}", describe(token)),
+            *span,
+        ));
+    }
+    Ok(expression)
+}
+
+fn tokenize(source: &str, offset: usize) -> Result<Vec<(Token, Span)>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, character)) = chars.peek() {
+        let span = |end: usize| Span::new(offset + start, offset + end);
+        if character.is_whitespace() {
+            chars.next();
+        } else if character == '"' {
+            chars.next();
+            let mut text = String::new();
+            let mut closed = false;
+            while let Some((position, character)) = chars.next() {
+                match character {
+                    '"' => {
+                        tokens.push((
+                            Token::Literal(Value::String(text.clone())),
+                            span(position + 1),
+                        ));
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => text.push('\n'),
+                        Some((_, 't')) => text.push('\t'),
+                        Some((_, escaped)) => text.push(escaped),
+                        None => break,
+                    },
+                    _ => text.push(character),
+                }
+            }
+            if !closed {
+                return Err(syntax_error(
+                    "unclosed string".to_string(),
+                    span(source.len()),
+                ));
+            }
+        } else if character.is_ascii_digit() {
+            let mut end = start;
+            while let Some(&(position, digit)) = chars.peek() {
+                if !digit.is_ascii_digit() && digit != '.' {
+                    break;
+                }
+                end = position + digit.len_utf8();
+                chars.next();
+            }
+            let number = parse_number(&source[start..end])
+                .ok_or_else(|| syntax_error("invalid number".to_string(), span(end)))?;
+            tokens.push((Token::Literal(Value::Number(number)), span(end)));
+        } else if character.is_alphabetic() || character == '_' {
+            let mut end = start;
+            while let Some(&(position, letter)) = chars.peek() {
+                let continues_path = letter == '.'
+                    && source[position + 1..]
+                        .chars()
+                        .next()
+                        .is_some_and(|next| next.is_alphanumeric() || next == '_');
+                if !letter.is_alphanumeric() && letter != '_' && !continues_path {
+                    break;
+                }
+                end = position + letter.len_utf8();
+                chars.next();
+            }
+            let token = match &source[start..end] {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "null" => Token::Literal(Value::Null),
+                "and" => Token::Symbol("and"),
+                "or" => Token::Symbol("or"),
+                "not" => Token::Symbol("not"),
+                identifier => Token::Identifier(identifier.to_string()),
+            };
+            tokens.push((token, span(end)));
+        } else if let Some(symbol) = SYMBOLS
+            .iter()
+            .find(|symbol| source[start..].starts_with(**symbol))
+        {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((Token::Symbol(symbol), span(start + symbol.len())));
+        } else {
+            return Err(syntax_error(
+                format!("unexpected character `{}`", character),
+                span(start + character.len_utf8()),
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<Number> {
+    if text.contains('.') {
+        text.parse::<f64>().ok().and_then(Number::from_f64)
+    } else {
+        text.parse::<i64>().ok().map(Number::from)
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    end: usize,
+    /// The number of expressions being parsed within each other.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, Span)> {
+        self.tokens.get(self.position)
+    }
+
+    fn peek_symbol(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some((Token::Symbol(symbol), _)) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    fn next(&mut self) -> Result<(Token, Span), TemplateError> {
+        let token = self.tokens.get(self.position).cloned().ok_or_else(|| {
+            syntax_error(
+                "unexpected end of expression".to_string(),
+                Span::new(self.end, self.end),
+            )
+        })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &'static str) -> Result<Span, TemplateError> {
+        match self.peek().cloned() {
+            Some((Token::Symbol(symbol), span)) if symbol == expected => {
+                self.position += 1;
+                Ok(span)
+            }
+            Some((token, span)) => Err(syntax_error(
+                format!("expected `{}`, found {}", expected, describe(&token)),
+                span,
+            )),
+            None => Err(syntax_error(
+                format!("expected `{}`, found end of expression", expected),
+                Span::new(self.end, self.end),
+            )),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, TemplateError> {
+        self.nested(|parser| parser.parse_binary(&OR, Parser::parse_and))
+    }
+
+    /// Parses an expression nested within the one being parsed, as in parentheses, arguments
+    /// and operands of unary operators, failing beyond `MAX_NESTING_DEPTH`.
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Parser) -> Result<Expression, TemplateError>,
+    ) -> Result<Expression, TemplateError> {
+        if self.depth == MAX_NESTING_DEPTH {
+            let span = self
+                .peek()
+                .map_or(Span::new(self.end, self.end), |(_, span)| *span);
+            return Err(syntax_error(
+                format!("expression nested deeper than {} levels", MAX_NESTING_DEPTH),
+                span,
+            ));
+        }
+        self.depth += 1;
+        let expression = parse(self);
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_binary(&AND, Parser::parse_not)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_unary(
+            "not",
+            UnaryOperator::Not,
+            Parser::parse_not,
+            Parser::parse_comparison,
+        )
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_binary(&COMPARISON, Parser::parse_concat)
+    }
+
+    fn parse_concat(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_binary(&CONCAT, Parser::parse_additive)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_binary(&ADDITIVE, Parser::parse_multiplicative)
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_binary(&MULTIPLICATIVE, Parser::parse_negate)
+    }
+
+    fn parse_negate(&mut self) -> Result<Expression, TemplateError> {
+        self.parse_unary(
+            "-",
+            UnaryOperator::Negate,
+            Parser::parse_negate,
+            Parser::parse_filtered,
+        )
+    }
+
+    /// Parses a left-associative sequence of operands joined by any of `operators`.
+    fn parse_binary(
+        &mut self,
+        operators: &[(&str, BinaryOperator)],
+        operand: fn(&mut Parser) -> Result<Expression, TemplateError>,
+    ) -> Result<Expression, TemplateError> {
+        let mut left = operand(self)?;
+        while let Some((_, operator)) = self
+            .peek_symbol()
+            .and_then(|symbol| operators.iter().find(|(candidate, _)| *candidate == symbol))
+        {
+            self.position += 1;
+            let right = operand(self)?;
+            let span = Span::new(left.span().start, right.span().end);
+            left = Expression::Binary {
+                operator: *operator,
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(
+        &mut self,
+        symbol: &str,
+        operator: UnaryOperator,
+        operand: fn(&mut Parser) -> Result<Expression, TemplateError>,
+        otherwise: fn(&mut Parser) -> Result<Expression, TemplateError>,
+    ) -> Result<Expression, TemplateError> {
+        match self.peek().cloned() {
+            Some((Token::Symbol(found), span)) if found == symbol => {
+                self.position += 1;
+                let operand = self.nested(operand)?;
+                Ok(Expression::Unary {
+                    operator,
+                    span: Span::new(span.start, operand.span().end),
+                    operand: Box::new(operand),
+                })
+            }
+            _ => otherwise(self),
+        }
+    }
+
+    /// Parses an operand followed by any number of filters. `value | name(arguments)` calls the
+    /// function `name` with `value` as its first argument, and `value | name` is
+    /// `value | name()`. Filters bind tighter than any operator.
+    fn parse_filtered(&mut self) -> Result<Expression, TemplateError> {
+        let mut expression = self.parse_primary()?;
+        while self.peek_symbol() == Some("|") {
+            self.position += 1;
+            let (name, span) = match self.next()? {
+                (Token::Identifier(name), span) if !name.contains('.') => (name, span),
+                (token, span) => {
+                    return Err(syntax_error(
+                        format!("expected the name of a filter, found {}", describe(&token)),
+                        span,
+                    ))
+                }
+            };
+            let mut arguments = vec![expression];
+            let mut end = span.end;
+            if self.peek_symbol() == Some("(") {
+                self.position += 1;
+                arguments.extend(self.parse_arguments()?);
+                end = self.expect(")")?.end;
+            }
+            expression = Expression::Call {
+                name,
+                span: Span::new(arguments[0].span().start, end),
+                arguments,
+            };
+        }
+        Ok(expression)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, TemplateError> {
+        let (token, span) = self.next()?;
+        match token {
+            Token::Literal(value) => Ok(Expression::Literal(value, span)),
+            Token::Identifier(name) => {
+                if self.peek_symbol() == Some("(") {
+                    self.position += 1;
+                    let arguments = self.parse_arguments()?;
+                    let end = self.expect(")")?;
+                    Ok(Expression::Call {
+                        name,
+                        arguments,
+                        span: Span::new(span.start, end.end),
+                    })
+                } else {
+                    Ok(Expression::Variable(name, span))
+                }
+            }
+            Token::Symbol("(") => {
+                let expression = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            token => Err(syntax_error(
+                format!("unexpected {}", describe(&token)),
+                span,
+            )),
+        }
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Expression>, TemplateError> {
+        let mut arguments = Vec::new();
+        if self.peek_symbol() == Some(")") {
+            return Ok(arguments);
+        }
+        loop {
+            arguments.push(self.parse_or()?);
+            if self.peek_symbol() != Some(",") {
+                return Ok(arguments);
+            }
+            self.position += 1;
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Identifier(name) => format!("`{}`", name),
+        Token::Literal(value) => format!("`{}`", value),
+        Token::Symbol(symbol) => format!("`{}`", symbol),
+    }
+}
+
+fn syntax_error(message: String, span: Span) -> TemplateError {
+    TemplateError::Syntax { message, span }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_a_function_call_it_parses_its_arguments() {
+        let expression = parse_expression(r#"range(1, "a")"#, 2).unwrap();
+        assert_eq!(
+            expression,
+            Expression::Call {
+                name: "range".to_string(),
+                arguments: vec![
+                    Expression::Literal(Value::from(1), Span::new(8, 9)),
+                    Expression::Literal(Value::from("a"), Span::new(11, 14)),
+                ],
+                span: Span::new(2, 15),
+            }
+        );
+    }
+
+    #[test]
+    fn given_arithmetic_operators_it_respects_their_precedence() {
+        let expression = parse_expression("1 + 2 * 3", 0).unwrap();
+        let Expression::Binary {
+            operator, right, ..
+        } = expression
+        else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(operator, BinaryOperator::Add);
+        assert!(matches!(
+            *right,
+            Expression::Binary {
+                operator: BinaryOperator::Multiply,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn given_a_dotted_path_it_parses_a_single_variable() {
+        let expression = parse_expression("user.name", 0).unwrap();
+        assert_eq!(
+            expression,
+            Expression::Variable("user.name".to_string(), Span::new(0, 9))
+        );
+    }
+
+    #[test]
+    fn given_an_unclosed_call_it_raises_a_syntax_error() {
+        let error = parse_expression("len(items", 0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Syntax error at 9..9: expected `)`, found end of expression"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use regex::Regex;
+
+use crate::escape::Escape;
+use crate::expression::Expression;
+use crate::template::{Node, Template};
+use crate::TemplateError;
+
+/// The most values `extract` tries for the variables of a template writing some variables
+/// several times, whose matching can take time exponential in the number of variables.
+pub const MAX_EXTRACTION_STEPS: usize = 1_000_000;
+
+impl Template {
+    /// Recovers the values of the variables from a text rendered with this template.
+    ///
+    /// The literal text of the template must appear as is in `rendered`, and each `${variable}`
+    /// placeholder captures the text between its surrounding literals, the shortest text first.
+    /// A variable written several times captures the same text at each place, so `${a}-${a}`
+    /// extracts `x-y` from `x-y-x-y`. Placeholders of expressions without variables, such as
+    /// `${now("%Y")}`, must match too, but capture nothing.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::TextDoesNotMatch` if the text cannot have been
+    /// rendered with this template, `TemplateError::NotExtractable` if the template contains
+    /// blocks, includes or messages, or placeholders whose values are transformed by a format
+    /// specification, a function, a filter or an escaper, and
+    /// `TemplateError::ExtractionLimitExceeded` if matching variables written several times
+    /// takes more than `MAX_EXTRACTION_STEPS` tries.
+    pub fn extract(&self, rendered: &str) -> Result<HashMap<String, String>, TemplateError> {
+        self.extract_with_patterns(rendered, &HashMap::new())
+    }
+
+    /// Recovers the values of the variables from a text rendered with this template, like
+    /// `extract`, constraining the values of some variables to match a regular expression.
+    ///
+    /// # Arguments
+    ///
+    /// * `rendered` - A string slice that holds the rendered text.
+    /// * `patterns` - A `HashMap` where the keys are variable names and the values are the
+    ///   regular expressions their values must match. Variables without a pattern match any text.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the same errors as `extract`, and `TemplateError::InvalidPattern`
+    /// if a pattern is not a valid regular expression.
+    pub fn extract_with_patterns(
+        &self,
+        rendered: &str,
+        patterns: &HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, TemplateError> {
+        let not_extractable = |kind: &str, span| TemplateError::NotExtractable {
+            kind: kind.to_string(),
+            span,
+        };
+        let escaped = self.front_matter().escape != Escape::None;
+        let mut parts = Vec::new();
+        for node in self.nodes() {
+            match node {
+                Node::Text(literal, _) => parts.push(Part::Literal(literal)),
+                Node::Placeholder {
+                    expression,
+                    spec,
+                    escape,
+                    indent,
+                    span,
+                    ..
+                } if spec.is_some()
+                    || *indent
+                    || escape
+                        .as_ref()
+                        .map_or(escaped, |escape| *escape != Escape::None)
+                    || !matches!(expression, Expression::Variable(..))
+                        && !expression.variable_paths().is_empty() =>
+                {
+                    return Err(not_extractable("placeholder", *span))
+                }
+                Node::Placeholder {
+                    expression: Expression::Variable(name, _),
+                    ..
+                } => {
+                    let pattern = match patterns.get(name) {
+                        Some(pattern) => {
+                            Regex::new(pattern).map_err(|error| TemplateError::InvalidPattern {
+                                variable: name.clone(),
+                                message: error.to_string(),
+                            })?;
+                            Some(pattern.as_str())
+                        }
+                        None => None,
+                    };
+                    parts.push(Part::Value {
+                        variable: Some(name),
+                        pattern,
+                    });
+                }
+                Node::Placeholder { .. } => parts.push(Part::Value {
+                    variable: None,
+                    pattern: None,
+                }),
+                Node::If { span, .. }
+                | Node::For { span, .. }
+                | Node::Include { span, .. }
+                | Node::Choice { span, .. }
+                | Node::Message { span, .. }
+                | Node::Variable { span, .. }
+                | Node::Section { span, .. }
+                | Node::Partial { span, .. } => return Err(not_extractable("block", *span)),
+            }
+        }
+        let mut variables: Vec<_> = parts
+            .iter()
+            .filter_map(|part| match part {
+                Part::Value { variable, .. } => *variable,
+                Part::Literal(_) => None,
+            })
+            .collect();
+        let count = variables.len();
+        variables.sort();
+        variables.dedup();
+        let values = match variables.len() == count {
+            true => extract_with_regex(&parts, rendered)?,
+            false => extract_with_backtracking(&parts, rendered)?,
+        };
+        values.ok_or(TemplateError::TextDoesNotMatch)
+    }
+}
+
+/// A part of a template matched against a rendered text.
+enum Part<'t> {
+    Literal(&'t str),
+    /// A placeholder, capturing its text if it is a variable, whose text must match `pattern`
+    /// if it has one.
+    Value {
+        variable: Option<&'t String>,
+        pattern: Option<&'t str>,
+    },
+}
+
+/// Matches the parts in a single regular expression, for templates writing each variable once.
+fn extract_with_regex(
+    parts: &[Part],
+    rendered: &str,
+) -> Result<Option<HashMap<String, String>>, TemplateError> {
+    let mut expression = String::from("(?s)^");
+    let mut captured = Vec::new();
+    for part in parts {
+        match part {
+            Part::Literal(literal) => expression.push_str(&regex::escape(literal)),
+            Part::Value {
+                variable: Some(name),
+                pattern,
+            } => {
+                let pattern = pattern.unwrap_or(".*?");
+                expression.push_str(&format!("(?P<v{}>{})", captured.len(), pattern));
+                captured.push(*name);
+            }
+            Part::Value { variable: None, .. } => expression.push_str(".*?"),
+        }
+    }
+    expression.push('$');
+    let regex = Regex::new(&expression).map_err(|error| TemplateError::InvalidPattern {
+        variable: String::new(),
+        message: error.to_string(),
+    })?;
+    let Some(captures) = regex.captures(rendered) else {
+        return Ok(None);
+    };
+    let values = captured
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let value = &captures[format!("v{}", index).as_str()];
+            (name.clone(), value.to_string())
+        })
+        .collect();
+    Ok(Some(values))
+}
+
+/// Matches the parts by trying every split of the text, shortest values first, so that a
+/// variable written several times captures the same text at each place, which the regular
+/// expressions of the `regex` crate cannot require.
+fn extract_with_backtracking(
+    parts: &[Part],
+    rendered: &str,
+) -> Result<Option<HashMap<String, String>>, TemplateError> {
+    let mut regexes = HashMap::new();
+    for part in parts {
+        if let Part::Value {
+            variable: Some(name),
+            pattern: Some(pattern),
+        } = part
+        {
+            let regex = Regex::new(&format!("(?s)^(?:{})$", pattern)).map_err(|error| {
+                TemplateError::InvalidPattern {
+                    variable: (*name).clone(),
+                    message: error.to_string(),
+                }
+            })?;
+            regexes.insert(name.as_str(), regex);
+        }
+    }
+    let mut later = vec![BTreeSet::new(); parts.len() + 1];
+    for (index, part) in parts.iter().enumerate().rev() {
+        later[index] = later[index + 1].clone();
+        if let Part::Value {
+            variable: Some(name),
+            ..
+        } = part
+        {
+            later[index].insert(name.as_str());
+        }
+    }
+    let mut backtracking = Backtracking {
+        parts,
+        regexes,
+        rendered,
+        later,
+        values: HashMap::new(),
+        failed: HashSet::new(),
+        steps: 0,
+    };
+    let matched = backtracking.matches(0, 0)?;
+    Ok(matched.then(|| {
+        backtracking
+            .values
+            .into_iter()
+            .map(|(name, (start, end))| (name.to_string(), rendered[start..end].to_string()))
+            .collect()
+    }))
+}
+
+/// The index of a part, the position in the text where it is matched, and the values of the
+/// variables written in it or after it that are already captured.
+type State = (usize, usize, Vec<(usize, usize)>);
+
+struct Backtracking<'p, 't> {
+    parts: &'p [Part<'t>],
+    regexes: HashMap<&'t str, Regex>,
+    rendered: &'p str,
+    /// The variables written in each part or after it.
+    later: Vec<BTreeSet<&'t str>>,
+    /// The byte ranges of the values captured so far.
+    values: HashMap<&'t str, (usize, usize)>,
+    /// The states that failed to match, so that each is tried once.
+    failed: HashSet<State>,
+    steps: usize,
+}
+
+impl<'p, 't> Backtracking<'p, 't> {
+    /// Whether the parts from `index` match the text from `position` to its end, given the
+    /// values already captured.
+    fn matches(&mut self, index: usize, position: usize) -> Result<bool, TemplateError> {
+        let bound = self.later[index]
+            .iter()
+            .filter_map(|name| self.values.get(name).copied())
+            .collect();
+        let state = (index, position, bound);
+        if self.failed.contains(&state) {
+            return Ok(false);
+        }
+        let matched = self.matches_part(index, position)?;
+        if !matched {
+            self.failed.insert(state);
+        }
+        Ok(matched)
+    }
+
+    fn matches_part(&mut self, index: usize, position: usize) -> Result<bool, TemplateError> {
+        let rest = &self.rendered[position..];
+        let Some(part) = self.parts.get(index) else {
+            return Ok(rest.is_empty());
+        };
+        let variable = match part {
+            Part::Literal(literal) => {
+                return Ok(rest.starts_with(literal)
+                    && self.matches(index + 1, position + literal.len())?);
+            }
+            Part::Value { variable, .. } => variable.map(String::as_str),
+        };
+        if let Some((start, end)) = variable.and_then(|name| self.values.get(name).copied()) {
+            return Ok(rest.starts_with(&self.rendered[start..end])
+                && self.matches(index + 1, position + end - start)?);
+        }
+        // A value followed by a literal can only end where the literal starts.
+        let next_literal = match self.parts.get(index + 1) {
+            Some(Part::Literal(literal)) => Some(*literal),
+            _ => None,
+        };
+        for (length, _) in rest.char_indices().chain([(rest.len(), ' ')]) {
+            self.steps += 1;
+            if self.steps > MAX_EXTRACTION_STEPS {
+                return Err(TemplateError::ExtractionLimitExceeded {
+                    limit: MAX_EXTRACTION_STEPS,
+                });
+            }
+            let value = &rest[..length];
+            let regex = variable.and_then(|name| self.regexes.get(name));
+            if next_literal.is_some_and(|literal| !rest[length..].starts_with(literal))
+                || regex.is_some_and(|regex| !regex.is_match(value))
+            {
+                continue;
+            }
+            if let Some(name) = variable {
+                self.values.insert(name, (position, position + length));
+            }
+            if self.matches(index + 1, position + length)? {
+                return Ok(true);
+            }
+            if let Some(name) = variable {
+                self.values.remove(name);
+            }
+        }
+        Ok(false)
+    }
+}
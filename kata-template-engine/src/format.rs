@@ -0,0 +1,428 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use serde_json::Value;
+
+use crate::function::{ArgType, Function};
+use crate::locale::normalize_locale;
+
+/// Where the symbol of a currency goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CurrencyPosition {
+    /// `€1,234.50`
+    Prefix,
+    /// `€ 1.234,50`
+    PrefixSpaced,
+    /// `1.234,50 €`
+    SuffixSpaced,
+}
+
+/// How a locale writes numbers, amounts of money and dates.
+struct LocaleFormat {
+    decimal: &'static str,
+    group: &'static str,
+    /// The fewest integer digits a number needs before its thousands are grouped.
+    grouping_from: usize,
+    currency: CurrencyPosition,
+    percent: &'static str,
+    months: [&'static str; 12],
+    /// The patterns of dates, where `{d}` and `{dd}` are the day, `{m}` and `{mm}` the month,
+    /// `{month}` its name, and `{yy}` and `{yyyy}` the year.
+    long_date: &'static str,
+    short_date: &'static str,
+}
+
+const ENGLISH: LocaleFormat = LocaleFormat {
+    decimal: ".",
+    group: ",",
+    grouping_from: 4,
+    currency: CurrencyPosition::Prefix,
+    percent: "%",
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    long_date: "{month} {d}, {yyyy}",
+    short_date: "{m}/{d}/{yy}",
+};
+
+const GERMAN: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: ".",
+    grouping_from: 4,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "\u{a0}%",
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    long_date: "{d}. {month} {yyyy}",
+    short_date: "{dd}.{mm}.{yy}",
+};
+
+const FRENCH: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: "\u{202f}",
+    grouping_from: 4,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "\u{202f}%",
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    long_date: "{d} {month} {yyyy}",
+    short_date: "{dd}/{mm}/{yyyy}",
+};
+
+const SPANISH: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: ".",
+    grouping_from: 5,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "\u{a0}%",
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    long_date: "{d} de {month} de {yyyy}",
+    short_date: "{d}/{m}/{yy}",
+};
+
+const ITALIAN: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: ".",
+    grouping_from: 4,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "%",
+    months: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    long_date: "{d} {month} {yyyy}",
+    short_date: "{dd}/{mm}/{yy}",
+};
+
+const PORTUGUESE: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: ".",
+    grouping_from: 4,
+    currency: CurrencyPosition::PrefixSpaced,
+    percent: "%",
+    months: [
+        "janeiro",
+        "fevereiro",
+        "março",
+        "abril",
+        "maio",
+        "junho",
+        "julho",
+        "agosto",
+        "setembro",
+        "outubro",
+        "novembro",
+        "dezembro",
+    ],
+    long_date: "{d} de {month} de {yyyy}",
+    short_date: "{dd}/{mm}/{yyyy}",
+};
+
+const DUTCH: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: ".",
+    grouping_from: 4,
+    currency: CurrencyPosition::PrefixSpaced,
+    percent: "%",
+    months: [
+        "januari",
+        "februari",
+        "maart",
+        "april",
+        "mei",
+        "juni",
+        "juli",
+        "augustus",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    long_date: "{d} {month} {yyyy}",
+    short_date: "{dd}-{mm}-{yyyy}",
+};
+
+const RUSSIAN: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: "\u{a0}",
+    grouping_from: 4,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "\u{a0}%",
+    months: [
+        "января",
+        "февраля",
+        "марта",
+        "апреля",
+        "мая",
+        "июня",
+        "июля",
+        "августа",
+        "сентября",
+        "октября",
+        "ноября",
+        "декабря",
+    ],
+    long_date: "{d} {month} {yyyy}\u{a0}г.",
+    short_date: "{dd}.{mm}.{yyyy}",
+};
+
+const POLISH: LocaleFormat = LocaleFormat {
+    decimal: ",",
+    group: "\u{a0}",
+    grouping_from: 5,
+    currency: CurrencyPosition::SuffixSpaced,
+    percent: "%",
+    months: [
+        "stycznia",
+        "lutego",
+        "marca",
+        "kwietnia",
+        "maja",
+        "czerwca",
+        "lipca",
+        "sierpnia",
+        "września",
+        "października",
+        "listopada",
+        "grudnia",
+    ],
+    long_date: "{d} {month} {yyyy}",
+    short_date: "{dd}.{mm}.{yyyy}",
+};
+
+const JAPANESE: LocaleFormat = LocaleFormat {
+    decimal: ".",
+    group: ",",
+    grouping_from: 4,
+    currency: CurrencyPosition::Prefix,
+    percent: "%",
+    months: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    long_date: "{yyyy}年{m}月{d}日",
+    short_date: "{yyyy}/{mm}/{dd}",
+};
+
+fn locale_format(locale: &str) -> Option<&'static LocaleFormat> {
+    let locale = normalize_locale(locale);
+    let language = locale.split('-').next().unwrap_or_default();
+    match language {
+        "en" => Some(&ENGLISH),
+        "de" => Some(&GERMAN),
+        "fr" => Some(&FRENCH),
+        "es" => Some(&SPANISH),
+        "it" => Some(&ITALIAN),
+        "pt" => Some(&PORTUGUESE),
+        "nl" => Some(&DUTCH),
+        "ru" => Some(&RUSSIAN),
+        "pl" => Some(&POLISH),
+        "ja" => Some(&JAPANESE),
+        _ => None,
+    }
+}
+
+/// Whether numbers, amounts of money and dates can be formatted the way `locale` does.
+pub(crate) fn has_locale_format(locale: &str) -> bool {
+    locale_format(locale).is_some()
+}
+
+/// Returns the formatting function called `name` for `locale`, if there is one: `number`,
+/// `currency`, `percent` or `date`.
+pub(crate) fn formatting_function(name: &str, locale: &str) -> Option<Box<dyn Function>> {
+    let kind = match name {
+        "number" => Formatting::Number,
+        "currency" => Formatting::Currency,
+        "percent" => Formatting::Percent,
+        "date" => Formatting::Date,
+        _ => return None,
+    };
+    Some(Box::new(LocaleFunction {
+        kind,
+        locale: locale.to_string(),
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Formatting {
+    /// `number(value)`: the number with grouped thousands and up to 3 decimals.
+    Number,
+    /// `currency(value, code)`: the amount with the symbol of an ISO 4217 currency code.
+    Currency,
+    /// `percent(value)`: the ratio as a rounded percentage, so that `0.25` is `25%`.
+    Percent,
+    /// `date(value, style)`: the ISO-8601 date or date-time in the `short` or `long` style.
+    Date,
+}
+
+struct LocaleFunction {
+    kind: Formatting,
+    locale: String,
+}
+
+impl Function for LocaleFunction {
+    fn params(&self) -> &[ArgType] {
+        match self.kind {
+            Formatting::Number | Formatting::Percent => &[ArgType::Number],
+            Formatting::Currency => &[ArgType::Number, ArgType::String],
+            Formatting::Date => &[ArgType::String, ArgType::String],
+        }
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, String> {
+        let format = locale_format(&self.locale)
+            .ok_or_else(|| format!("there are no formatting rules for locale {}", self.locale))?;
+        let text = match self.kind {
+            Formatting::Number => format_decimal(number(&args[0]), 0, 3, format),
+            Formatting::Currency => format_currency(number(&args[0]), &args[1], format),
+            Formatting::Percent => {
+                format_decimal(number(&args[0]) * 100.0, 0, 0, format) + format.percent
+            }
+            Formatting::Date => format_date(&args[0], &args[1], format)?,
+        };
+        Ok(Value::String(text))
+    }
+}
+
+fn number(value: &Value) -> f64 {
+    value.as_f64().unwrap_or_default()
+}
+
+/// Writes a number rounded to at most `maximum` decimals, keeping at least `minimum` of them.
+fn format_decimal(number: f64, minimum: usize, maximum: usize, format: &LocaleFormat) -> String {
+    let fixed = format!("{:.*}", maximum, number.abs());
+    let (integer, fraction) = fixed.split_once('.').unwrap_or((&fixed, ""));
+    let mut fraction = fraction.to_string();
+    while fraction.len() > minimum && fraction.ends_with('0') {
+        fraction.pop();
+    }
+    let mut text = String::new();
+    if number < 0.0 && fixed.chars().any(|digit| matches!(digit, '1'..='9')) {
+        text.push('-');
+    }
+    for (index, digit) in integer.chars().enumerate() {
+        let remaining = integer.len() - index;
+        if index > 0 && remaining % 3 == 0 && integer.len() >= format.grouping_from {
+            text.push_str(format.group);
+        }
+        text.push(digit);
+    }
+    if !fraction.is_empty() {
+        text.push_str(format.decimal);
+        text.push_str(&fraction);
+    }
+    text
+}
+
+fn format_currency(amount: f64, code: &Value, format: &LocaleFormat) -> String {
+    let code = code.as_str().unwrap_or_default().to_ascii_uppercase();
+    let (symbol, decimals) = match code.as_str() {
+        "EUR" => ("€", 2),
+        "USD" => ("$", 2),
+        "GBP" => ("£", 2),
+        "JPY" => ("¥", 0),
+        "BRL" => ("R$", 2),
+        "PLN" => ("zł", 2),
+        "RUB" => ("₽", 2),
+        code => (code, 2),
+    };
+    let number = format_decimal(amount, decimals, decimals, format);
+    match format.currency {
+        CurrencyPosition::Prefix => match number.strip_prefix('-') {
+            Some(number) => format!("-{}{}", symbol, number),
+            None => format!("{}{}", symbol, number),
+        },
+        CurrencyPosition::PrefixSpaced => format!("{}\u{a0}{}", symbol, number),
+        CurrencyPosition::SuffixSpaced => format!("{}\u{a0}{}", number, symbol),
+    }
+}
+
+fn format_date(value: &Value, style: &Value, format: &LocaleFormat) -> Result<String, String> {
+    let text = value.as_str().unwrap_or_default();
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d")
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|date| date.date_naive())
+        })
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f")
+                .ok()
+                .map(|date| date.date())
+        })
+        .ok_or_else(|| format!("{} is not an ISO-8601 date", text))?;
+    let pattern = match style.as_str() {
+        Some("long") => format.long_date,
+        Some("short") => format.short_date,
+        _ => {
+            return Err(format!(
+                "unknown date style {}, expected short or long",
+                style.as_str().unwrap_or_default()
+            ))
+        }
+    };
+    Ok(pattern
+        .replace("{dd}", &format!("{:02}", date.day()))
+        .replace("{d}", &date.day().to_string())
+        .replace("{mm}", &format!("{:02}", date.month()))
+        .replace("{month}", format.months[date.month0() as usize])
+        .replace("{m}", &date.month().to_string())
+        .replace("{yyyy}", &date.year().to_string())
+        .replace("{yy}", &format!("{:02}", date.year() % 100)))
+}
@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+
+use crate::limits::MAX_FORMAT_WIDTH;
+use crate::renderer::value_to_string;
+use crate::{Span, TemplateError};
+
+/// The format specification of a `${value:spec}` placeholder, following the grammar of Rust's
+/// `format!`: `[[fill]align][sign][0][width][.precision]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FormatSpec {
+    fill: char,
+    align: Option<Align>,
+    plus: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    fn from_char(character: char) -> Option<Align> {
+        match character {
+            '<' => Some(Align::Left),
+            '^' => Some(Align::Center),
+            '>' => Some(Align::Right),
+            _ => None,
+        }
+    }
+}
+
+impl FormatSpec {
+    /// Parses the text after the `:` of a placeholder, which starts at `offset` in the template.
+    pub(crate) fn parse(text: &str, offset: usize) -> Result<FormatSpec, TemplateError> {
+        let invalid = |message: String| TemplateError::Syntax {
+            message: format!("invalid format specification `{}`: {}", text, message),
+            span: Span::new(offset, offset + text.len()),
+        };
+        let mut spec = FormatSpec {
+            fill: ' ',
+            align: None,
+            plus: false,
+            zero: false,
+            width: 0,
+            precision: None,
+        };
+        let mut rest = text;
+        let mut characters = rest.chars();
+        if let (Some(fill), Some(align)) = (
+            characters.next(),
+            characters.next().and_then(Align::from_char),
+        ) {
+            spec.fill = fill;
+            spec.align = Some(align);
+            rest = characters.as_str();
+        } else if let Some(align) = rest.chars().next().and_then(Align::from_char) {
+            spec.align = Some(align);
+            rest = &rest[1..];
+        }
+        if let Some(after) = rest.strip_prefix('+') {
+            spec.plus = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix('-') {
+            rest = after;
+        }
+        if let Some(after) = rest.strip_prefix('0') {
+            spec.zero = true;
+            rest = after;
+        }
+        let bounded = |digits: &str, name: &str| match digits.parse::<usize>() {
+            Ok(number) if number <= MAX_FORMAT_WIDTH => Ok(number),
+            _ => Err(invalid(format!(
+                "{} is larger than {}",
+                name, MAX_FORMAT_WIDTH
+            ))),
+        };
+        let (width, after) = split_digits(rest);
+        if !width.is_empty() {
+            spec.width = bounded(width, "width")?;
+        }
+        rest = after;
+        if let Some(after) = rest.strip_prefix('.') {
+            let (precision, after) = split_digits(after);
+            if precision.is_empty() {
+                return Err(invalid("expected a precision after `.`".into()));
+            }
+            spec.precision = Some(bounded(precision, "precision")?);
+            rest = after;
+        }
+        match rest.chars().next() {
+            Some(character) => Err(invalid(format!("unexpected `{}`", character))),
+            None => Ok(spec),
+        }
+    }
+
+    /// Formats a value as `format!` would: numbers are rounded to the precision, signed with
+    /// `+` and padded with zeros after their sign, texts are truncated to the precision, and
+    /// values are aligned in the width, to the right for numbers and to the left otherwise.
+    ///
+    /// A text holding a JSON number, as CSV fields and form inputs do, is formatted as that
+    /// number when the spec has a precision, a `+` or a `0`, so that `${price:.2}` rounds
+    /// `"3.14159"` to `3.14` rather than truncating it.
+    pub(crate) fn apply(&self, value: &Value) -> String {
+        let parsed;
+        let value = match value {
+            Value::String(text) if self.precision.is_some() || self.plus || self.zero => {
+                match text.parse::<Number>() {
+                    Ok(number) => {
+                        parsed = Value::Number(number);
+                        &parsed
+                    }
+                    Err(_) => value,
+                }
+            }
+            value => value,
+        };
+        let text = match (value, self.precision) {
+            (Value::Number(number), Some(precision)) => {
+                format!("{:.*}", precision, number.as_f64().unwrap_or_default())
+            }
+            (Value::Number(_), None) => value_to_string(value),
+            (_, Some(precision)) => value_to_string(value).chars().take(precision).collect(),
+            (_, None) => value_to_string(value),
+        };
+        let Value::Number(_) = value else {
+            return self.pad(&text, Align::Left);
+        };
+        let (sign, digits) = match text.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None if self.plus => ("+", text.as_str()),
+            None => ("", text.as_str()),
+        };
+        if self.zero {
+            let length = sign.len() + digits.chars().count();
+            let zeros = "0".repeat(self.width.saturating_sub(length));
+            return format!("{}{}{}", sign, zeros, digits);
+        }
+        self.pad(&format!("{}{}", sign, digits), Align::Right)
+    }
+
+    fn pad(&self, text: &str, default: Align) -> String {
+        let padding = self.width.saturating_sub(text.chars().count());
+        let (before, after) = match self.align.unwrap_or(default) {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        let fill = |count| self.fill.to_string().repeat(count);
+        format!("{}{}{}", fill(before), text, fill(after))
+    }
+}
+
+/// Splits the leading decimal digits of a text from the rest.
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|character: char| !character.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn format(spec: &str, value: Value) -> String {
+        FormatSpec::parse(spec, 0).unwrap().apply(&value)
+    }
+
+    #[test]
+    fn given_specs_of_the_format_macro_it_formats_as_the_macro_does() {
+        assert_eq!(format(".2", json!(1.23456)), format!("{:.2}", 1.23456));
+        assert_eq!(format(">8", json!(42)), format!("{:>8}", 42));
+        assert_eq!(format("08", json!(-42)), format!("{:08}", -42));
+        assert_eq!(format("+.1", json!(2.25)), format!("{:+.1}", 2.25));
+        assert_eq!(format("*^9", json!("abc")), format!("{:*^9}", "abc"));
+        assert_eq!(format("6", json!("abc")), format!("{:6}", "abc"));
+        assert_eq!(format(".2", json!("abc")), format!("{:.2}", "abc"));
+        assert_eq!(format("<5", json!(7)), format!("{:<5}", 7));
+        assert_eq!(format(":>4", json!(7)), format!("{::>4}", 7));
+    }
+
+    #[test]
+    fn given_a_text_holding_a_number_and_a_numeric_spec_it_formats_the_number() {
+        assert_eq!(format(".2", json!("3.14159")), "3.14");
+        assert_eq!(format("+08.1", json!("-2.25")), format!("{:+08.1}", -2.25));
+        assert_eq!(format("05", json!("42")), "00042");
+        assert_eq!(format(">6", json!("42")), "    42");
+        assert_eq!(format("<6", json!("42")), "42    ");
+        assert_eq!(format(".2", json!("3.14 EUR")), "3.");
+        assert_eq!(format(".1", json!("NaN")), "N");
+    }
+
+    #[test]
+    fn given_an_invalid_spec_it_raises_a_syntax_error_on_the_spec() {
+        assert_eq!(
+            FormatSpec::parse(">8x", 10).unwrap_err().to_string(),
+            "Syntax error at 10..13: invalid format specification `>8x`: unexpected `x`"
+        );
+        assert_eq!(
+            FormatSpec::parse(".", 0).unwrap_err().to_string(),
+            "Syntax error at 0..1: invalid format specification `.`: expected a precision after `.`"
+        );
+    }
+}
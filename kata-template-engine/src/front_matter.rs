@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::escape::Escape;
+use crate::TemplateError;
+
+/// The settings of a template declared in a YAML block at its start, between two `---` lines:
+///
+/// ```yaml
+/// ---
+/// description: Reminder sent when an invoice is overdue
+/// required: [customer.name, total]
+/// defaults:
+///   currency: EUR
+/// escape: html
+/// metadata:
+///   owner: billing
+/// ---
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    /// What the template is for, shown by tools that list templates.
+    pub description: Option<String>,
+    /// Values of the variables the context does not define.
+    pub defaults: Map<String, Value>,
+    /// Variables or dotted paths that must be defined, by the context or the defaults.
+    pub required: Vec<String>,
+    /// How the values of placeholders are escaped.
+    pub escape: Escape,
+    /// Any other information about the template, left to the tools that read it.
+    pub metadata: Map<String, Value>,
+}
+
+/// Splits the front matter off a template text, returning it along with the offset where the
+/// body of the template starts.
+///
+/// A front matter is only recognized when the text starts with a `---` line. A text starting
+/// with a `\---` line has none, and its body starts after the backslash, so that templates of
+/// files starting with a `---` line, such as YAML documents, can be written.
+pub(crate) fn split_front_matter(text: &str) -> Result<(FrontMatter, usize), TemplateError> {
+    if text.starts_with("\\---\n") || text.starts_with("\\---\r\n") {
+        return Ok((FrontMatter::default(), 1));
+    }
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return Ok((FrontMatter::default(), 0));
+    };
+    let yaml_start = text.len() - rest.len();
+    let mut line_start = yaml_start;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            let yaml = &text[yaml_start..line_start];
+            let front_matter = if yaml.trim().is_empty() {
+                FrontMatter::default()
+            } else {
+                serde_yaml::from_str(yaml).map_err(|error| TemplateError::InvalidFrontMatter {
+                    message: error.to_string(),
+                })?
+            };
+            return Ok((front_matter, line_start + line.len()));
+        }
+        line_start += line.len();
+    }
+    Err(TemplateError::InvalidFrontMatter {
+        message: "missing closing `---` line".to_string(),
+    })
+}
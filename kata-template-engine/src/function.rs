@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use serde_json::Value;
+
+/// A helper that can be called from a placeholder, as in `${len(items)}`.
+///
+/// The renderer checks the number and the types of the arguments against `params` before
+/// calling the function, so `call` only receives arguments of the declared types.
+pub trait Function: Send + Sync {
+    /// The types of the arguments the function accepts, in order.
+    fn params(&self) -> &[ArgType];
+
+    /// Calls the function. An `Err` message is reported as `TemplateError::FunctionFailed`.
+    fn call(&self, args: &[Value]) -> Result<Value, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    Any,
+    String,
+    Number,
+    Integer,
+    Bool,
+    Array,
+    Object,
+}
+
+impl ArgType {
+    pub fn accepts(&self, value: &Value) -> bool {
+        match self {
+            ArgType::Any => true,
+            ArgType::String => value.is_string(),
+            ArgType::Number => value.is_number(),
+            ArgType::Integer => value.is_i64() || value.is_u64(),
+            ArgType::Bool => value.is_boolean(),
+            ArgType::Array => value.is_array(),
+            ArgType::Object => value.is_object(),
+        }
+    }
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArgType::Any => write!(f, "any"),
+            ArgType::String => write!(f, "string"),
+            ArgType::Number => write!(f, "number"),
+            ArgType::Integer => write!(f, "integer"),
+            ArgType::Bool => write!(f, "bool"),
+            ArgType::Array => write!(f, "array"),
+            ArgType::Object => write!(f, "object"),
+        }
+    }
+}
+
+/// Returns the name of the JSON type of a value, as used in error messages.
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(number) if number.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+pub(crate) fn builtin_functions() -> HashMap<String, Box<dyn Function>> {
+    let mut functions: HashMap<String, Box<dyn Function>> = HashMap::new();
+    functions.insert("now".to_string(), Box::new(Now));
+    functions.insert("len".to_string(), Box::new(Len));
+    functions.insert("range".to_string(), Box::new(Range));
+    functions
+}
+
+/// `now(format)`: the current local time formatted with a `strftime` format.
+struct Now;
+
+impl Function for Now {
+    fn params(&self) -> &[ArgType] {
+        &[ArgType::String]
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, String> {
+        let format = args[0].as_str().unwrap_or_default();
+        let mut text = String::new();
+        write!(text, "{}", chrono::Local::now().format(format))
+            .map_err(|_| format!("invalid date format {}", format))?;
+        Ok(Value::String(text))
+    }
+}
+
+/// `len(value)`: the number of characters of a string or of elements of an array or object.
+struct Len;
+
+impl Function for Len {
+    fn params(&self) -> &[ArgType] {
+        &[ArgType::Any]
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, String> {
+        match &args[0] {
+            Value::String(text) => Ok(Value::from(text.chars().count())),
+            Value::Array(elements) => Ok(Value::from(elements.len())),
+            Value::Object(entries) => Ok(Value::from(entries.len())),
+            value => Err(format!("cannot get the length of {}", type_name(value))),
+        }
+    }
+}
+
+/// `env(name)`: the value of an environment variable, registered by `Renderer::enable_env`.
+pub(crate) struct Env;
+
+impl Function for Env {
+    fn params(&self) -> &[ArgType] {
+        &[ArgType::String]
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, String> {
+        let name = args[0].as_str().unwrap_or_default();
+        std::env::var(name)
+            .map(Value::String)
+            .map_err(|_| format!("environment variable {} is not set", name))
+    }
+}
+
+/// `range(start, end)`: the integers from `start` up to, but not including, `end`.
+struct Range;
+
+/// The longest range `range` builds, so that a huge range fails instead of exhausting memory
+/// before any loop limit is checked.
+const MAX_RANGE_LENGTH: i64 = 1_000_000;
+
+impl Function for Range {
+    fn params(&self) -> &[ArgType] {
+        &[ArgType::Integer, ArgType::Integer]
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, String> {
+        let start = args[0].as_i64().unwrap_or_default();
+        let end = args[1].as_i64().unwrap_or_default();
+        if end.saturating_sub(start) > MAX_RANGE_LENGTH {
+            return Err(format!(
+                "ranges are limited to {} elements",
+                MAX_RANGE_LENGTH
+            ));
+        }
+        Ok(Value::Array((start..end).map(Value::from).collect()))
+    }
+}
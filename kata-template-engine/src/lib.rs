@@ -1,6 +1,55 @@
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
+
+pub mod batch;
+pub mod cache;
+pub mod cli;
+mod context;
+pub mod coverage;
+mod escape;
+mod expression;
+mod extract;
+mod format;
+mod format_spec;
+mod front_matter;
+mod function;
+mod limits;
+mod locale;
+pub mod lsp;
+pub mod mail;
+mod mustache;
+mod redaction;
+mod renderer;
+pub mod scaffold;
+pub mod server;
+pub mod snapshot;
+mod source_map;
+mod template;
+pub mod watch;
+
+pub use context::TemplateContext;
+pub use escape::{Escape, Escaper};
+pub use expression::{BinaryOperator, Expression, UnaryOperator};
+pub use extract::MAX_EXTRACTION_STEPS;
+pub use front_matter::FrontMatter;
+pub use function::{ArgType, Function};
+pub use limits::{
+    Limits, DEFAULT_MAX_EXPANSION_DEPTH, DEFAULT_MAX_INCLUDE_DEPTH, MAX_FORMAT_WIDTH,
+    MAX_NESTING_DEPTH,
+};
+pub use redaction::REDACTED;
+pub use renderer::{Renderer, DEFAULT_LOCALE};
+pub use source_map::{SourceMap, SourceMapEntry};
+pub use template::Template;
+
+/// Items used by the code generated by the `kata-template-engine-macros` crate.
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+}
 
 /// Parses a template from a JSON string.
 ///
@@ -18,7 +67,6 @@ use std::fmt;
 /// # Errors
 ///
 /// This function returns an error if the template text is empty, the JSON string is not a valid JSON object, or a variable in the JSON string is not a string.
-
 pub fn parse_template_from_json(
     template_text: &str,
     json_variables: &str,
@@ -32,6 +80,9 @@ pub fn parse_template_from_json(
 ///
 /// This function takes a template text and a dictionary of variables. It replaces placeholders in the form of `${variable}` in the template text with the corresponding values from the dictionary. If a placeholder in the template text does not have a corresponding variable in the dictionary, a warning is generated.
 ///
+/// Placeholders written in the values are left as they are, whatever the order of the
+/// variables; `Renderer::set_recursive_expansion` expands them.
+///
 /// # Arguments
 ///
 /// * `template_text` - A string slice that holds the template text.
@@ -51,10 +102,20 @@ pub fn parse_template(
     check_text_not_empty(template_text)?;
     check_dictionary_not_empty(&variables)?;
     let mut warnings = Vec::new();
-    let mut parsed_template_text = template_text.to_string();
-    for (variable, value) in variables {
-        parsed_template_text = parsed_template_text.replace(&format!("${{{}}}", variable), &value);
-        if !parsed_template_text.contains(&value) {
+    // A single pass over the text, so that values are never expanded again whatever the order
+    // of the variables.
+    let placeholder = Regex::new(r"\$\{(\w+)\}").unwrap();
+    let parsed_template_text = placeholder
+        .replace_all(
+            template_text,
+            |captures: &regex::Captures| match variables.get(&captures[1]) {
+                Some(value) => value.clone(),
+                None => captures[0].to_string(),
+            },
+        )
+        .into_owned();
+    for (variable, value) in &variables {
+        if !parsed_template_text.contains(value.as_str()) {
             warnings.push(format!("Variable {} not found", variable));
         }
     }
@@ -64,20 +125,185 @@ pub fn parse_template(
     Ok(ParsedTemplate {
         text: parsed_template_text,
         warnings,
+        sensitive: Vec::new(),
     })
 }
 
-#[derive(Debug)]
 pub struct ParsedTemplate {
     pub text: String,
     pub warnings: Vec<String>,
+    /// The byte ranges of `text` written from variables marked as sensitive, which `Debug`
+    /// masks.
+    pub sensitive: Vec<Span>,
+}
+
+impl fmt::Debug for ParsedTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParsedTemplate")
+            .field(
+                "text",
+                &redaction::redact_spans(&self.text, &self.sensitive),
+            )
+            .field("warnings", &self.warnings)
+            .field("sensitive", &self.sensitive)
+            .finish()
+    }
+}
+
+/// A byte range of the template text, used to point at the source of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
 }
 
 #[derive(Debug)]
 pub enum TemplateError {
     JsonError(serde_json::Error),
+    IoError(std::io::Error),
     EmptyDictionary,
     EmptyFile,
+    Syntax {
+        message: String,
+        span: Span,
+    },
+    UnknownFunction {
+        name: String,
+        span: Span,
+    },
+    WrongArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    WrongArgumentType {
+        function: String,
+        expected: ArgType,
+        found: String,
+        span: Span,
+    },
+    FunctionFailed {
+        function: String,
+        message: String,
+        span: Span,
+    },
+    InvalidOperands {
+        operator: String,
+        operands: Vec<String>,
+        span: Span,
+    },
+    DivisionByZero {
+        span: Span,
+    },
+    NotIterable {
+        found: String,
+        span: Span,
+    },
+    UnknownTemplate {
+        name: String,
+        span: Option<Span>,
+    },
+    OutputLimitExceeded {
+        limit: usize,
+    },
+    LoopLimitExceeded {
+        limit: usize,
+        span: Span,
+    },
+    IncludeDepthExceeded {
+        limit: usize,
+        span: Span,
+    },
+    StepLimitExceeded {
+        limit: usize,
+    },
+    TextDoesNotMatch,
+    /// The template holds a block, or a placeholder whose value is transformed, such as by a
+    /// format specification, a function or an escaper, which values cannot be recovered from.
+    NotExtractable {
+        /// What the template holds, `block` or `placeholder`.
+        kind: String,
+        span: Span,
+    },
+    /// Recovering the values of variables written several times took more than `limit` tries.
+    ExtractionLimitExceeded {
+        limit: usize,
+    },
+    InvalidPattern {
+        variable: String,
+        message: String,
+    },
+    OutputExists {
+        path: PathBuf,
+    },
+    InvalidOutputPath {
+        path: String,
+    },
+    InFile {
+        path: PathBuf,
+        error: Box<TemplateError>,
+    },
+    InvalidFrontMatter {
+        message: String,
+    },
+    MissingRequiredVariable {
+        name: String,
+    },
+    UnknownLocale {
+        locale: String,
+    },
+    UnknownMessage {
+        key: String,
+        locale: String,
+        span: Span,
+    },
+    InvalidPluralArgument {
+        found: String,
+        span: Span,
+    },
+    UnknownEscaper {
+        name: String,
+        span: Span,
+    },
+    /// Values expanded recursively reference each other, as in `a -> b -> a`.
+    ExpansionCycle {
+        cycle: Vec<String>,
+    },
+    ExpansionDepthExceeded {
+        limit: usize,
+    },
+    /// The errors of a template text with several, in the order of the text.
+    Multiple {
+        errors: Vec<TemplateError>,
+    },
+    InvalidMail {
+        message: String,
+    },
+    /// Two files of a directory define templates with the same name, as `invoice.txt` and
+    /// `invoice.html` do.
+    DuplicateTemplate {
+        name: String,
+        paths: (PathBuf, PathBuf),
+    },
+    /// A fixture is named after two templates, as `invoice.overdue.json` is after `invoice.txt`
+    /// and `invoice.overdue.txt`.
+    AmbiguousFixture {
+        fixture: PathBuf,
+        templates: (String, String),
+    },
 }
 
 fn check_dictionary_not_empty(dictionary: &HashMap<String, String>) -> Result<(), TemplateError> {
@@ -116,8 +342,161 @@ impl fmt::Display for TemplateError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             TemplateError::JsonError(ref err) => write!(f, "JSON error: {}", err),
+            TemplateError::IoError(ref err) => write!(f, "I/O error: {}", err),
             TemplateError::EmptyDictionary => write!(f, "Empty dictionary is not allowed"),
             TemplateError::EmptyFile => write!(f, "Empty file is not allowed"),
+            TemplateError::Syntax { ref message, span } => {
+                write!(f, "Syntax error at {}: {}", span, message)
+            }
+            TemplateError::UnknownFunction { ref name, span } => {
+                write!(f, "Unknown function {} at {}", name, span)
+            }
+            TemplateError::WrongArgumentCount {
+                ref function,
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "Function {} expects {} arguments but got {} at {}",
+                function, expected, found, span
+            ),
+            TemplateError::WrongArgumentType {
+                ref function,
+                expected,
+                ref found,
+                span,
+            } => write!(
+                f,
+                "Function {} expects an argument of type {} but got {} at {}",
+                function, expected, found, span
+            ),
+            TemplateError::FunctionFailed {
+                ref function,
+                ref message,
+                span,
+            } => write!(f, "Function {} failed at {}: {}", function, span, message),
+            TemplateError::InvalidOperands {
+                ref operator,
+                ref operands,
+                span,
+            } => write!(
+                f,
+                "Operator {} cannot be applied to {} at {}",
+                operator,
+                operands.join(" and "),
+                span
+            ),
+            TemplateError::DivisionByZero { span } => write!(f, "Division by zero at {}", span),
+            TemplateError::NotIterable { ref found, span } => {
+                write!(f, "Cannot loop over {} at {}", found, span)
+            }
+            TemplateError::UnknownTemplate {
+                ref name,
+                span: Some(span),
+            } => write!(f, "Unknown template {} at {}", name, span),
+            TemplateError::UnknownTemplate { ref name, .. } => {
+                write!(f, "Unknown template {}", name)
+            }
+            TemplateError::OutputLimitExceeded { limit } => {
+                write!(f, "Output exceeds the limit of {} bytes", limit)
+            }
+            TemplateError::LoopLimitExceeded { limit, span } => write!(
+                f,
+                "Loops exceed the limit of {} iterations at {}",
+                limit, span
+            ),
+            TemplateError::IncludeDepthExceeded { limit, span } => write!(
+                f,
+                "Includes exceed the limit of {} nested templates at {}",
+                limit, span
+            ),
+            TemplateError::StepLimitExceeded { limit } => {
+                write!(f, "Rendering exceeds the limit of {} steps", limit)
+            }
+            TemplateError::TextDoesNotMatch => write!(f, "Text does not match the template"),
+            TemplateError::NotExtractable { ref kind, span } => {
+                write!(f, "Cannot extract values through the {} at {}", kind, span)
+            }
+            TemplateError::ExtractionLimitExceeded { limit } => write!(
+                f,
+                "Extraction exceeds the limit of {} tries, the template repeats too many variables",
+                limit
+            ),
+            TemplateError::InvalidPattern {
+                ref variable,
+                ref message,
+            } => write!(f, "Invalid pattern for variable {}: {}", variable, message),
+            TemplateError::OutputExists { ref path } => {
+                write!(f, "Output file {} already exists", path.display())
+            }
+            TemplateError::InvalidOutputPath { ref path } => {
+                write!(f, "Invalid output path {}", path)
+            }
+            TemplateError::InFile {
+                ref path,
+                ref error,
+            } => write!(f, "{}: {}", path.display(), error),
+            TemplateError::InvalidFrontMatter { ref message } => {
+                write!(f, "Invalid front matter: {}", message)
+            }
+            TemplateError::MissingRequiredVariable { ref name } => {
+                write!(f, "Required variable {} is not defined", name)
+            }
+            TemplateError::UnknownLocale { ref locale } => {
+                write!(f, "Unknown locale {}", locale)
+            }
+            TemplateError::UnknownMessage {
+                ref key,
+                ref locale,
+                span,
+            } => write!(
+                f,
+                "Unknown message {} for locale {} at {}",
+                key, locale, span
+            ),
+            TemplateError::InvalidPluralArgument { ref found, span } => write!(
+                f,
+                "Plural messages expect a number but got {} at {}",
+                found, span
+            ),
+            TemplateError::UnknownEscaper { ref name, span } => {
+                write!(f, "Unknown escaper {} at {}", name, span)
+            }
+            TemplateError::ExpansionCycle { ref cycle } => {
+                write!(f, "Reference cycle {}", cycle.join(" -> "))
+            }
+            TemplateError::ExpansionDepthExceeded { limit } => {
+                write!(f, "Expansions exceed the limit of {} nested values", limit)
+            }
+            TemplateError::InvalidMail { ref message } => write!(f, "Invalid mail: {}", message),
+            TemplateError::DuplicateTemplate {
+                ref name,
+                paths: (ref first, ref second),
+            } => write!(
+                f,
+                "Files {} and {} both define the template {}",
+                first.display(),
+                second.display(),
+                name
+            ),
+            TemplateError::AmbiguousFixture {
+                ref fixture,
+                templates: (ref first, ref second),
+            } => write!(
+                f,
+                "Fixture {} is named after both templates {} and {}",
+                fixture.display(),
+                first,
+                second
+            ),
+            TemplateError::Multiple { ref errors } => {
+                write!(f, "{} errors:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n{}", error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
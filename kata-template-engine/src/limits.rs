@@ -0,0 +1,49 @@
+/// Limits on the resources a single render may use, to render templates written by untrusted
+/// authors without a runaway template taking down the process.
+///
+/// `None` means unlimited. The default limits only bound the include and expansion depths, so
+/// that a template including itself fails with an error instead of overflowing the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum size in bytes of the rendered text.
+    pub max_output_bytes: Option<usize>,
+    /// Maximum number of iterations of all the `{% for %}` blocks of a render together.
+    pub max_loop_iterations: Option<usize>,
+    /// Maximum number of nested `{% include %}` tags.
+    pub max_include_depth: Option<usize>,
+    /// Maximum number of nodes rendered, counting every loop iteration and include.
+    pub max_steps: Option<usize>,
+    /// Maximum number of values expanded within each other when the renderer expands values
+    /// recursively.
+    pub max_expansion_depth: Option<usize>,
+}
+
+pub const DEFAULT_MAX_INCLUDE_DEPTH: usize = 64;
+pub const DEFAULT_MAX_EXPANSION_DEPTH: usize = 16;
+
+/// Maximum width and precision of the format specification of a placeholder, as in
+/// `${price:>12.2}`. Larger ones are syntax errors, since the padded text is built before
+/// `Limits::max_output_bytes` is checked.
+pub const MAX_FORMAT_WIDTH: usize = 1024;
+
+/// Maximum nesting of parentheses and operators within an expression, and of blocks, messages
+/// and Mustache sections within a template. Deeper ones are syntax errors, since the parser
+/// would otherwise overflow the stack of the thread.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_output_bytes: None,
+            max_loop_iterations: None,
+            max_include_depth: Some(DEFAULT_MAX_INCLUDE_DEPTH),
+            max_steps: None,
+            max_expansion_depth: Some(DEFAULT_MAX_EXPANSION_DEPTH),
+        }
+    }
+}
+
+/// Whether `count` goes beyond `limit`.
+pub(crate) fn exceeds(count: usize, limit: Option<usize>) -> bool {
+    limit.is_some_and(|limit| count > limit)
+}
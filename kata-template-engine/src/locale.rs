@@ -0,0 +1,195 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// The CLDR plural categories a number falls in, which select the case of a `plural` message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+impl PluralCategory {
+    pub(crate) fn from_keyword(keyword: &str) -> Option<PluralCategory> {
+        match keyword {
+            "zero" => Some(PluralCategory::Zero),
+            "one" => Some(PluralCategory::One),
+            "two" => Some(PluralCategory::Two),
+            "few" => Some(PluralCategory::Few),
+            "many" => Some(PluralCategory::Many),
+            "other" => Some(PluralCategory::Other),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PluralCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self {
+            PluralCategory::Zero => "zero",
+            PluralCategory::One => "one",
+            PluralCategory::Two => "two",
+            PluralCategory::Few => "few",
+            PluralCategory::Many => "many",
+            PluralCategory::Other => "other",
+        };
+        write!(f, "{}", keyword)
+    }
+}
+
+/// The operands of a number the CLDR rules are written with: its absolute value `n`, its
+/// integer digits `i` and the number `v` of its visible fraction digits, so that `1` and `1.0`
+/// can fall in different categories.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Operands {
+    n: f64,
+    i: u64,
+    v: usize,
+}
+
+impl Operands {
+    /// Reads the operands of a number written in decimal, such as `5`, `-3` or `1.50`.
+    pub(crate) fn parse(number: &str) -> Option<Operands> {
+        let number = number.trim().trim_start_matches('-');
+        let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+        if integer.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        Some(Operands {
+            n: number.parse().ok()?,
+            i: integer.parse().ok()?,
+            v: fraction.len(),
+        })
+    }
+}
+
+/// The rule of a language, mapping the operands of a number to its plural category.
+pub(crate) type PluralRule = fn(&Operands) -> PluralCategory;
+
+/// Returns the cardinal plural rule of a locale such as `en`, `pt-BR` or `ru_RU`, from its
+/// language subtag, or `None` if the language is not supported.
+pub(crate) fn plural_rule(locale: &str) -> Option<PluralRule> {
+    let language = locale
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let rule: PluralRule = match language.as_str() {
+        "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" => |_| PluralCategory::Other,
+        "en" | "de" | "nl" | "sv" | "fi" | "et" | "nb" | "no" | "el" | "hu" | "tr" | "gl" => {
+            one_if_integer_one
+        }
+        "es" => |o| match o {
+            o if o.n == 1.0 => PluralCategory::One,
+            o if is_million_multiple(o) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "it" => |o| match o {
+            o if o.i == 1 && o.v == 0 => PluralCategory::One,
+            o if is_million_multiple(o) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "fr" | "pt" => |o| match o {
+            o if o.i <= 1 => PluralCategory::One,
+            o if is_million_multiple(o) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "ru" | "uk" | "be" => |o| match (o.v, o.i % 10, o.i % 100) {
+            (0, 1, i100) if i100 != 11 => PluralCategory::One,
+            (0, 2..=4, i100) if !(12..=14).contains(&i100) => PluralCategory::Few,
+            (0, _, _) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "pl" => |o| match (o.v, o.i, o.i % 10, o.i % 100) {
+            (0, 1, _, _) => PluralCategory::One,
+            (0, _, 2..=4, i100) if !(12..=14).contains(&i100) => PluralCategory::Few,
+            (0, _, _, _) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        "cs" | "sk" => |o| match (o.v, o.i) {
+            (0, 1) => PluralCategory::One,
+            (0, 2..=4) => PluralCategory::Few,
+            (0, _) => PluralCategory::Other,
+            _ => PluralCategory::Many,
+        },
+        "he" => |o| match (o.v, o.i) {
+            (0, 1) => PluralCategory::One,
+            (0, 2) => PluralCategory::Two,
+            _ => PluralCategory::Other,
+        },
+        "ar" => |o| match o.n {
+            0.0 => PluralCategory::Zero,
+            1.0 => PluralCategory::One,
+            2.0 => PluralCategory::Two,
+            n if n.fract() == 0.0 && (3.0..=10.0).contains(&(n % 100.0)) => PluralCategory::Few,
+            n if n.fract() == 0.0 && (11.0..=99.0).contains(&(n % 100.0)) => PluralCategory::Many,
+            _ => PluralCategory::Other,
+        },
+        _ => return None,
+    };
+    Some(rule)
+}
+
+/// Returns the locales whose catalogs are searched for a message, most specific first, such as
+/// `pt-br` and then `pt` for `pt_BR`.
+pub(crate) fn locale_fallbacks(locale: &str) -> Vec<String> {
+    let locale = normalize_locale(locale);
+    let mut fallbacks = vec![locale.clone()];
+    if let Some((language, _)) = locale.split_once('-') {
+        fallbacks.push(language.to_string());
+    }
+    fallbacks
+}
+
+/// Writes a locale in lower case with `-` separators, so that `pt_BR` and `pt-br` are the same.
+pub(crate) fn normalize_locale(locale: &str) -> String {
+    locale.replace('_', "-").to_ascii_lowercase()
+}
+
+fn one_if_integer_one(operands: &Operands) -> PluralCategory {
+    if operands.i == 1 && operands.v == 0 {
+        PluralCategory::One
+    } else {
+        PluralCategory::Other
+    }
+}
+
+fn is_million_multiple(operands: &Operands) -> bool {
+    operands.v == 0 && operands.i != 0 && operands.i.is_multiple_of(1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(locale: &str, number: &str) -> PluralCategory {
+        plural_rule(locale).unwrap()(&Operands::parse(number).unwrap())
+    }
+
+    #[test]
+    fn given_russian_numbers_it_uses_the_one_few_and_many_categories() {
+        assert_eq!(category("ru", "1"), PluralCategory::One);
+        assert_eq!(category("ru", "21"), PluralCategory::One);
+        assert_eq!(category("ru", "11"), PluralCategory::Many);
+        assert_eq!(category("ru", "3"), PluralCategory::Few);
+        assert_eq!(category("ru", "13"), PluralCategory::Many);
+        assert_eq!(category("ru", "1.5"), PluralCategory::Other);
+    }
+
+    #[test]
+    fn given_visible_fraction_digits_english_does_not_take_them_as_one() {
+        assert_eq!(category("en-US", "1"), PluralCategory::One);
+        assert_eq!(category("en-US", "1.0"), PluralCategory::Other);
+        assert_eq!(category("fr_FR", "0"), PluralCategory::One);
+        assert_eq!(category("fr", "1000000"), PluralCategory::Many);
+    }
+
+    #[test]
+    fn given_an_unsupported_language_it_has_no_rule() {
+        assert!(plural_rule("xx").is_none());
+    }
+}
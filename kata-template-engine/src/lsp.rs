@@ -0,0 +1,418 @@
+//! A language server for template files, speaking the Language Server Protocol over stdio.
+//!
+//! It publishes all the syntax errors of open templates as diagnostics, completes variable names
+//! from a sample context or a JSON schema, goes to the templates of `{% include %}` tags and
+//! `{{> partial}}` tags, and shows the value of a variable of the sample context on hover.
+//!
+//! The sample context and the schema are JSON files given by the client in its
+//! `initializationOptions`, as `{"sample": "sample.json", "schema": "schema.json"}`, relative to
+//! the root of the workspace. Templates with the `.mustache` extension are parsed as Mustache.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use crate::function::type_name;
+use crate::{Span, Template, TemplateError};
+
+/// Serves the requests read from `input`, writing responses and notifications to `output`,
+/// until the client sends `exit` or closes the input.
+///
+/// # Errors
+///
+/// This function returns an error if reading or writing fails, or if a message is not framed
+/// by a `Content-Length` header. A message that is not valid JSON is answered with a parse
+/// error, and the server goes on with the next one.
+pub fn serve(input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<()> {
+    let mut server = Server::default();
+    while let Some(body) = read_message(input)? {
+        let message = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(error) => {
+                let error = json!({"code": -32700, "message": format!("Parse error: {}", error)});
+                write_message(
+                    output,
+                    &json!({"jsonrpc": "2.0", "id": null, "error": error}),
+                )?;
+                continue;
+            }
+        };
+        if !server.handle(&message, output)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of the open templates, by URI.
+    documents: HashMap<String, String>,
+    sample: Value,
+    schema: Value,
+}
+
+impl Server {
+    /// Handles a message, returning `false` once the client asks the server to exit.
+    fn handle(&mut self, message: &Value, output: &mut dyn Write) -> io::Result<bool> {
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => {
+                self.configure(params);
+                json!({
+                    "capabilities": {
+                        "textDocumentSync": 1,
+                        "completionProvider": {"triggerCharacters": ["{", "."]},
+                        "definitionProvider": true,
+                        "hoverProvider": true,
+                    },
+                    "serverInfo": {"name": "template-lsp"},
+                })
+            }
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.to_string(), text.to_string());
+                publish_diagnostics(uri, text, output)?;
+                return Ok(true);
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.documents.insert(uri.to_string(), text.to_string());
+                    publish_diagnostics(uri, text, output)?;
+                }
+                return Ok(true);
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                publish_diagnostics(uri, "", output)?;
+                return Ok(true);
+            }
+            "textDocument/completion" => self.complete(uri, &params["position"]),
+            "textDocument/definition" => self.definition(uri, &params["position"]),
+            "textDocument/hover" => self.hover(uri, &params["position"]),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(false),
+            method => {
+                if let Some(id) = message.get("id") {
+                    let error =
+                        json!({"code": -32601, "message": format!("Unknown method {}", method)});
+                    write_message(output, &json!({"jsonrpc": "2.0", "id": id, "error": error}))?;
+                }
+                return Ok(true);
+            }
+        };
+        if let Some(id) = message.get("id") {
+            write_message(
+                output,
+                &json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            )?;
+        }
+        Ok(true)
+    }
+
+    /// Reads the sample context and the schema named by the initialization options.
+    fn configure(&mut self, params: &Value) {
+        let root = params["rootUri"].as_str().map(uri_to_path);
+        let read = |option: &str| {
+            let path = Path::new(params["initializationOptions"][option].as_str()?);
+            let path = match &root {
+                Some(root) => root.join(path),
+                None => path.to_path_buf(),
+            };
+            serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+        };
+        self.sample = read("sample").unwrap_or_default();
+        self.schema = read("schema").unwrap_or_default();
+    }
+
+    /// Completes the variable or the key of a dotted path written before the position inside a
+    /// placeholder or a tag.
+    fn complete(&self, uri: &str, position: &Value) -> Value {
+        let Some((text, offset)) = self.locate(uri, position) else {
+            return json!([]);
+        };
+        let before = &text[..offset];
+        let opened = ["${", "{%", "{{"]
+            .iter()
+            .filter_map(|opener| before.rfind(opener))
+            .max();
+        if opened.is_none() || before.rfind('}') > opened {
+            return json!([]);
+        }
+        let path_start = before
+            .rfind(|c: char| !is_path_character(c))
+            .map_or(0, |index| index + 1);
+        let (parent, prefix) = match before[path_start..].rsplit_once('.') {
+            Some((parent, prefix)) => (Some(parent), prefix),
+            None => (None, &before[path_start..]),
+        };
+        let mut items = Map::new();
+        if let Some(Value::Object(entries)) = lookup(&self.sample, parent) {
+            for (key, value) in entries {
+                items.insert(key.clone(), Value::from(type_name(value)));
+            }
+        }
+        if let Some(Value::Object(properties)) = schema_properties(&self.schema, parent) {
+            for (key, property) in properties {
+                let kind = property["type"].as_str().unwrap_or("any");
+                items.entry(key.clone()).or_insert(Value::from(kind));
+            }
+        }
+        items
+            .into_iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, detail)| json!({"label": key, "kind": 6, "detail": detail}))
+            .collect()
+    }
+
+    /// Finds the template of the `{% include %}` or `{{> partial}}` tag at the position, in
+    /// the directory of the template, with or without its extension.
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        let Some((text, offset)) = self.locate(uri, position) else {
+            return Value::Null;
+        };
+        let include = Regex::new(r#"\{%\s*include\s+"([^"]+)"\s*%\}|\{\{>\s*([^\s}]+)\s*\}\}"#)
+            .expect("the include pattern is valid");
+        let Some(name) = include
+            .captures_iter(text)
+            .find(|captures| {
+                let tag = captures.get(0).expect("a match has a whole capture");
+                tag.start() <= offset && offset <= tag.end()
+            })
+            .and_then(|captures| captures.get(1).or_else(|| captures.get(2)))
+        else {
+            return Value::Null;
+        };
+        let document = uri_to_path(uri);
+        let directory = document.parent().unwrap_or(Path::new(""));
+        let mut candidates = vec![directory.join(name.as_str())];
+        if let Some(extension) = document.extension() {
+            let mut with_extension = directory.join(name.as_str()).into_os_string();
+            with_extension.push(".");
+            with_extension.push(extension);
+            candidates.push(PathBuf::from(with_extension));
+        }
+        match candidates.into_iter().find(|path| path.is_file()) {
+            Some(path) => json!({
+                "uri": path_to_uri(&path),
+                "range": {
+                    "start": {"line": 0, "character": 0},
+                    "end": {"line": 0, "character": 0},
+                },
+            }),
+            None => Value::Null,
+        }
+    }
+
+    /// Shows the value in the sample context of the dotted path at the position, up to the end
+    /// of the segment under it.
+    fn hover(&self, uri: &str, position: &Value) -> Value {
+        let Some((text, offset)) = self.locate(uri, position) else {
+            return Value::Null;
+        };
+        let start = text[..offset]
+            .rfind(|c: char| !is_path_character(c))
+            .map_or(0, |index| index + 1);
+        let end = text[offset..]
+            .find(|c: char| !is_path_character(c) || c == '.')
+            .map_or(text.len(), |index| offset + index);
+        let path = &text[start..end];
+        if path.is_empty() {
+            return Value::Null;
+        }
+        match lookup(&self.sample, Some(path)) {
+            Some(value) => json!({
+                "contents": {
+                    "kind": "markdown",
+                    "value": format!(
+                        "```json\n{}\n```",
+                        serde_json::to_string_pretty(value).unwrap_or_default()
+                    ),
+                },
+                "range": {
+                    "start": position_of(text, start),
+                    "end": position_of(text, end),
+                },
+            }),
+            None => Value::Null,
+        }
+    }
+
+    /// Returns the text of an open template and the byte offset of an LSP position in it.
+    fn locate(&self, uri: &str, position: &Value) -> Option<(&str, usize)> {
+        let text = self.documents.get(uri)?;
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+        let line_start = match line {
+            0 => 0,
+            line => text.match_indices('\n').nth(line - 1)?.0 + 1,
+        };
+        let mut units = 0;
+        for (index, c) in text[line_start..].char_indices() {
+            if units >= character || c == '\n' {
+                return Some((text, line_start + index));
+            }
+            units += c.len_utf16();
+        }
+        Some((text, text.len()))
+    }
+}
+
+fn publish_diagnostics(uri: &str, text: &str, output: &mut dyn Write) -> io::Result<()> {
+    let error = if uri.ends_with(".mustache") {
+        Template::parse_mustache(text).err()
+    } else {
+        Template::parse_with_recovery(text).1
+    };
+    let errors = match error {
+        Some(TemplateError::Multiple { errors }) => errors,
+        error => error.into_iter().collect(),
+    };
+    let diagnostics: Vec<Value> = errors
+        .into_iter()
+        .map(|error| {
+            let (span, message) = match error {
+                TemplateError::Syntax { span, message } => (span, message),
+                error => (Span::new(0, 0), error.to_string()),
+            };
+            json!({
+                "range": {
+                    "start": position_of(text, span.start),
+                    "end": position_of(text, span.end),
+                },
+                "severity": 1,
+                "source": "kata-template-engine",
+                "message": message,
+            })
+        })
+        .collect();
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        }),
+    )
+}
+
+/// Converts a byte offset into an LSP position, counting characters in UTF-16 code units.
+fn position_of(text: &str, offset: usize) -> Value {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+    json!({
+        "line": text[..line_start].matches('\n').count(),
+        "character": text[line_start..offset].encode_utf16().count(),
+    })
+}
+
+fn is_path_character(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn lookup<'v>(value: &'v Value, path: Option<&str>) -> Option<&'v Value> {
+    let Some(path) = path else {
+        return Some(value);
+    };
+    path.split('.').try_fold(value, |value, key| match value {
+        Value::Object(entries) => entries.get(key),
+        Value::Array(elements) => elements.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Returns the `properties` of the JSON schema of a dotted path, looking into the `items` of
+/// arrays.
+fn schema_properties<'s>(schema: &'s Value, path: Option<&str>) -> Option<&'s Value> {
+    let items = |schema: &'s Value| match schema.get("items") {
+        Some(items) => items,
+        None => schema,
+    };
+    let mut schema = schema;
+    for key in path.into_iter().flat_map(|path| path.split('.')) {
+        schema = match key.parse::<usize>() {
+            Ok(_) => items(schema),
+            Err(_) => items(schema).get("properties")?.get(key)?,
+        };
+    }
+    items(schema).get("properties")
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let decoded = match (byte, tail) {
+            (b'%', [high, low, ..]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Returns the `file` URI of a path, percent-encoding the bytes that URIs cannot hold, such
+/// as spaces and `#`.
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(char::from(byte))
+            }
+            byte => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// Reads the body of a message framed by a `Content-Length` header, or `None` at the end of the
+/// input.
+fn read_message(input: &mut dyn BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_message(output: &mut dyn Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
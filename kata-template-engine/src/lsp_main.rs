@@ -0,0 +1,10 @@
+use std::io;
+use std::process;
+
+fn main() {
+    let stdin = io::stdin();
+    if let Err(error) = kata_template_engine::lsp::serve(&mut stdin.lock(), &mut io::stdout()) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
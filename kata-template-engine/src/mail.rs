@@ -0,0 +1,450 @@
+//! Rendering of emails: a subject, a plain text part and an HTML part rendered with one context
+//! into a MIME message ready to be sent.
+
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset, Local};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::cache::fnv1a;
+use crate::{Escape, Renderer, Span, Template, TemplateError};
+
+/// The longest line of an encoded header or body, not counting its line break.
+const MAX_LINE_LENGTH: usize = 76;
+
+/// The templates of the subject and of the parts of an email.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailTemplate {
+    subject: Template,
+    text: Option<Template>,
+    html: Option<Template>,
+}
+
+impl MailTemplate {
+    /// Parses a mail template made of sections, each starting with a `--- subject ---`,
+    /// `--- text ---` or `--- html ---` line:
+    ///
+    /// ```text
+    /// --- subject ---
+    /// Your invoice ${number}
+    /// --- text ---
+    /// Dear ${name}, you owe ${total}.
+    /// --- html ---
+    /// <p>Dear ${name}, you owe <b>${total}</b>.</p>
+    /// ```
+    ///
+    /// The subject is required along with at least one of the parts. The values of the HTML
+    /// part are escaped for HTML unless its placeholders or front matter choose another escape.
+    /// Spans still count from the start of the whole text.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::InvalidMail` if text comes before the first
+    /// section, a section is repeated, or the subject or both parts are missing, and the errors
+    /// of `Template::parse` if a section cannot be parsed.
+    pub fn parse(template_text: &str) -> Result<MailTemplate, TemplateError> {
+        let marker = Regex::new(r"(?m)^--- *(\w+) *---[ \t]*(\r?\n|$)")
+            .expect("the section pattern is valid");
+        let markers: Vec<_> = marker.captures_iter(template_text).collect();
+        let first = markers.first().map_or(template_text.len(), |captures| {
+            captures.get(0).expect("a match").start()
+        });
+        if !template_text[..first].trim().is_empty() {
+            return Err(invalid_mail("text before the first section"));
+        }
+        let (mut subject, mut text, mut html) = (None, None, None);
+        for (index, captures) in markers.iter().enumerate() {
+            let start = captures.get(0).expect("a match").end();
+            let end = markers.get(index + 1).map_or(template_text.len(), |next| {
+                next.get(0).expect("a match").start()
+            });
+            let section = match &captures[1] {
+                "subject" => &mut subject,
+                "text" => &mut text,
+                "html" => &mut html,
+                name => return Err(invalid_mail(&format!("unknown section `{}`", name))),
+            };
+            if section.is_some() {
+                return Err(invalid_mail(&format!(
+                    "repeated section `{}`",
+                    &captures[1]
+                )));
+            }
+            let template = Template::parse(&template_text[start..end])
+                .map_err(|error| shift_spans(error, start))?;
+            *section = Some(template);
+        }
+        let subject = subject.ok_or_else(|| invalid_mail("missing subject section"))?;
+        MailTemplate::from_templates(subject, text, html)
+    }
+
+    /// Builds a mail template from separate templates of the subject, the plain text part and
+    /// the HTML part, whose values are escaped for HTML unless its placeholders or front matter
+    /// choose another escape.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::InvalidMail` if both parts are missing.
+    pub fn from_templates(
+        subject: Template,
+        text: Option<Template>,
+        html: Option<Template>,
+    ) -> Result<MailTemplate, TemplateError> {
+        if text.is_none() && html.is_none() {
+            return Err(invalid_mail("missing text and html sections"));
+        }
+        Ok(MailTemplate {
+            subject,
+            text,
+            html: html.map(|html| html.with_default_escape(Escape::Html)),
+        })
+    }
+}
+
+/// A mailbox of a header, as in `Ada Lovelace <ada@example.com>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub address: String,
+}
+
+impl Mailbox {
+    pub fn new(address: &str) -> Mailbox {
+        Mailbox {
+            name: None,
+            address: address.to_string(),
+        }
+    }
+
+    pub fn named(name: &str, address: &str) -> Mailbox {
+        Mailbox {
+            name: Some(name.to_string()),
+            address: address.to_string(),
+        }
+    }
+}
+
+/// The headers of an email that do not come from its template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MailHeaders {
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    pub cc: Vec<Mailbox>,
+    pub reply_to: Option<Mailbox>,
+    /// The date of the message, the current time if `None`.
+    pub date: Option<DateTime<FixedOffset>>,
+}
+
+impl MailHeaders {
+    pub fn new(from: Mailbox, to: Vec<Mailbox>) -> MailHeaders {
+        MailHeaders {
+            from,
+            to,
+            cc: Vec::new(),
+            reply_to: None,
+            date: None,
+        }
+    }
+}
+
+/// An email rendered by `render_mail`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedMail {
+    /// The whole RFC 5322 message, with CRLF line breaks.
+    pub message: Vec<u8>,
+    /// The warnings of the subject and the parts.
+    pub warnings: Vec<String>,
+}
+
+/// Renders the subject and the parts of an email with one context into a MIME message.
+///
+/// A message with both parts is a `multipart/alternative` message with the plain text part
+/// first. Headers holding other characters than ASCII are encoded as RFC 2047 encoded words,
+/// and the parts as quoted-printable UTF-8 text. Line breaks in the rendered subject are
+/// replaced by spaces.
+///
+/// # Errors
+///
+/// This function returns `TemplateError::InvalidMail` if an address or a name of the headers
+/// holds a line break or an address is not valid, and the errors of `Renderer::render` if the
+/// subject or a part cannot be rendered.
+pub fn render_mail(
+    renderer: &Renderer,
+    template: &MailTemplate,
+    context: &Value,
+    headers: &MailHeaders,
+) -> Result<RenderedMail, TemplateError> {
+    let mut warnings = Vec::new();
+    let mut render = |template: &Template| -> Result<String, TemplateError> {
+        let parsed_template = renderer.render(template, context)?;
+        warnings.extend(parsed_template.warnings);
+        Ok(parsed_template.text)
+    };
+    let subject = render(&template.subject)?;
+    let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    let text = template.text.as_ref().map(&mut render).transpose()?;
+    let html = template.html.as_ref().map(&mut render).transpose()?;
+
+    let mut message = String::new();
+    let date = headers
+        .date
+        .unwrap_or_else(|| Local::now().fixed_offset())
+        .to_rfc2822();
+    write_header(&mut message, "Date", &date);
+    write_header(
+        &mut message,
+        "From",
+        &format_mailbox(&headers.from, "From")?,
+    );
+    write_mailboxes(&mut message, "To", &headers.to)?;
+    write_mailboxes(&mut message, "Cc", &headers.cc)?;
+    if let Some(reply_to) = &headers.reply_to {
+        write_header(
+            &mut message,
+            "Reply-To",
+            &format_mailbox(reply_to, "Reply-To")?,
+        );
+    }
+    write_header(&mut message, "Subject", &encode_words(&subject, "Subject"));
+    write_header(&mut message, "MIME-Version", "1.0");
+    match (text, html) {
+        (Some(text), Some(html)) => {
+            // `=` is always encoded in quoted-printable text, so no part can hold the boundary.
+            let boundary = format!(
+                "=_kata_{:016x}",
+                fnv1a(format!("{}{}", text, html).as_bytes())
+            );
+            write_header(
+                &mut message,
+                "Content-Type",
+                &format!("multipart/alternative; boundary=\"{}\"", boundary),
+            );
+            message.push_str("\r\n");
+            for (subtype, body) in [("plain", text), ("html", html)] {
+                write!(message, "--{}\r\n", boundary).expect("writing to a string");
+                write_part(&mut message, subtype, &body);
+            }
+            write!(message, "--{}--\r\n", boundary).expect("writing to a string");
+        }
+        (Some(text), None) => write_part(&mut message, "plain", &text),
+        (None, Some(html)) => write_part(&mut message, "html", &html),
+        (None, None) => unreachable!("a mail template has at least one part"),
+    }
+    Ok(RenderedMail {
+        message: message.into_bytes(),
+        warnings,
+    })
+}
+
+fn write_part(message: &mut String, subtype: &str, body: &str) {
+    write_header(
+        message,
+        "Content-Type",
+        &format!("text/{}; charset=utf-8", subtype),
+    );
+    write_header(message, "Content-Transfer-Encoding", "quoted-printable");
+    message.push_str("\r\n");
+    message.push_str(&quoted_printable(body));
+    message.push_str("\r\n");
+}
+
+/// Writes a header, folding its value at spaces to keep lines within `MAX_LINE_LENGTH`.
+fn write_header(message: &mut String, name: &str, value: &str) {
+    let mut line_length = name.len() + 1;
+    message.push_str(name);
+    message.push(':');
+    for word in value.split(' ') {
+        if line_length + 1 + word.len() > MAX_LINE_LENGTH && line_length > name.len() + 1 {
+            message.push_str("\r\n");
+            line_length = 0;
+        }
+        message.push(' ');
+        message.push_str(word);
+        line_length += 1 + word.len();
+    }
+    message.push_str("\r\n");
+}
+
+fn write_mailboxes(
+    message: &mut String,
+    name: &str,
+    mailboxes: &[Mailbox],
+) -> Result<(), TemplateError> {
+    if mailboxes.is_empty() {
+        return Ok(());
+    }
+    let formatted = mailboxes
+        .iter()
+        .map(|mailbox| format_mailbox(mailbox, name))
+        .collect::<Result<Vec<_>, _>>()?;
+    write_header(message, name, &formatted.join(", "));
+    Ok(())
+}
+
+/// Formats a mailbox of the header `header`, encoding its name if it is not ASCII.
+fn format_mailbox(mailbox: &Mailbox, header: &str) -> Result<String, TemplateError> {
+    let address = &mailbox.address;
+    let valid_address = address.is_ascii()
+        && address.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && !domain.is_empty() && !domain.contains('@')
+        })
+        && !address.contains(|c: char| c.is_whitespace() || c.is_control() || "<>,;\"".contains(c));
+    if !valid_address {
+        return Err(invalid_mail(&format!("invalid address `{}`", address)));
+    }
+    let Some(name) = &mailbox.name else {
+        return Ok(address.clone());
+    };
+    if name.contains(['\r', '\n']) {
+        return Err(invalid_mail(&format!(
+            "line break in the name of `{}`",
+            address
+        )));
+    }
+    let name = match name.is_ascii() {
+        true if name.contains(|c: char| "()<>[]:;@\\,.\"".contains(c)) => {
+            format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        true => name.clone(),
+        false => encode_words(name, header),
+    };
+    Ok(format!("{} <{}>", name, address))
+}
+
+/// Encodes a text of the header `header` holding other characters than printable ASCII as
+/// RFC 2047 encoded words, each short enough to fit on a folded line, and the first one short
+/// enough to fit after the name of the header, since headers are not folded before their first
+/// word.
+fn encode_words(text: &str, header: &str) -> String {
+    if text.chars().all(|c| c == ' ' || c.is_ascii_graphic()) {
+        return text.to_string();
+    }
+    // A space, "=?utf-8?q?" and "?=" leave 63 characters for the encoded text of a line.
+    const ENCODED_LENGTH: usize = MAX_LINE_LENGTH - 13;
+    let mut length = ENCODED_LENGTH - (header.len() + 1);
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        let mut encoded = String::new();
+        match c {
+            ' ' => encoded.push('_'),
+            c if c.is_ascii_alphanumeric() || "!*+-/".contains(c) => encoded.push(c),
+            c => {
+                let mut bytes = [0; 4];
+                for byte in c.encode_utf8(&mut bytes).bytes() {
+                    write!(encoded, "={:02X}", byte).expect("writing to a string");
+                }
+            }
+        }
+        if word.len() + encoded.len() > length {
+            words.push(format!("=?utf-8?q?{}?=", word));
+            word.clear();
+            length = ENCODED_LENGTH;
+        }
+        word.push_str(&encoded);
+    }
+    words.push(format!("=?utf-8?q?{}?=", word));
+    words.join(" ")
+}
+
+/// Encodes a text as quoted-printable with CRLF line breaks, splitting lines longer than
+/// `MAX_LINE_LENGTH` with soft line breaks.
+fn quoted_printable(text: &str) -> String {
+    let text = text.replace("\r\n", "\n");
+    let mut encoded = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            encoded.push_str("\r\n");
+        }
+        let mut line_length = 0;
+        let bytes = line.as_bytes();
+        for (position, byte) in bytes.iter().enumerate() {
+            let last = position + 1 == bytes.len();
+            let literal = matches!(byte, b'!'..=b'<' | b'>'..=b'~')
+                || (matches!(byte, b' ' | b'\t') && !last);
+            let length = if literal { 1 } else { 3 };
+            // Leaves room for the `=` of a soft line break, unless this ends the line.
+            let room = if last {
+                MAX_LINE_LENGTH
+            } else {
+                MAX_LINE_LENGTH - 1
+            };
+            if line_length + length > room {
+                encoded.push_str("=\r\n");
+                line_length = 0;
+            }
+            if literal {
+                encoded.push(char::from(*byte));
+            } else {
+                write!(encoded, "={:02X}", byte).expect("writing to a string");
+            }
+            line_length += length;
+        }
+    }
+    encoded
+}
+
+/// Moves the spans of the syntax errors of a section to count from the start of the whole
+/// mail template.
+fn shift_spans(error: TemplateError, offset: usize) -> TemplateError {
+    match error {
+        TemplateError::Syntax { message, span } => TemplateError::Syntax {
+            message,
+            span: Span::new(span.start + offset, span.end + offset),
+        },
+        TemplateError::Multiple { errors } => TemplateError::Multiple {
+            errors: errors
+                .into_iter()
+                .map(|error| shift_spans(error, offset))
+                .collect(),
+        },
+        error => error,
+    }
+}
+
+fn invalid_mail(message: &str) -> TemplateError {
+    TemplateError::InvalidMail {
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_long_lines_and_special_characters_it_encodes_them_as_quoted_printable() {
+        let line = "a".repeat(80);
+        assert_eq!(
+            quoted_printable(&format!("{}\ncafé = 1 \nend", line)),
+            format!(
+                "{}=\r\n{}\r\ncaf=C3=A9 =3D 1=20\r\nend",
+                "a".repeat(75),
+                "a".repeat(5)
+            )
+        );
+    }
+
+    #[test]
+    fn given_a_non_ascii_header_it_encodes_it_as_encoded_words() {
+        assert_eq!(encode_words("Plain subject", "Subject"), "Plain subject");
+        assert_eq!(
+            encode_words("Votre facture n°1", "Subject"),
+            "=?utf-8?q?Votre_facture_n=C2=B01?="
+        );
+        let long = encode_words(&"é".repeat(20), "Subject");
+        assert!(long.split(' ').all(|word| word.len() <= MAX_LINE_LENGTH));
+        assert_eq!(long.split(' ').count(), 3);
+    }
+
+    #[test]
+    fn given_a_long_non_ascii_subject_it_keeps_every_header_line_within_the_maximum_length() {
+        let mut message = String::new();
+        let subject = encode_words(&"é".repeat(20), "Subject");
+        write_header(&mut message, "Subject", &subject);
+        let lines: Vec<_> = message.split_terminator("\r\n").collect();
+        assert!(lines[0].starts_with("Subject: =?utf-8?q?"));
+        assert!(lines.iter().all(|line| line.len() <= MAX_LINE_LENGTH));
+    }
+}
@@ -0,0 +1,8 @@
+use std::io;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let code = kata_template_engine::cli::run(&args, &mut io::stdout(), &mut io::stderr());
+    process::exit(code);
+}
@@ -0,0 +1,199 @@
+use crate::limits::MAX_NESTING_DEPTH;
+use crate::template::{too_deep, Node};
+use crate::{Span, TemplateError};
+
+/// Parses a Mustache template into the nodes the renderer knows.
+///
+/// Section, inverted section, comment, partial and delimiter tags alone on their line are
+/// standalone: the whole line is removed from the output, and the indentation of a standalone
+/// partial is added to each line of the partial.
+pub(crate) fn parse_mustache(text: &str) -> Result<Vec<Node>, TemplateError> {
+    let mut parser = MustacheParser {
+        text,
+        position: 0,
+        open: "{{".to_string(),
+        close: "}}".to_string(),
+        depth: 0,
+    };
+    let (nodes, _) = parser.parse_nodes(None)?;
+    Ok(nodes)
+}
+
+struct MustacheParser<'a> {
+    text: &'a str,
+    position: usize,
+    open: String,
+    close: String,
+    /// The number of sections being parsed within each other.
+    depth: usize,
+}
+
+/// A `{{...}}` tag, found from `start` to `end`.
+struct Tag<'a> {
+    sigil: Option<char>,
+    content: &'a str,
+    start: usize,
+    end: usize,
+}
+
+impl<'a> MustacheParser<'a> {
+    /// Parses nodes until the end of the text or until the `{{/closing}}` tag, returning them
+    /// along with the end of the closing tag.
+    fn parse_nodes(&mut self, closing: Option<&str>) -> Result<(Vec<Node>, usize), TemplateError> {
+        let mut nodes = Vec::new();
+        loop {
+            let Some(offset) = self.text[self.position..].find(&self.open) else {
+                self.push_text(&mut nodes, self.text.len());
+                self.position = self.text.len();
+                return match closing {
+                    Some(name) => Err(syntax_error(
+                        format!("unclosed section `{}`", name),
+                        Span::new(self.text.len(), self.text.len()),
+                    )),
+                    None => Ok((nodes, self.text.len())),
+                };
+            };
+            let tag = self.read_tag(self.position + offset)?;
+            let standalone = match tag.sigil {
+                Some('#' | '^' | '/' | '!' | '>' | '=') => self.standalone_line(&tag),
+                _ => None,
+            };
+            let (text_end, next) = match standalone {
+                Some((line_start, line_end)) => (line_start, line_end),
+                None => (tag.start, tag.end),
+            };
+            self.push_text(&mut nodes, text_end);
+            self.position = next;
+            let name = tag.content.trim();
+            let span = Span::new(tag.start, tag.end);
+            match tag.sigil {
+                Some('!') => {}
+                Some('=') => self.set_delimiters(name, span)?,
+                Some('#' | '^') => {
+                    check_name(name, span)?;
+                    if self.depth == MAX_NESTING_DEPTH {
+                        return Err(too_deep(span));
+                    }
+                    self.depth += 1;
+                    let (section, end) = self.parse_nodes(Some(name))?;
+                    self.depth -= 1;
+                    nodes.push(Node::Section {
+                        path: name.to_string(),
+                        inverted: tag.sigil == Some('^'),
+                        nodes: section,
+                        span: Span::new(tag.start, end),
+                    });
+                }
+                Some('/') => {
+                    return match closing {
+                        Some(expected) if expected == name => Ok((nodes, tag.end)),
+                        Some(expected) => Err(syntax_error(
+                            format!("unexpected closing tag `{}`, expected `{}`", name, expected),
+                            span,
+                        )),
+                        None => Err(syntax_error(
+                            format!("closing tag `{}` without section", name),
+                            span,
+                        )),
+                    }
+                }
+                Some('>') => {
+                    check_name(name, span)?;
+                    nodes.push(Node::Partial {
+                        name: name.to_string(),
+                        indent: standalone
+                            .map(|(line_start, _)| self.text[line_start..tag.start].to_string()),
+                        span,
+                    });
+                }
+                sigil => {
+                    check_name(name, span)?;
+                    nodes.push(Node::Variable {
+                        path: name.to_string(),
+                        raw: sigil.is_some(),
+                        span,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Reads the tag opening at `start`, with its sigil: `#`, `^`, `/`, `!`, `>`, `=`, `&`, or
+    /// `{` for a triple mustache.
+    fn read_tag(&self, start: usize) -> Result<Tag<'a>, TemplateError> {
+        let content_start = start + self.open.len();
+        let sigil = self.text[content_start..]
+            .chars()
+            .next()
+            .filter(|sigil| "#^/!>=&{".contains(*sigil));
+        let closing = match sigil {
+            Some('{') => format!("}}{}", self.close),
+            Some('=') => format!("={}", self.close),
+            _ => self.close.clone(),
+        };
+        let body_start = content_start + sigil.map_or(0, char::len_utf8);
+        let body_end = self.text[body_start..]
+            .find(&closing)
+            .map(|offset| body_start + offset)
+            .ok_or_else(|| {
+                syntax_error(
+                    "unclosed tag".to_string(),
+                    Span::new(start, self.text.len()),
+                )
+            })?;
+        Ok(Tag {
+            sigil,
+            content: &self.text[body_start..body_end],
+            start,
+            end: body_end + closing.len(),
+        })
+    }
+
+    /// Returns the start of the line of a tag and the start of the next line when the tag is
+    /// alone on its line, apart from spaces and tabs.
+    fn standalone_line(&self, tag: &Tag) -> Option<(usize, usize)> {
+        let line_start = self.text[..tag.start]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        let line_end = self.text[tag.end..]
+            .find('\n')
+            .map_or(self.text.len(), |index| tag.end + index + 1);
+        let blank = |text: &str| text.chars().all(|c| matches!(c, ' ' | '\t' | '\r' | '\n'));
+        (blank(&self.text[line_start..tag.start]) && blank(&self.text[tag.end..line_end]))
+            .then_some((line_start, line_end))
+    }
+
+    fn set_delimiters(&mut self, content: &str, span: Span) -> Result<(), TemplateError> {
+        match content.split_whitespace().collect::<Vec<_>>()[..] {
+            [open, close] if !open.contains('=') && !close.contains('=') => {
+                self.open = open.to_string();
+                self.close = close.to_string();
+                Ok(())
+            }
+            _ => Err(syntax_error(
+                format!("invalid delimiters `{}`", content),
+                span,
+            )),
+        }
+    }
+
+    fn push_text(&self, nodes: &mut Vec<Node>, end: usize) {
+        if end > self.position {
+            nodes.push(Node::Text(
+                self.text[self.position..end].to_string(),
+                Span::new(self.position, end),
+            ));
+        }
+    }
+}
+
+fn check_name(name: &str, span: Span) -> Result<(), TemplateError> {
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(syntax_error(format!("invalid tag name `{}`", name), span));
+    }
+    Ok(())
+}
+
+fn syntax_error(message: String, span: Span) -> TemplateError {
+    TemplateError::Syntax { message, span }
+}
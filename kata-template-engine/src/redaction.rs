@@ -0,0 +1,210 @@
+use serde_json::Value;
+
+use crate::{Span, TemplateError};
+
+/// The text written in place of sensitive values in debug output, warnings and errors.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Whether a dotted path of the context is, or is inside, one of the `sensitive` paths.
+pub(crate) fn is_sensitive(path: &str, sensitive: &[String]) -> bool {
+    sensitive.iter().any(|sensitive| {
+        path == sensitive
+            || path
+                .strip_prefix(sensitive.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Replaces the byte ranges `spans` of `text`, sorted and not overlapping, with `REDACTED`.
+pub(crate) fn redact_spans(text: &str, spans: &[Span]) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut position = 0;
+    for span in spans {
+        redacted.push_str(&text[position..span.start]);
+        redacted.push_str(REDACTED);
+        position = span.end;
+    }
+    redacted.push_str(&text[position..]);
+    redacted
+}
+
+/// Replaces every occurrence of the `secrets` in `text` with `REDACTED`, longest first so that
+/// a secret containing another one is masked whole.
+pub(crate) fn redact_secrets(text: &str, secrets: &[String]) -> String {
+    let mut secrets: Vec<_> = secrets.iter().filter(|secret| !secret.is_empty()).collect();
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    secrets.into_iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret.as_str(), REDACTED)
+    })
+}
+
+/// Collects the texts of the string and number leaves of a sensitive `value`, which are all
+/// secrets when the value is an object or an array.
+pub(crate) fn collect_secrets(value: &Value, secrets: &mut Vec<String>) {
+    match value {
+        Value::String(text) => secrets.push(text.clone()),
+        Value::Number(number) => secrets.push(number.to_string()),
+        Value::Array(elements) => elements
+            .iter()
+            .for_each(|element| collect_secrets(element, secrets)),
+        Value::Object(entries) => entries
+            .values()
+            .for_each(|entry| collect_secrets(entry, secrets)),
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Masks the `secrets` in the free text of an error, such as the message of a failed function,
+/// the names and values it quotes, and the errors it holds.
+pub(crate) fn redact_error(error: TemplateError, secrets: &[String]) -> TemplateError {
+    let redact = |text: String| redact_secrets(&text, secrets);
+    match error {
+        TemplateError::Syntax { message, span } => TemplateError::Syntax {
+            message: redact(message),
+            span,
+        },
+        TemplateError::UnknownFunction { name, span } => TemplateError::UnknownFunction {
+            name: redact(name),
+            span,
+        },
+        TemplateError::WrongArgumentCount {
+            function,
+            expected,
+            found,
+            span,
+        } => TemplateError::WrongArgumentCount {
+            function: redact(function),
+            expected,
+            found,
+            span,
+        },
+        TemplateError::WrongArgumentType {
+            function,
+            expected,
+            found,
+            span,
+        } => TemplateError::WrongArgumentType {
+            function: redact(function),
+            expected,
+            found: redact(found),
+            span,
+        },
+        TemplateError::FunctionFailed {
+            function,
+            message,
+            span,
+        } => TemplateError::FunctionFailed {
+            function: redact(function),
+            message: redact(message),
+            span,
+        },
+        TemplateError::InvalidOperands {
+            operator,
+            operands,
+            span,
+        } => TemplateError::InvalidOperands {
+            operator,
+            operands: operands.into_iter().map(redact).collect(),
+            span,
+        },
+        TemplateError::NotIterable { found, span } => TemplateError::NotIterable {
+            found: redact(found),
+            span,
+        },
+        TemplateError::UnknownTemplate { name, span } => TemplateError::UnknownTemplate {
+            name: redact(name),
+            span,
+        },
+        TemplateError::InvalidPattern { variable, message } => TemplateError::InvalidPattern {
+            variable,
+            message: redact(message),
+        },
+        TemplateError::InvalidOutputPath { path } => {
+            TemplateError::InvalidOutputPath { path: redact(path) }
+        }
+        TemplateError::InFile { path, error } => TemplateError::InFile {
+            path,
+            error: Box::new(redact_error(*error, secrets)),
+        },
+        TemplateError::InvalidFrontMatter { message } => TemplateError::InvalidFrontMatter {
+            message: redact(message),
+        },
+        TemplateError::MissingRequiredVariable { name } => {
+            TemplateError::MissingRequiredVariable { name: redact(name) }
+        }
+        TemplateError::UnknownLocale { locale } => TemplateError::UnknownLocale {
+            locale: redact(locale),
+        },
+        TemplateError::UnknownMessage { key, locale, span } => TemplateError::UnknownMessage {
+            key: redact(key),
+            locale: redact(locale),
+            span,
+        },
+        TemplateError::InvalidPluralArgument { found, span } => {
+            TemplateError::InvalidPluralArgument {
+                found: redact(found),
+                span,
+            }
+        }
+        TemplateError::UnknownEscaper { name, span } => TemplateError::UnknownEscaper {
+            name: redact(name),
+            span,
+        },
+        TemplateError::ExpansionCycle { cycle } => TemplateError::ExpansionCycle {
+            cycle: cycle.into_iter().map(redact).collect(),
+        },
+        TemplateError::Multiple { errors } => TemplateError::Multiple {
+            errors: errors
+                .into_iter()
+                .map(|error| redact_error(error, secrets))
+                .collect(),
+        },
+        TemplateError::InvalidMail { message } => TemplateError::InvalidMail {
+            message: redact(message),
+        },
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn given_an_object_it_collects_its_string_and_number_leaves_as_secrets() {
+        let mut secrets = Vec::new();
+        collect_secrets(
+            &json!({"user": "admin", "pin": 4711, "keys": ["k1", {"old": "k0"}], "on": true}),
+            &mut secrets,
+        );
+        secrets.sort();
+        assert_eq!(secrets, vec!["4711", "admin", "k0", "k1"]);
+    }
+
+    #[test]
+    fn given_errors_quoting_secrets_it_masks_them_in_every_nested_error() {
+        let secrets = vec!["s3cr3t".to_string()];
+        let error = TemplateError::InFile {
+            path: "page.txt".into(),
+            error: Box::new(TemplateError::Multiple {
+                errors: vec![
+                    TemplateError::Syntax {
+                        message: "unknown tag `s3cr3t`".to_string(),
+                        span: Span::new(0, 6),
+                    },
+                    TemplateError::UnknownTemplate {
+                        name: "s3cr3t".to_string(),
+                        span: None,
+                    },
+                    TemplateError::InvalidMail {
+                        message: "bad header s3cr3t".to_string(),
+                    },
+                ],
+            }),
+        };
+        let redacted = redact_error(error, &secrets).to_string();
+        assert!(!redacted.contains("s3cr3t"));
+        assert_eq!(redacted.matches(REDACTED).count(), 3);
+    }
+}
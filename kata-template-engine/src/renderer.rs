@@ -0,0 +1,1385 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde_json::{Number, Value};
+
+use crate::cache::TemplateCache;
+use crate::coverage::{selector_name, Coverage, RegionKind};
+use crate::escape::{Escape, Escaper};
+use crate::expression::{parse_expression, BinaryOperator, Expression, UnaryOperator};
+use crate::format::{formatting_function, has_locale_format};
+use crate::front_matter::FrontMatter;
+use crate::function::{builtin_functions, type_name, Env, Function};
+use crate::limits::{exceeds, Limits};
+use crate::locale::{
+    locale_fallbacks, normalize_locale, plural_rule, Operands, PluralCategory, PluralRule,
+};
+use crate::redaction::{collect_secrets, is_sensitive, redact_error, redact_secrets, REDACTED};
+use crate::source_map::{SourceMap, SourceMapEntry};
+use crate::template::{Case, ChoiceKind, Node, Selector, Template};
+use crate::{ParsedTemplate, Span, TemplateError};
+
+/// The locale of a new renderer.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Renders templates against a JSON context, with a registry of functions callable from
+/// placeholders and a registry of named templates that can be included from other templates.
+///
+/// A new renderer knows the built-in functions `now`, `len` and `range`, and the
+/// formatting functions `number`, `currency`, `percent` and `date`, which write numbers and
+/// ISO-8601 dates as its locale does, and the escapers listed by `Escape`. It renders with the
+/// default `Limits` in the `DEFAULT_LOCALE`. The `env` function, which reads the environment of
+/// the process, is only known after `enable_env`.
+pub struct Renderer {
+    functions: HashMap<String, Box<dyn Function>>,
+    escapers: HashMap<String, Box<dyn Escaper>>,
+    templates: HashMap<String, Template>,
+    limits: Limits,
+    sensitive: Vec<String>,
+    locale: String,
+    plural_rule: PluralRule,
+    /// The messages of each locale, by normalized locale and then by key.
+    catalogs: HashMap<String, HashMap<String, Template>>,
+    expand_values: bool,
+    indent_values: bool,
+}
+
+enum EvaluationError {
+    Undefined(String),
+    Failed(TemplateError),
+}
+
+impl From<TemplateError> for EvaluationError {
+    fn from(error: TemplateError) -> Self {
+        EvaluationError::Failed(error)
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Renderer::new()
+    }
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer {
+            functions: builtin_functions(),
+            escapers: HashMap::new(),
+            templates: HashMap::new(),
+            limits: Limits::default(),
+            sensitive: Vec::new(),
+            locale: DEFAULT_LOCALE.to_string(),
+            plural_rule: plural_rule(DEFAULT_LOCALE).expect("the default locale is supported"),
+            catalogs: HashMap::new(),
+            expand_values: false,
+            indent_values: false,
+        }
+    }
+
+    /// Registers a function under `name`, replacing any function already registered with it.
+    /// A registered function takes precedence over a formatting function with the same name.
+    pub fn register_function(&mut self, name: &str, function: impl Function + 'static) {
+        self.functions.insert(name.to_string(), Box::new(function));
+    }
+
+    /// Registers the built-in function `env(name)`, which renders the value of an environment
+    /// variable and fails when it is not set. It is left out of a new renderer so that templates
+    /// from other people cannot read the secrets the environment often holds.
+    pub fn enable_env(&mut self) {
+        self.functions.insert("env".to_string(), Box::new(Env));
+    }
+
+    /// Registers an escaper under `name`, so that templates can choose it with `escape: name` in
+    /// their front matter or with `${value | escape("name")}`. A registered escaper takes
+    /// precedence over a built-in one with the same name.
+    pub fn register_escaper(&mut self, name: &str, escaper: impl Escaper + 'static) {
+        self.escapers.insert(name.to_string(), Box::new(escaper));
+    }
+
+    /// Parses a template and registers it under `name`, so that it can be rendered with
+    /// `render_template` or included from other templates with `{% include "name" %}`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the template text cannot be parsed.
+    pub fn add_template(&mut self, name: &str, template_text: &str) -> Result<(), TemplateError> {
+        let template = Template::parse(template_text)?;
+        self.templates.insert(name.to_string(), template);
+        Ok(())
+    }
+
+    /// Registers the template compiled from `template_text` under `name` like `add_template`,
+    /// taking it from `cache` when the cache holds it for the same text, and adding it to the
+    /// cache otherwise.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the template text cannot be parsed.
+    pub fn add_cached_template(
+        &mut self,
+        name: &str,
+        template_text: &str,
+        cache: &mut TemplateCache,
+    ) -> Result<(), TemplateError> {
+        let template = cache.compile(name, template_text)?;
+        self.templates.insert(name.to_string(), template);
+        Ok(())
+    }
+
+    /// Registers a parsed template under `name`.
+    pub(crate) fn insert_template(&mut self, name: &str, template: Template) {
+        self.templates.insert(name.to_string(), template);
+    }
+
+    /// Parses a Mustache template and registers it under `name`, so that it can be rendered
+    /// with `render_template` or included from other templates, as a `{{> name}}` partial or
+    /// with `{% include "name" %}`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the template text cannot be parsed.
+    pub fn add_mustache_template(
+        &mut self,
+        name: &str,
+        template_text: &str,
+    ) -> Result<(), TemplateError> {
+        let template = Template::parse_mustache(template_text)?;
+        self.templates.insert(name.to_string(), template);
+        Ok(())
+    }
+
+    /// Returns the registered templates sorted by name, so that tools can list them along with
+    /// the description and metadata of their front matter.
+    pub fn templates(&self) -> Vec<(&str, &Template)> {
+        let mut templates: Vec<_> = self
+            .templates
+            .iter()
+            .map(|(name, template)| (name.as_str(), template))
+            .collect();
+        templates.sort_by_key(|(name, _)| *name);
+        templates
+    }
+
+    /// Marks a variable or a dotted path of the context, such as `api_key` or `user.password`,
+    /// as sensitive.
+    ///
+    /// Sensitive values are rendered as usual, but the parts of the output written from them
+    /// are listed in `ParsedTemplate::sensitive` and masked by its `Debug`, flagged in source
+    /// maps, and their text is replaced by `REDACTED` in warnings and error messages.
+    pub fn mark_sensitive(&mut self, path: &str) {
+        self.sensitive.push(path.to_string());
+    }
+
+    /// Sets the locale whose plural rules choose the cases of `plural` messages, whose
+    /// catalog `{% message %}` tags read from, and whose conventions the formatting functions
+    /// follow. A new renderer uses `DEFAULT_LOCALE`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::UnknownLocale` if the language of the locale has
+    /// no plural rules or no formatting conventions.
+    pub fn set_locale(&mut self, locale: &str) -> Result<(), TemplateError> {
+        if !has_locale_format(locale) {
+            return Err(TemplateError::UnknownLocale {
+                locale: locale.to_string(),
+            });
+        }
+        self.plural_rule = supported_plural_rule(locale)?;
+        self.locale = locale.to_string();
+        Ok(())
+    }
+
+    /// Sets whether the placeholders written in text values, as in
+    /// `"url": "https://${host}:${port}"`, are expanded against the same context before the
+    /// values are written. Expanded values can reference other values up to
+    /// `Limits::max_expansion_depth` levels deep. A new renderer writes values as they are.
+    ///
+    /// Rendering fails with `TemplateError::ExpansionCycle` when values reference each other.
+    pub fn set_recursive_expansion(&mut self, enabled: bool) {
+        self.expand_values = enabled;
+    }
+
+    /// Sets whether the lines of every value after the first are indented to the column of its
+    /// placeholder, as the `indent` filter does for a single placeholder, so that a multi-line
+    /// value stays within its block of YAML or Python. The line endings of the value are
+    /// converted to the ones of the template, and empty lines are left empty. A new renderer
+    /// writes values as they are.
+    pub fn set_value_indentation(&mut self, enabled: bool) {
+        self.indent_values = enabled;
+    }
+
+    /// Parses messages and adds them to the catalog of `locale`, replacing the messages already
+    /// added under the same keys.
+    ///
+    /// A `{% message "key" %}` tag renders the message of the locale of the renderer, falling
+    /// back from a regional locale such as `pt-BR` to its language `pt`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::UnknownLocale` if the language of the locale has
+    /// no plural rules, and the errors of `Template::parse` if a message cannot be parsed.
+    pub fn add_catalog(
+        &mut self,
+        locale: &str,
+        messages: &HashMap<String, String>,
+    ) -> Result<(), TemplateError> {
+        supported_plural_rule(locale)?;
+        let mut parsed = HashMap::new();
+        for (key, text) in messages {
+            parsed.insert(key.clone(), Template::parse(text)?);
+        }
+        self.catalogs
+            .entry(normalize_locale(locale))
+            .or_default()
+            .extend(parsed);
+        Ok(())
+    }
+
+    fn message(&self, key: &str) -> Option<&Template> {
+        locale_fallbacks(&self.locale)
+            .iter()
+            .find_map(|locale| self.catalogs.get(locale)?.get(key))
+    }
+
+    /// Sets the limits every render must stay within.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Renders the template registered under `name`.
+    ///
+    /// # Errors
+    ///
+    /// This function returns `TemplateError::UnknownTemplate` if no template is registered under
+    /// `name`, and otherwise the same errors as `render`.
+    pub fn render_template(
+        &self,
+        name: &str,
+        context: &Value,
+    ) -> Result<ParsedTemplate, TemplateError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownTemplate {
+                name: name.to_string(),
+                span: None,
+            })?;
+        self.render(template, context)
+    }
+
+    /// Renders the template registered under `name` like `render_template`, along with the
+    /// `SourceMap` of the rendered text.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the same errors as `render_template`.
+    pub fn render_template_with_source_map(
+        &self,
+        name: &str,
+        context: &Value,
+    ) -> Result<(ParsedTemplate, SourceMap), TemplateError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownTemplate {
+                name: name.to_string(),
+                span: None,
+            })?;
+        let (parsed_template, entries) =
+            self.render_tracked(template, Some(name), context, true, None)?;
+        Ok((parsed_template, SourceMap { entries }))
+    }
+
+    /// Renders the template registered under `name` like `render_template`, counting the
+    /// regions it renders in `coverage`.
+    pub(crate) fn render_template_with_coverage(
+        &self,
+        name: &str,
+        context: &Value,
+        coverage: &mut Coverage,
+    ) -> Result<ParsedTemplate, TemplateError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| TemplateError::UnknownTemplate {
+                name: name.to_string(),
+                span: None,
+            })?;
+        self.render_tracked(template, Some(name), context, false, Some(coverage))
+            .map(|(parsed_template, _)| parsed_template)
+    }
+
+    /// Renders a template using a JSON object as context.
+    ///
+    /// The defaults of the front matter of the template, and of the templates it includes, are
+    /// used for the variables the context does not define, and the values of placeholders are
+    /// escaped as the front matter says. Placeholders whose expression uses a variable missing
+    /// from the context are left as is and generate a warning, like `parse_template` does. In
+    /// conditions and loops, a missing variable is taken as `null` and also generates a warning.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if a placeholder calls an unknown function, calls a
+    /// function with the wrong number or types of arguments, or the function itself fails, if
+    /// an operator is applied to values of the wrong types, if a number is divided by zero, if a
+    /// loop goes over something that is not an array, if an included template is not
+    /// registered, if a variable required by a front matter is not defined, or if the render
+    /// exceeds any of the `Limits`.
+    pub fn render(
+        &self,
+        template: &Template,
+        context: &Value,
+    ) -> Result<ParsedTemplate, TemplateError> {
+        self.render_tracked(template, None, context, false, None)
+            .map(|(parsed_template, _)| parsed_template)
+    }
+
+    /// Renders a template like `render`, along with the `SourceMap` that links each byte range
+    /// of the rendered text to the literal text or placeholder that produced it.
+    ///
+    /// # Errors
+    ///
+    /// This function returns the same errors as `render`.
+    pub fn render_with_source_map(
+        &self,
+        template: &Template,
+        context: &Value,
+    ) -> Result<(ParsedTemplate, SourceMap), TemplateError> {
+        let (parsed_template, entries) =
+            self.render_tracked(template, None, context, true, None)?;
+        Ok((parsed_template, SourceMap { entries }))
+    }
+
+    /// Renders `template`, registered under `name` if it is, collecting the entries of its
+    /// source map if `source_map` is set, and redacting the sensitive values from the warnings
+    /// and the error.
+    fn render_tracked(
+        &self,
+        template: &Template,
+        name: Option<&str>,
+        context: &Value,
+        source_map: bool,
+        coverage: Option<&mut Coverage>,
+    ) -> Result<(ParsedTemplate, Vec<SourceMapEntry>), TemplateError> {
+        let mut rendering = Rendering::new(self, context);
+        rendering.template = name.map(str::to_string);
+        rendering.covered = rendering.template.clone();
+        if source_map {
+            rendering.source_map = Some(Vec::new());
+        }
+        rendering.coverage = coverage;
+        if let Err(error) = rendering.render_body(template) {
+            return Err(redact_error(error, &rendering.secrets));
+        }
+        let warnings = rendering
+            .warnings
+            .iter()
+            .map(|warning| redact_secrets(warning, &rendering.secrets))
+            .collect();
+        let parsed_template = ParsedTemplate {
+            text: rendering.text,
+            warnings,
+            sensitive: rendering.sensitive,
+        };
+        Ok((parsed_template, rendering.source_map.unwrap_or_default()))
+    }
+
+    fn escape(&self, escape: &Escape, text: &str, span: Span) -> Result<String, TemplateError> {
+        let escaper = match self.escapers.get(escape.name()) {
+            Some(escaper) => escaper.as_ref(),
+            None => escape
+                .builtin()
+                .ok_or_else(|| TemplateError::UnknownEscaper {
+                    name: escape.name().to_string(),
+                    span,
+                })?,
+        };
+        Ok(escaper.escape(text))
+    }
+
+    /// Evaluates a condition written like the ones of `{% if %}` tags, taking missing variables
+    /// as `null`.
+    pub(crate) fn evaluate_condition(
+        &self,
+        condition: &str,
+        context: &Value,
+    ) -> Result<bool, TemplateError> {
+        let condition = parse_expression(condition, 0)?;
+        let value = Rendering::new(self, context).evaluate_lenient(&condition)?;
+        Ok(is_truthy(&value))
+    }
+}
+
+/// The state of a single call to `Renderer::render`.
+struct Rendering<'a> {
+    renderer: &'a Renderer,
+    context: &'a Value,
+    /// The variables bound by the enclosing `{% for %}` blocks and the contexts of the
+    /// enclosing Mustache sections, innermost last.
+    scopes: Vec<Scope>,
+    text: String,
+    warnings: Vec<String>,
+    steps: usize,
+    iterations: usize,
+    depth: usize,
+    /// The front matters of the template being rendered and of the templates including it,
+    /// innermost last.
+    front_matters: Vec<&'a FrontMatter>,
+    /// The name of the registered template being rendered, `None` for the one given to `render`.
+    template: Option<String>,
+    /// The name of the template whose regions are counted in the coverage, `None` within the
+    /// messages of catalogs and the expanded values, whose spans are not the ones of a template
+    /// file.
+    covered: Option<String>,
+    /// The entries of the source map, only collected when one is requested.
+    source_map: Option<Vec<SourceMapEntry>>,
+    /// The byte ranges of the output written from sensitive variables.
+    sensitive: Vec<Span>,
+    /// The texts of the sensitive values, masked in warnings and errors.
+    secrets: Vec<String>,
+    /// The indentation added to the lines of the standalone Mustache partials being rendered.
+    indent: String,
+    /// Whether the next text of the template starts a line, to be indented first.
+    pending_indent: bool,
+    /// The variables whose values are being expanded, outermost first.
+    expanding: Vec<String>,
+    /// The line endings of the template being rendered and of the templates including it,
+    /// innermost last.
+    line_endings: Vec<Option<&'a str>>,
+    /// The coverage the rendered regions are counted in, only when one is requested.
+    coverage: Option<&'a mut Coverage>,
+}
+
+/// A variable bound by a `{% for %}` block, or the context of a Mustache section.
+struct Scope {
+    /// The name of the variable, `None` for a section, whose value defines its keys.
+    variable: Option<String>,
+    value: Value,
+    /// The path of the value in the context, such as `items.2`, when the loop goes over a
+    /// variable.
+    path: Option<String>,
+}
+
+impl<'a> Rendering<'a> {
+    fn new(renderer: &'a Renderer, context: &'a Value) -> Rendering<'a> {
+        let mut secrets = Vec::new();
+        for value in renderer
+            .sensitive
+            .iter()
+            .filter_map(|path| lookup_path(context, path))
+        {
+            collect_secrets(value, &mut secrets);
+        }
+        Rendering {
+            renderer,
+            context,
+            scopes: Vec::new(),
+            text: String::new(),
+            warnings: Vec::new(),
+            steps: 0,
+            iterations: 0,
+            depth: 0,
+            front_matters: Vec::new(),
+            template: None,
+            covered: None,
+            source_map: None,
+            sensitive: Vec::new(),
+            secrets,
+            indent: String::new(),
+            pending_indent: false,
+            expanding: Vec::new(),
+            line_endings: Vec::new(),
+            coverage: None,
+        }
+    }
+
+    fn render_nodes(&mut self, nodes: &[Node]) -> Result<(), TemplateError> {
+        for node in nodes {
+            self.steps += 1;
+            if exceeds(self.steps, self.renderer.limits.max_steps) {
+                return Err(TemplateError::StepLimitExceeded {
+                    limit: self.steps - 1,
+                });
+            }
+            match node {
+                Node::Text(literal, span) => {
+                    let start = self.text.len();
+                    self.write_text(literal)?;
+                    self.map(start, *span, Vec::new(), false);
+                }
+                Node::Placeholder {
+                    expression,
+                    spec,
+                    escape,
+                    indent,
+                    source,
+                    span,
+                } => {
+                    self.flush_indent(*span)?;
+                    self.cover(*span, RegionKind::Placeholder);
+                    let start = self.text.len();
+                    let mut expanded_secret = false;
+                    match self.evaluate(expression) {
+                        Ok(value) => {
+                            let value = match value {
+                                Value::String(text)
+                                    if self.renderer.expand_values && text.contains("${") =>
+                                {
+                                    let (text, used_secret) =
+                                        self.expand(&text, expression, source, *span)?;
+                                    expanded_secret = used_secret;
+                                    Value::String(text)
+                                }
+                                value => value,
+                            };
+                            // Expanded values are escaped once, as part of the outermost value.
+                            let escape = escape.as_ref().filter(|_| self.expanding.is_empty());
+                            let escape = escape.or_else(|| {
+                                self.front_matters
+                                    .last()
+                                    .filter(|_| self.expanding.is_empty())
+                                    .map(|front_matter| &front_matter.escape)
+                            });
+                            let text = match spec {
+                                Some(spec) => spec.apply(&value),
+                                None => value_to_string(&value),
+                            };
+                            let text = match escape {
+                                Some(escape) => self.renderer.escape(escape, &text, *span)?,
+                                None => text,
+                            };
+                            let text = match *indent || self.renderer.indent_values {
+                                true => self.indent_lines(text),
+                                false => text,
+                            };
+                            self.write(&text)?
+                        }
+                        Err(EvaluationError::Undefined(name)) => {
+                            self.write(source)?;
+                            self.warnings
+                                .push(format!("Variable {} not replaced", name));
+                        }
+                        Err(EvaluationError::Failed(error)) => return Err(error),
+                    }
+                    self.track(start, *span, &expression.variable_paths(), expanded_secret);
+                }
+                Node::Variable { path, raw, span } => {
+                    self.flush_indent(*span)?;
+                    self.cover(*span, RegionKind::Placeholder);
+                    let start = self.text.len();
+                    match self.lookup(path) {
+                        Some(value) => {
+                            let text = value_to_string(&value);
+                            let text = match raw {
+                                true => text,
+                                false => self.renderer.escape(&Escape::Html, &text, *span)?,
+                            };
+                            let text = match self.renderer.indent_values {
+                                true => self.indent_lines(text),
+                                false => text,
+                            };
+                            self.write(&text)?;
+                        }
+                        None => self
+                            .warnings
+                            .push(format!("Variable {} not replaced", path)),
+                    }
+                    self.track(start, *span, &[path], false);
+                }
+                Node::Section {
+                    path,
+                    inverted,
+                    nodes,
+                    span,
+                } => {
+                    let (elements, origin) = match self.lookup(path) {
+                        None | Some(Value::Null) | Some(Value::Bool(false)) => (Vec::new(), None),
+                        Some(Value::Array(elements)) => (elements, Some(self.resolve_path(path))),
+                        Some(value) => (vec![value], None),
+                    };
+                    if *inverted {
+                        if elements.is_empty() {
+                            self.cover(*span, RegionKind::Section);
+                            self.render_nodes(nodes)?;
+                        }
+                        continue;
+                    }
+                    for (index, element) in elements.into_iter().enumerate() {
+                        self.count_iteration(*span)?;
+                        self.cover(*span, RegionKind::Section);
+                        self.scopes.push(Scope {
+                            variable: None,
+                            value: element,
+                            path: Some(match &origin {
+                                Some(origin) => format!("{}.{}", origin, index),
+                                None => self.resolve_path(path),
+                            }),
+                        });
+                        let rendered = self.render_nodes(nodes);
+                        self.scopes.pop();
+                        rendered?;
+                    }
+                }
+                Node::Partial { name, indent, span } => {
+                    let Some(template) = self.renderer.templates.get(name) else {
+                        self.warnings.push(format!("Partial {} not found", name));
+                        continue;
+                    };
+                    // A standalone partial indents its lines further, any other is not indented.
+                    let standalone = indent.is_some();
+                    let indent = match indent {
+                        Some(indent) => format!("{}{}", self.indent, indent),
+                        None => String::new(),
+                    };
+                    let outer_indent = std::mem::replace(&mut self.indent, indent);
+                    let outer_pending = std::mem::replace(&mut self.pending_indent, standalone);
+                    self.render_included(template, name, *span, true)?;
+                    self.indent = outer_indent;
+                    // The line of a standalone partial ends with it, so the next text starts one.
+                    self.pending_indent = standalone || outer_pending;
+                }
+                Node::If {
+                    branches,
+                    otherwise,
+                    span,
+                } => {
+                    let mut chosen = None;
+                    for branch in branches {
+                        if is_truthy(&self.evaluate_lenient(&branch.condition)?) {
+                            chosen = Some((&branch.nodes, branch.span, RegionKind::Branch));
+                            break;
+                        }
+                    }
+                    let chosen = chosen.or_else(|| {
+                        let start = branches.last()?.span.end;
+                        Some((
+                            otherwise.as_ref()?,
+                            Span::new(start, span.end),
+                            RegionKind::Else,
+                        ))
+                    });
+                    if let Some((nodes, span, kind)) = chosen {
+                        self.cover(span, kind);
+                        self.render_nodes(nodes)?;
+                    }
+                }
+                Node::For {
+                    variable,
+                    iterable,
+                    nodes,
+                    span,
+                } => {
+                    let elements = match self.evaluate_lenient(iterable)? {
+                        Value::Array(elements) => elements,
+                        Value::Null => Vec::new(),
+                        value => {
+                            return Err(TemplateError::NotIterable {
+                                found: type_name(&value).to_string(),
+                                span: iterable.span(),
+                            })
+                        }
+                    };
+                    let origin = match iterable {
+                        Expression::Variable(path, _) => Some(self.resolve_path(path)),
+                        _ => None,
+                    };
+                    for (index, element) in elements.into_iter().enumerate() {
+                        self.count_iteration(*span)?;
+                        self.cover(*span, RegionKind::Loop);
+                        self.scopes.push(Scope {
+                            variable: Some(variable.clone()),
+                            value: element,
+                            path: origin
+                                .as_ref()
+                                .map(|origin| format!("{}.{}", origin, index)),
+                        });
+                        let rendered = self.render_nodes(nodes);
+                        self.scopes.pop();
+                        rendered?;
+                    }
+                }
+                Node::Include { name, span } => {
+                    let template = self.renderer.templates.get(name).ok_or_else(|| {
+                        TemplateError::UnknownTemplate {
+                            name: name.clone(),
+                            span: Some(*span),
+                        }
+                    })?;
+                    self.render_included(template, name, *span, true)?;
+                }
+                Node::Message { key, span } => {
+                    let template = self.renderer.message(key).ok_or_else(|| {
+                        TemplateError::UnknownMessage {
+                            key: key.clone(),
+                            locale: self.renderer.locale.clone(),
+                            span: *span,
+                        }
+                    })?;
+                    self.render_included(template, key, *span, false)?;
+                }
+                Node::Choice {
+                    variable,
+                    kind,
+                    cases,
+                    span,
+                } => {
+                    let value = self.evaluate_lenient(variable)?;
+                    if let Some(case) = self.choose(&value, *kind, cases, *span)? {
+                        if self.coverage.is_some() {
+                            self.cover(*span, RegionKind::Case(selector_name(&case.selector)));
+                        }
+                        self.render_nodes(&case.nodes)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a render of a region of the template being rendered, when coverage is requested.
+    fn cover(&mut self, span: Span, kind: RegionKind) {
+        if let (Some(coverage), Some(template)) = (self.coverage.as_deref_mut(), &self.covered) {
+            coverage.hit(template, span, kind);
+        }
+    }
+
+    /// Renders a template inserted by an `{% include %}` or `{% message %}` tag, counting its
+    /// regions in the coverage if `covered` is set.
+    fn render_included(
+        &mut self,
+        template: &'a Template,
+        name: &str,
+        span: Span,
+        covered: bool,
+    ) -> Result<(), TemplateError> {
+        self.depth += 1;
+        if exceeds(self.depth, self.renderer.limits.max_include_depth) {
+            return Err(TemplateError::IncludeDepthExceeded {
+                limit: self.depth - 1,
+                span,
+            });
+        }
+        let including = self.template.replace(name.to_string());
+        let covering = std::mem::replace(&mut self.covered, covered.then(|| name.to_string()));
+        self.render_body(template)?;
+        self.covered = covering;
+        self.template = including;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Chooses the case of an ICU message for `value`: for plurals, the first case matching the
+    /// number exactly, or else the case of its plural category in the locale; for selects, the
+    /// case of its text. Falls back to the `other` case.
+    fn choose<'c>(
+        &self,
+        value: &Value,
+        kind: ChoiceKind,
+        cases: &'c [Case],
+        span: Span,
+    ) -> Result<Option<&'c Case>, TemplateError> {
+        let selector = match kind {
+            ChoiceKind::Select => Selector::Key(value_to_string(value)),
+            ChoiceKind::Plural { offset } => {
+                let number = match value {
+                    Value::Number(number) => Some(number.clone()),
+                    Value::String(text) => text.trim().parse::<Number>().ok(),
+                    _ => None,
+                }
+                .ok_or_else(|| TemplateError::InvalidPluralArgument {
+                    found: type_name(value).to_string(),
+                    span,
+                })?;
+                let exact = cases.iter().find(|case| {
+                    matches!(case.selector, Selector::Exact(exact) if Some(exact) == number.as_f64())
+                });
+                if exact.is_some() {
+                    return Ok(exact);
+                }
+                let shifted = apply(
+                    BinaryOperator::Subtract,
+                    &Value::Number(number),
+                    &Value::from(offset),
+                    span,
+                )?;
+                let category = Operands::parse(&value_to_string(&shifted))
+                    .map(|operands| (self.renderer.plural_rule)(&operands))
+                    .unwrap_or(PluralCategory::Other);
+                Selector::Category(category)
+            }
+        };
+        let other = match kind {
+            ChoiceKind::Plural { .. } => Selector::Category(PluralCategory::Other),
+            ChoiceKind::Select => Selector::Key("other".to_string()),
+        };
+        Ok(cases
+            .iter()
+            .find(|case| case.selector == selector)
+            .or_else(|| cases.iter().find(|case| case.selector == other)))
+    }
+
+    /// Renders the nodes of a template within the settings of its front matter.
+    fn render_body(&mut self, template: &'a Template) -> Result<(), TemplateError> {
+        let front_matter = template.front_matter();
+        self.front_matters.push(front_matter);
+        self.line_endings.push(template.line_ending());
+        if let Some(name) = front_matter
+            .required
+            .iter()
+            .find(|name| self.lookup(name).is_none())
+        {
+            return Err(TemplateError::MissingRequiredVariable { name: name.clone() });
+        }
+        self.render_nodes(template.nodes())?;
+        self.line_endings.pop();
+        self.front_matters.pop();
+        Ok(())
+    }
+
+    /// Renders the placeholders written in the text value of `expression` against the same
+    /// context, into a text of its own, and returns whether it wrote a sensitive value, so that
+    /// the placeholder masks its whole output.
+    fn expand(
+        &mut self,
+        text: &str,
+        expression: &Expression,
+        source: &str,
+        span: Span,
+    ) -> Result<(String, bool), TemplateError> {
+        let name = match expression {
+            Expression::Variable(path, _) => self.resolve_path(path),
+            _ => source.to_string(),
+        };
+        if let Some(start) = self.expanding.iter().position(|outer| *outer == name) {
+            let mut cycle = self.expanding[start..].to_vec();
+            cycle.push(name);
+            return Err(TemplateError::ExpansionCycle { cycle });
+        }
+        if exceeds(
+            self.expanding.len() + 1,
+            self.renderer.limits.max_expansion_depth,
+        ) {
+            return Err(TemplateError::ExpansionDepthExceeded {
+                limit: self.expanding.len(),
+            });
+        }
+        // The syntax errors of a secret quote parts of it, and their spans point into it rather
+        // than into the template, so they are reported at the placeholder instead.
+        let secret = std::iter::once(&name)
+            .chain(&self.expanding)
+            .any(|path| is_sensitive(path, &self.renderer.sensitive));
+        let template = Template::parse(text).map_err(|error| match error {
+            TemplateError::Syntax { .. } if secret => TemplateError::Syntax {
+                message: format!("invalid expanded value {}", REDACTED),
+                span,
+            },
+            error => error,
+        })?;
+        // The output of the value is mapped and masked as a whole by its placeholder.
+        let outer_text = std::mem::take(&mut self.text);
+        let outer_source_map = self.source_map.take();
+        let outer_sensitive = std::mem::take(&mut self.sensitive);
+        let covering = self.covered.take();
+        self.expanding.push(name);
+        let rendered = self.render_nodes(template.nodes());
+        self.expanding.pop();
+        self.covered = covering;
+        let used_secret = !self.sensitive.is_empty();
+        self.sensitive = outer_sensitive;
+        self.source_map = outer_source_map;
+        let expanded = std::mem::replace(&mut self.text, outer_text);
+        rendered?;
+        Ok((expanded, used_secret))
+    }
+
+    /// Indents the lines of a value after the first to the column the value starts at in the
+    /// output, and ends them like the lines of the template. Empty lines are left empty.
+    fn indent_lines(&self, text: String) -> String {
+        // An expanded value is indented as a whole, as part of the outermost value.
+        if !text.contains('\n') || !self.expanding.is_empty() {
+            return text;
+        }
+        let line = &self.text[self.text.rfind('\n').map_or(0, |index| index + 1)..];
+        let indent = match line.chars().all(|c| c == ' ' || c == '\t') {
+            true => line.to_string(),
+            false => " ".repeat(line.chars().count()),
+        };
+        let template_ending = self.line_endings.last().copied().flatten();
+        let mut indented = String::with_capacity(text.len());
+        for (index, line) in text.split_inclusive('\n').enumerate() {
+            let (content, ending) = match line.strip_suffix('\n') {
+                Some(content) => match content.strip_suffix('\r') {
+                    Some(content) => (content, "\r\n"),
+                    None => (content, "\n"),
+                },
+                None => (line, ""),
+            };
+            if index > 0 && !content.is_empty() {
+                indented.push_str(&indent);
+            }
+            indented.push_str(content);
+            if !ending.is_empty() {
+                indented.push_str(template_ending.unwrap_or(ending));
+            }
+        }
+        indented
+    }
+
+    fn count_iteration(&mut self, span: Span) -> Result<(), TemplateError> {
+        self.iterations += 1;
+        if exceeds(self.iterations, self.renderer.limits.max_loop_iterations) {
+            return Err(TemplateError::LoopLimitExceeded {
+                limit: self.iterations - 1,
+                span,
+            });
+        }
+        Ok(())
+    }
+
+    /// Writes literal text of the template, indenting its lines inside standalone partials.
+    fn write_text(&mut self, text: &str) -> Result<(), TemplateError> {
+        if self.indent.is_empty() {
+            return self.write(text);
+        }
+        for line in text.split_inclusive('\n') {
+            if self.pending_indent {
+                self.pending_indent = false;
+                let indent = self.indent.clone();
+                self.write(&indent)?;
+            }
+            self.write(line)?;
+            self.pending_indent = line.ends_with('\n');
+        }
+        Ok(())
+    }
+
+    /// Writes the indentation of the line a value starts, if the template text has not yet,
+    /// mapped to the placeholder at `span` apart from the value, which may be sensitive.
+    fn flush_indent(&mut self, span: Span) -> Result<(), TemplateError> {
+        if self.pending_indent {
+            self.pending_indent = false;
+            let start = self.text.len();
+            let indent = self.indent.clone();
+            self.write(&indent)?;
+            self.map(start, span, Vec::new(), false);
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), TemplateError> {
+        if exceeds(
+            self.text.len() + text.len(),
+            self.renderer.limits.max_output_bytes,
+        ) {
+            return Err(TemplateError::OutputLimitExceeded {
+                limit: self.renderer.limits.max_output_bytes.unwrap_or_default(),
+            });
+        }
+        self.text.push_str(text);
+        Ok(())
+    }
+
+    /// Records the text written since `start` for a value read from `paths` in the source map,
+    /// and among the sensitive parts of the output if one of them is sensitive or if the value
+    /// expanded a sensitive one.
+    fn track(&mut self, start: usize, span: Span, paths: &[&str], expanded_secret: bool) {
+        if self.source_map.is_none() && self.renderer.sensitive.is_empty() {
+            return;
+        }
+        let variables: Vec<_> = paths.iter().map(|path| self.resolve_path(path)).collect();
+        let sensitive = expanded_secret
+            || variables
+                .iter()
+                .any(|path| is_sensitive(path, &self.renderer.sensitive));
+        if sensitive && self.text.len() > start {
+            self.sensitive.push(Span::new(start, self.text.len()));
+            self.secrets.push(self.text[start..].to_string());
+        }
+        self.map(start, span, variables, sensitive);
+    }
+
+    /// Records that the text written since `start` comes from `span` of the current template.
+    fn map(&mut self, start: usize, span: Span, variables: Vec<String>, sensitive: bool) {
+        let end = self.text.len();
+        if let Some(entries) = self.source_map.as_mut().filter(|_| end > start) {
+            entries.push(SourceMapEntry {
+                output: Span::new(start, end),
+                template: self.template.clone(),
+                span,
+                variables,
+                sensitive,
+            });
+        }
+    }
+
+    /// Returns the path in the context of a variable or dotted path, replacing a variable bound
+    /// by a loop with the path of its element. Paths into elements of other arrays, such as
+    /// `range(0, 3)`, are returned as written.
+    fn resolve_path(&self, path: &str) -> String {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        let origin = match self.scope_of(name) {
+            Some((
+                Scope {
+                    variable: Some(_),
+                    path: Some(origin),
+                    ..
+                },
+                _,
+            )) => origin.clone(),
+            Some((
+                Scope {
+                    variable: None,
+                    path: Some(origin),
+                    ..
+                },
+                _,
+            )) => format!("{}.{}", origin, name),
+            _ => return path.to_string(),
+        };
+        match rest {
+            Some(rest) => format!("{}.{}", origin, rest),
+            None => origin,
+        }
+    }
+
+    /// Finds the innermost scope defining `name`, as its variable or as a key of the context of
+    /// a section, along with the value of `name`.
+    fn scope_of(&self, name: &str) -> Option<(&Scope, &Value)> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| match &scope.variable {
+                Some(variable) => (variable == name).then_some((scope, &scope.value)),
+                None => scope.value.get(name).map(|value| (scope, value)),
+            })
+    }
+
+    /// Evaluates the condition of a block or the array of a loop, taking missing variables as
+    /// `null`.
+    fn evaluate_lenient(&mut self, expression: &Expression) -> Result<Value, TemplateError> {
+        let mut expression = expression.clone();
+        loop {
+            match self.evaluate(&expression) {
+                Ok(value) => return Ok(value),
+                Err(EvaluationError::Failed(error)) => return Err(error),
+                Err(EvaluationError::Undefined(name)) => {
+                    self.warnings.push(format!("Variable {} not defined", name));
+                    replace_variable(&mut expression, &name);
+                }
+            }
+        }
+    }
+
+    fn evaluate(&self, expression: &Expression) -> Result<Value, EvaluationError> {
+        match expression {
+            Expression::Literal(value, _) => Ok(value.clone()),
+            Expression::Variable(name, _) => self
+                .lookup(name)
+                .ok_or_else(|| EvaluationError::Undefined(name.clone())),
+            Expression::Call {
+                name,
+                arguments,
+                span,
+            } => self.call(name, arguments, *span),
+            Expression::Unary {
+                operator,
+                operand,
+                span,
+            } => {
+                let value = self.evaluate(operand)?;
+                match operator {
+                    UnaryOperator::Not => Ok(Value::Bool(!is_truthy(&value))),
+                    UnaryOperator::Negate => match value.as_i64().and_then(i64::checked_neg) {
+                        Some(negated) => Ok(Value::from(negated)),
+                        None => match value.as_f64() {
+                            Some(number) => Ok(float(-number)),
+                            None => Err(invalid_operands(operator, &[&value], *span).into()),
+                        },
+                    },
+                }
+            }
+            Expression::Binary {
+                operator: BinaryOperator::And,
+                left,
+                right,
+                ..
+            } => Ok(Value::Bool(
+                is_truthy(&self.evaluate(left)?) && is_truthy(&self.evaluate(right)?),
+            )),
+            Expression::Binary {
+                operator: BinaryOperator::Or,
+                left,
+                right,
+                ..
+            } => Ok(Value::Bool(
+                is_truthy(&self.evaluate(left)?) || is_truthy(&self.evaluate(right)?),
+            )),
+            Expression::Binary {
+                operator,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                Ok(apply(*operator, &left, &right, *span)?)
+            }
+        }
+    }
+
+    /// Looks up a variable or a dotted path such as `user.address.city` in the variables bound
+    /// by the enclosing loops and the contexts of the enclosing sections, then in the context,
+    /// and then in the defaults of the front matters, innermost first.
+    fn lookup(&self, path: &str) -> Option<Value> {
+        let (name, rest) = match path.split_once('.') {
+            Some((name, rest)) => (name, Some(rest)),
+            None => (path, None),
+        };
+        if path == "." {
+            // The implicit iterator of Mustache templates: the context of the innermost section.
+            let section = self
+                .scopes
+                .iter()
+                .rev()
+                .find(|scope| scope.variable.is_none());
+            return Some(section.map_or(self.context, |scope| &scope.value).clone());
+        }
+        let value = match self.scope_of(name) {
+            Some((_, value)) => match rest {
+                Some(rest) => lookup_path(value, rest)?,
+                None => value,
+            },
+            None => lookup_path(self.context, path).or_else(|| {
+                self.front_matters.iter().rev().find_map(|front_matter| {
+                    let value = front_matter.defaults.get(name)?;
+                    match rest {
+                        Some(rest) => lookup_path(value, rest),
+                        None => Some(value),
+                    }
+                })
+            })?,
+        };
+        Some(value.clone())
+    }
+
+    fn call(
+        &self,
+        name: &str,
+        arguments: &[Expression],
+        span: Span,
+    ) -> Result<Value, EvaluationError> {
+        let formatting;
+        let function = match self.renderer.functions.get(name) {
+            Some(function) => function.as_ref(),
+            None => {
+                formatting = formatting_function(name, &self.renderer.locale).ok_or_else(|| {
+                    TemplateError::UnknownFunction {
+                        name: name.to_string(),
+                        span,
+                    }
+                })?;
+                formatting.as_ref()
+            }
+        };
+        let params = function.params();
+        if params.len() != arguments.len() {
+            return Err(TemplateError::WrongArgumentCount {
+                function: name.to_string(),
+                expected: params.len(),
+                found: arguments.len(),
+                span,
+            }
+            .into());
+        }
+        let mut values = Vec::with_capacity(arguments.len());
+        for (param, argument) in params.iter().zip(arguments) {
+            let value = self.evaluate(argument)?;
+            if !param.accepts(&value) {
+                return Err(TemplateError::WrongArgumentType {
+                    function: name.to_string(),
+                    expected: *param,
+                    found: type_name(&value).to_string(),
+                    span: argument.span(),
+                }
+                .into());
+            }
+            values.push(value);
+        }
+        function.call(&values).map_err(|message| {
+            TemplateError::FunctionFailed {
+                function: name.to_string(),
+                message,
+                span,
+            }
+            .into()
+        })
+    }
+}
+
+fn supported_plural_rule(locale: &str) -> Result<PluralRule, TemplateError> {
+    plural_rule(locale).ok_or_else(|| TemplateError::UnknownLocale {
+        locale: locale.to_string(),
+    })
+}
+
+/// Looks up a dotted path such as `address.city` or `tags.0` inside a value.
+fn lookup_path<'v>(mut value: &'v Value, path: &str) -> Option<&'v Value> {
+    for key in path.split('.') {
+        value = match value {
+            Value::Object(entries) => entries.get(key)?,
+            Value::Array(elements) => elements.get(key.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Replaces the variable `name` with a `null` literal.
+fn replace_variable(expression: &mut Expression, name: &str) {
+    match expression {
+        Expression::Variable(variable, span) if variable == name => {
+            *expression = Expression::Literal(Value::Null, *span);
+        }
+        Expression::Call { arguments, .. } => {
+            for argument in arguments {
+                replace_variable(argument, name);
+            }
+        }
+        Expression::Unary { operand, .. } => replace_variable(operand, name),
+        Expression::Binary { left, right, .. } => {
+            replace_variable(left, name);
+            replace_variable(right, name);
+        }
+        _ => {}
+    }
+}
+
+/// Whether a value counts as true in a condition: everything except `null`, `false`, zero and
+/// empty strings, arrays and objects.
+pub(crate) fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(boolean) => *boolean,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(elements) => !elements.is_empty(),
+        Value::Object(entries) => !entries.is_empty(),
+    }
+}
+
+fn apply(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+    span: Span,
+) -> Result<Value, TemplateError> {
+    match operator {
+        BinaryOperator::Concat => Ok(Value::String(
+            value_to_string(left) + &value_to_string(right),
+        )),
+        BinaryOperator::Equal => Ok(Value::Bool(equals(left, right))),
+        BinaryOperator::NotEqual => Ok(Value::Bool(!equals(left, right))),
+        BinaryOperator::Less
+        | BinaryOperator::LessOrEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterOrEqual => {
+            let ordering = compare(left, right)
+                .ok_or_else(|| invalid_operands(&operator, &[left, right], span))?;
+            Ok(Value::Bool(match operator {
+                BinaryOperator::Less => ordering == Ordering::Less,
+                BinaryOperator::LessOrEqual => ordering != Ordering::Greater,
+                BinaryOperator::Greater => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        _ => arithmetic(operator, left, right, span),
+    }
+}
+
+fn arithmetic(
+    operator: BinaryOperator,
+    left: &Value,
+    right: &Value,
+    span: Span,
+) -> Result<Value, TemplateError> {
+    let (Value::Number(left_number), Value::Number(right_number)) = (left, right) else {
+        return Err(invalid_operands(&operator, &[left, right], span));
+    };
+    if matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder)
+        && right_number.as_f64() == Some(0.0)
+    {
+        return Err(TemplateError::DivisionByZero { span });
+    }
+    if let (Some(a), Some(b)) = (left_number.as_i64(), right_number.as_i64()) {
+        let result = match operator {
+            BinaryOperator::Add => a.checked_add(b),
+            BinaryOperator::Subtract => a.checked_sub(b),
+            BinaryOperator::Multiply => a.checked_mul(b),
+            BinaryOperator::Divide if a.checked_rem(b) == Some(0) => a.checked_div(b),
+            // Only `i64::MIN % -1` wraps, to its exact result 0.
+            BinaryOperator::Remainder => Some(a.wrapping_rem(b)),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Ok(Value::from(result));
+        }
+    }
+    let a = left_number.as_f64().unwrap_or_default();
+    let b = right_number.as_f64().unwrap_or_default();
+    Ok(float(match operator {
+        BinaryOperator::Add => a + b,
+        BinaryOperator::Subtract => a - b,
+        BinaryOperator::Multiply => a * b,
+        BinaryOperator::Divide => a / b,
+        _ => a % b,
+    }))
+}
+
+fn float(number: f64) -> Value {
+    Number::from_f64(number)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+fn invalid_operands(
+    operator: &dyn std::fmt::Display,
+    operands: &[&Value],
+    span: Span,
+) -> TemplateError {
+    TemplateError::InvalidOperands {
+        operator: operator.to_string(),
+        operands: operands
+            .iter()
+            .map(|value| type_name(value).to_string())
+            .collect(),
+        span,
+    }
+}
+
+/// Converts a value to the text written in the rendered template.
+pub(crate) fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{Renderer, Template, TemplateError};
+
+/// The file at the root of a template directory that declares which files are only created
+/// when a condition holds. It is not copied to the output.
+///
+/// It holds a JSON object whose keys are paths inside the template directory, or directories
+/// ending with `/`, and whose values are conditions written like the ones of `{% if %}` tags:
+///
+/// ```json
+/// { "conditional": { "Dockerfile": "docker", "migrations/": "database and not embedded" } }
+/// ```
+pub const MANIFEST_FILE: &str = "scaffold.json";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScaffoldOptions {
+    /// Overwrite files that already exist in the output directory.
+    pub force: bool,
+    /// Leave binary files out instead of copying them verbatim.
+    pub skip_binary_files: bool,
+}
+
+/// What `scaffold` did with each file of the template directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScaffoldReport {
+    /// Files rendered, relative to the output directory.
+    pub rendered: Vec<PathBuf>,
+    /// Binary files copied verbatim, relative to the output directory.
+    pub copied: Vec<PathBuf>,
+    /// Files left out by the manifest or because they are binary, relative to the template
+    /// directory.
+    pub skipped: Vec<PathBuf>,
+    /// Warnings of the rendered files, prefixed by the path of the file.
+    pub warnings: Vec<String>,
+}
+
+enum Content {
+    Rendered(String),
+    Copied(Vec<u8>),
+}
+
+/// Renders a whole directory of templates into an output directory.
+///
+/// Every file path and every text file body inside `source` is rendered with `context`, and
+/// binary files are copied verbatim unless `options.skip_binary_files` is set. Files listed in
+/// the `MANIFEST_FILE` are only created when their condition holds. All the files are rendered
+/// before any is written, so that an error leaves the output directory untouched.
+///
+/// # Errors
+///
+/// This function returns an error if a path or a file cannot be rendered, wrapped in
+/// `TemplateError::InFile`, if a rendered path leaves a placeholder unreplaced or points
+/// outside the output directory, if the manifest is invalid, if a file already exists in the
+/// output directory and `options.force` is not set, or if reading or writing files fails.
+pub fn scaffold(
+    renderer: &Renderer,
+    source: &Path,
+    destination: &Path,
+    context: &Value,
+    options: ScaffoldOptions,
+) -> Result<ScaffoldReport, TemplateError> {
+    let conditions = read_manifest(source)?;
+    let mut files = Vec::new();
+    collect_files(source, Path::new(""), &mut files)?;
+    let mut report = ScaffoldReport::default();
+    let mut planned = Vec::new();
+    for relative in files {
+        let key = to_key(&relative);
+        if key == MANIFEST_FILE {
+            continue;
+        }
+        if !is_included(renderer, &conditions, &key, context)? {
+            report.skipped.push(relative);
+            continue;
+        }
+        let bytes = fs::read(source.join(&relative)).map_err(TemplateError::IoError)?;
+        let content = match String::from_utf8(bytes) {
+            Ok(text) if !text.contains('\0') => {
+                let parsed_template = Template::parse(&text)
+                    .and_then(|template| renderer.render(&template, context))
+                    .map_err(|error| in_file(&relative, error))?;
+                report.warnings.extend(
+                    parsed_template
+                        .warnings
+                        .into_iter()
+                        .map(|warning| format!("{}: {}", key, warning)),
+                );
+                Content::Rendered(parsed_template.text)
+            }
+            _ if options.skip_binary_files => {
+                report.skipped.push(relative);
+                continue;
+            }
+            Ok(text) => Content::Copied(text.into_bytes()),
+            Err(error) => Content::Copied(error.into_bytes()),
+        };
+        planned.push((render_path(renderer, &relative, context)?, content));
+    }
+    if !options.force {
+        if let Some((path, _)) = planned
+            .iter()
+            .find(|(path, _)| destination.join(path).exists())
+        {
+            return Err(TemplateError::OutputExists {
+                path: destination.join(path),
+            });
+        }
+    }
+    for (path, content) in planned {
+        let file = destination.join(&path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent).map_err(TemplateError::IoError)?;
+        }
+        match content {
+            Content::Rendered(text) => {
+                fs::write(&file, text).map_err(TemplateError::IoError)?;
+                report.rendered.push(path);
+            }
+            Content::Copied(bytes) => {
+                fs::write(&file, bytes).map_err(TemplateError::IoError)?;
+                report.copied.push(path);
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn read_manifest(source: &Path) -> Result<HashMap<String, String>, TemplateError> {
+    #[derive(serde::Deserialize)]
+    struct Manifest {
+        #[serde(default)]
+        conditional: HashMap<String, String>,
+    }
+    let path = source.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let text = fs::read_to_string(&path).map_err(TemplateError::IoError)?;
+    let manifest: Manifest = serde_json::from_str(&text)
+        .map_err(|error| in_file(Path::new(MANIFEST_FILE), TemplateError::JsonError(error)))?;
+    Ok(manifest.conditional)
+}
+
+/// Collects the files inside `directory`, sorted, as paths relative to the template directory.
+fn collect_files(
+    root: &Path,
+    directory: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), TemplateError> {
+    let mut entries = fs::read_dir(root.join(directory))
+        .map_err(TemplateError::IoError)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(TemplateError::IoError)?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let relative = directory.join(entry.file_name());
+        if entry.file_type().map_err(TemplateError::IoError)?.is_dir() {
+            collect_files(root, &relative, files)?;
+        } else {
+            files.push(relative);
+        }
+    }
+    Ok(())
+}
+
+/// Whether every condition of the manifest that applies to the file holds.
+fn is_included(
+    renderer: &Renderer,
+    conditions: &HashMap<String, String>,
+    key: &str,
+    context: &Value,
+) -> Result<bool, TemplateError> {
+    for (path, condition) in conditions {
+        let applies = match path.strip_suffix('/') {
+            Some(directory) => key.starts_with(&format!("{}/", directory)),
+            None => key == path,
+        };
+        if applies
+            && !renderer
+                .evaluate_condition(condition, context)
+                .map_err(|error| in_file(Path::new(MANIFEST_FILE), error))?
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn render_path(
+    renderer: &Renderer,
+    relative: &Path,
+    context: &Value,
+) -> Result<PathBuf, TemplateError> {
+    let key = to_key(relative);
+    let parsed_path = Template::parse(&key)
+        .and_then(|template| renderer.render(&template, context))
+        .map_err(|error| in_file(relative, error))?;
+    let rendered = PathBuf::from(&parsed_path.text);
+    let escapes = rendered.is_absolute()
+        || rendered
+            .components()
+            .any(|component| component == Component::ParentDir);
+    if !parsed_path.warnings.is_empty() || escapes || parsed_path.text.is_empty() {
+        return Err(TemplateError::InvalidOutputPath {
+            path: parsed_path.text,
+        });
+    }
+    Ok(rendered)
+}
+
+/// The path of a file inside the template directory, with `/` separators on every platform.
+fn to_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn in_file(path: &Path, error: TemplateError) -> TemplateError {
+    TemplateError::InFile {
+        path: path.to_path_buf(),
+        error: Box::new(error),
+    }
+}
@@ -0,0 +1,369 @@
+//! A local HTTP service rendering templates for programs that cannot link the engine.
+//!
+//! It answers two requests, with JSON bodies:
+//!
+//! - `GET /templates` lists the registered templates with their description, variables and
+//!   metadata, as `{"templates": [{"name": "invoice", ...}]}`.
+//! - `POST /render` renders a registered template, `{"template": "invoice", "context": {...}}`,
+//!   or an inline one, `{"source": "Hello, ${name}", "context": {...}}`, and answers
+//!   `{"text": "...", "warnings": [{"message": "..."}]}`. Inline sources are refused with
+//!   `403 Forbidden` unless `ServerOptions::inline_sources` is set, since any client can send
+//!   them; they then render within the `Limits` of the renderer, like registered templates.
+//!
+//! Failures are answered with an error status and `{"error": "..."}`, along with the `span` of
+//! the template text for syntax errors. Each connection is served on its own thread, up to
+//! `MAX_CONNECTIONS` at once, must send its request within `READ_TIMEOUT` and is closed after
+//! its response.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::{Renderer, Template, TemplateError};
+
+/// The largest request body the server reads, in bytes.
+pub const MAX_BODY_LENGTH: usize = 1 << 20;
+
+/// The largest request line and headers the server reads together, in bytes.
+pub const MAX_HEAD_LENGTH: u64 = 8 << 10;
+
+/// The most connections served at once. Further ones are answered `503 Service Unavailable`.
+pub const MAX_CONNECTIONS: usize = 64;
+
+/// The stack of the thread serving each connection, larger than the default one so that the
+/// parser and the renderer have room within `MAX_NESTING_DEPTH` and the include limits.
+const WORKER_STACK_SIZE: usize = 8 << 20;
+
+/// How long `serve` waits before accepting connections again after failing to accept one.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long a client has to send its whole request, and the server to write its response.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerOptions {
+    /// Render the inline sources of `POST /render` requests, instead of only the registered
+    /// templates.
+    pub inline_sources: bool,
+}
+
+/// Registers every file of `directory` on the renderer under its name without extension,
+/// parsing the ones with the `.mustache` extension as Mustache.
+///
+/// # Errors
+///
+/// This function returns an error if the directory or a file cannot be read, if a template
+/// cannot be parsed, wrapped in `TemplateError::InFile`, or if two files have the same name
+/// without extension.
+pub fn load_templates(renderer: &mut Renderer, directory: &Path) -> Result<(), TemplateError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory).map_err(TemplateError::IoError)? {
+        let path = entry.map_err(TemplateError::IoError)?.path();
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    let mut loaded: HashMap<String, PathBuf> = HashMap::new();
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        if let Some(first) = loaded.get(name.as_ref()) {
+            return Err(TemplateError::DuplicateTemplate {
+                name: name.into_owned(),
+                paths: (first.clone(), path),
+            });
+        }
+        let text = fs::read_to_string(&path).map_err(TemplateError::IoError)?;
+        let added = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("mustache") => renderer.add_mustache_template(&name, &text),
+            _ => renderer.add_template(&name, &text),
+        };
+        added.map_err(|error| TemplateError::InFile {
+            path: path.clone(),
+            error: Box::new(error),
+        })?;
+        loaded.insert(name.into_owned(), path);
+    }
+    Ok(())
+}
+
+/// Serves the connections of `listener` with the templates of `renderer`, each on its own
+/// thread, forever. Connections that cannot be accepted, such as when the process runs out of
+/// file descriptors, are logged to the standard error and skipped, and a connection that fails
+/// only loses its own response.
+pub fn serve(listener: &TcpListener, renderer: &Renderer, options: &ServerOptions) -> ! {
+    let active = AtomicUsize::new(0);
+    let active = &active;
+    thread::scope(|scope| loop {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(error) => {
+                eprintln!("error: Cannot accept a connection: {}", error);
+                thread::sleep(ACCEPT_RETRY_DELAY);
+                continue;
+            }
+        };
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            refuse(&mut stream, 503, "Too many connections");
+            continue;
+        }
+        let worker = thread::Builder::new()
+            .stack_size(WORKER_STACK_SIZE)
+            .spawn_scoped(scope, {
+                let mut stream = stream.try_clone();
+                move || {
+                    if let Ok(stream) = &mut stream {
+                        let _ = handle_connection(renderer, options, stream);
+                    }
+                    active.fetch_sub(1, Ordering::SeqCst);
+                }
+            });
+        if let Err(error) = worker {
+            active.fetch_sub(1, Ordering::SeqCst);
+            eprintln!("error: Cannot start a thread: {}", error);
+            refuse(&mut stream, 503, "Cannot serve the connection");
+        }
+    })
+}
+
+/// Answers a connection that is not served with an error, without reading its request.
+fn refuse(stream: &mut TcpStream, status: u16, message: &str) {
+    let _ = stream.set_write_timeout(Some(READ_TIMEOUT));
+    let _ = write_response(stream, &Response::error(status, message));
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    fn error(status: u16, message: &str) -> Response {
+        Response {
+            status,
+            body: json!({"error": message}),
+        }
+    }
+}
+
+fn handle_connection(
+    renderer: &Renderer,
+    options: &ServerOptions,
+    stream: &mut TcpStream,
+) -> io::Result<()> {
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + READ_TIMEOUT,
+    });
+    let response = match read_request(&mut reader, stream)? {
+        Ok(request) => respond(renderer, options, &request),
+        Err(response) => response,
+    };
+    write_response(stream, &response)?;
+    // Closing a connection with unread bytes resets it, which can discard the response before
+    // the client reads it, so the rest of a refused request is read first.
+    stream.shutdown(Shutdown::Write)?;
+    io::copy(&mut reader.take(MAX_BODY_LENGTH as u64), &mut io::sink())?;
+    Ok(())
+}
+
+/// Reads a stream until a deadline, however slowly its bytes arrive.
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Reads a request, or returns the response to a request that cannot be served.
+fn read_request(
+    reader: &mut dyn BufRead,
+    writer: &mut dyn Write,
+) -> io::Result<Result<Request, Response>> {
+    let too_large = || Ok(Err(Response::error(431, "Request header fields too large")));
+    let mut head = (&mut *reader).take(MAX_HEAD_LENGTH);
+    let mut line = String::new();
+    if !read_head_line(&mut head, &mut line)? {
+        return too_large();
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(_version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(Err(Response::error(400, "Malformed request line")));
+    };
+    let method = method.to_string();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+    let mut content_length = None;
+    let mut expects_continue = false;
+    loop {
+        if !read_head_line(&mut head, &mut line)? {
+            return too_large();
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            break;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => match value.parse::<usize>() {
+                Ok(length) => content_length = Some(length),
+                Err(_) => return Ok(Err(Response::error(400, "Invalid Content-Length"))),
+            },
+            "expect" => expects_continue = value.eq_ignore_ascii_case("100-continue"),
+            _ => {}
+        }
+    }
+    let length = match (content_length, method.as_str()) {
+        (Some(length), _) if length > MAX_BODY_LENGTH => {
+            return Ok(Err(Response::error(413, "Request body too large")));
+        }
+        (Some(length), _) => length,
+        (None, "POST") => return Ok(Err(Response::error(411, "Missing Content-Length"))),
+        (None, _) => 0,
+    };
+    if expects_continue && length > 0 {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok(Request { method, path, body }))
+}
+
+/// Reads a line of the request line and headers into `line`, and returns whether it fit within
+/// `MAX_HEAD_LENGTH`.
+fn read_head_line(head: &mut io::Take<impl BufRead>, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    head.read_line(line)?;
+    match (line.ends_with('\n'), head.limit()) {
+        (true, _) => Ok(true),
+        (false, 0) => Ok(false),
+        (false, _) => Err(io::ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+fn respond(renderer: &Renderer, options: &ServerOptions, request: &Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/templates") => Response {
+            status: 200,
+            body: list_templates(renderer),
+        },
+        ("POST", "/render") => render(renderer, options, &request.body),
+        (method, "/templates" | "/render") => {
+            Response::error(405, &format!("Method {} not allowed", method))
+        }
+        (_, path) => Response::error(404, &format!("Unknown path {}", path)),
+    }
+}
+
+fn list_templates(renderer: &Renderer) -> Value {
+    let templates: Vec<Value> = renderer
+        .templates()
+        .into_iter()
+        .map(|(name, template)| {
+            let front_matter = template.front_matter();
+            json!({
+                "name": name,
+                "description": front_matter.description,
+                "variables": template.variables(),
+                "metadata": front_matter.metadata,
+            })
+        })
+        .collect();
+    json!({ "templates": templates })
+}
+
+fn render(renderer: &Renderer, options: &ServerOptions, body: &[u8]) -> Response {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(error) => return Response::error(400, &format!("JSON error: {}", error)),
+    };
+    let context = match request.get("context") {
+        Some(context) => context.clone(),
+        None => Value::Object(Default::default()),
+    };
+    let rendered = match (request["template"].as_str(), request["source"].as_str()) {
+        (Some(name), None) => renderer.render_template(name, &context),
+        (None, Some(_)) if !options.inline_sources => {
+            return Response::error(403, "Inline sources are disabled");
+        }
+        (None, Some(source)) => {
+            Template::parse(source).and_then(|template| renderer.render(&template, &context))
+        }
+        _ => return Response::error(400, "Expected either a template name or a source"),
+    };
+    match rendered {
+        Ok(parsed_template) => {
+            let warnings: Vec<Value> = parsed_template
+                .warnings
+                .iter()
+                .map(|warning| json!({ "message": warning }))
+                .collect();
+            Response {
+                status: 200,
+                body: json!({"text": parsed_template.text, "warnings": warnings}),
+            }
+        }
+        Err(error @ TemplateError::UnknownTemplate { .. }) => {
+            Response::error(404, &error.to_string())
+        }
+        Err(error) => {
+            let mut response = Response::error(422, &error.to_string());
+            if let TemplateError::Syntax { span, .. } = error {
+                response.body["span"] = json!({"start": span.start, "end": span.end});
+            }
+            response
+        }
+    }
+}
+
+fn write_response(writer: &mut dyn Write, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Content Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Unprocessable Content",
+    }
+}
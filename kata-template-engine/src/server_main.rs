@@ -0,0 +1,52 @@
+use std::net::TcpListener;
+use std::path::Path;
+use std::process;
+
+use kata_template_engine::server::{load_templates, serve, ServerOptions};
+use kata_template_engine::{Limits, Renderer};
+
+const USAGE: &str = "Usage: template-server <template directory> [<address>] [--inline-sources]";
+
+/// The address the server listens on when none is given, only reachable from this machine.
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+
+/// The limits of every render, so that a request cannot keep the server busy or exhaust its
+/// memory.
+const SERVER_LIMITS: Limits = Limits {
+    max_output_bytes: Some(16 << 20),
+    max_loop_iterations: Some(100_000),
+    max_include_depth: Some(16),
+    max_steps: Some(1_000_000),
+    max_expansion_depth: Some(16),
+};
+
+fn main() -> ! {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let options = ServerOptions {
+        inline_sources: args.iter().any(|arg| arg == "--inline-sources"),
+    };
+    args.retain(|arg| arg != "--inline-sources");
+    let (directory, address) = match args.as_slice() {
+        [directory] => (directory, DEFAULT_ADDRESS),
+        [directory, address] => (directory, address.as_str()),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let mut renderer = Renderer::new();
+    renderer.set_limits(SERVER_LIMITS);
+    if let Err(error) = load_templates(&mut renderer, Path::new(directory)) {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+    let listener = match TcpListener::bind(address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            eprintln!("error: Cannot listen on {}: {}", address, error);
+            process::exit(1);
+        }
+    };
+    eprintln!("Listening on http://{}", address);
+    serve(&listener, &renderer, &options)
+}
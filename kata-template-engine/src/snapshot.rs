@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::{Renderer, TemplateError};
+
+/// The extension of the files holding the expected output of a template for a fixture.
+pub const EXPECTED_EXTENSION: &str = "expected";
+
+/// The lines of unchanged text shown around each change of a diff.
+const CONTEXT_LINES: usize = 3;
+
+/// The most pairs of lines aligned along their longest common subsequence, which takes memory
+/// for each pair. Longer changes are shown as the removal of the old lines and the insertion
+/// of the new ones.
+const MAX_DIFF_CELLS: usize = 1 << 22;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotOptions {
+    /// Rewrite the snapshots that are missing or differ from the output instead of failing.
+    pub update: bool,
+}
+
+/// A snapshot that does not match the output of its template, or whose template failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotFailure {
+    /// The snapshot, relative to the tested directory.
+    pub snapshot: PathBuf,
+    /// The unified diff from the snapshot to the output, or the error of the template.
+    pub message: String,
+}
+
+/// The outcome of `test_snapshots` for each fixture of the tested directory.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SnapshotReport {
+    /// Snapshots matching the output, relative to the tested directory.
+    pub passed: Vec<PathBuf>,
+    /// Snapshots written or rewritten by `SnapshotOptions::update`, relative to the tested
+    /// directory.
+    pub updated: Vec<PathBuf>,
+    pub failures: Vec<SnapshotFailure>,
+    /// Warnings of the rendered templates, prefixed by the path of the fixture.
+    pub warnings: Vec<String>,
+}
+
+/// A template of the tested directory rendered with the context of `fixture`, whose output is
+/// expected to be the text of `snapshot`.
+struct Case {
+    template: String,
+    fixture: PathBuf,
+    snapshot: PathBuf,
+}
+
+/// Renders every template of `directory` with each of its fixtures and compares the outputs
+/// to the snapshots stored next to them, so that changes to templates are reviewed as diffs.
+///
+/// The context of the template `invoice.txt` is read from the JSON fixture `invoice.json`, or
+/// from several ones such as `invoice.overdue.json`, and its output is compared to
+/// `invoice.expected` or `invoice.overdue.expected`. A JSON file is a fixture when its name
+/// without `.json` is the name without extension of another file, or starts with it followed
+/// by a `.`, and a template otherwise, so that `config.json` is a template whose fixtures are
+/// named like `config.default.json`. Every template is registered on the renderer under its
+/// name without extension, so that templates can include each other, and templates without
+/// fixtures are only included. Templates with the `.mustache` extension are parsed as Mustache.
+///
+/// # Errors
+///
+/// This function returns an error if the directory cannot be read, if a template cannot be
+/// parsed, wrapped in `TemplateError::InFile`, if two templates have the same name without
+/// extension, if a fixture is named after two templates, or if a snapshot cannot be written. Fixtures that cannot be read and templates
+/// that fail to render are reported as failures.
+pub fn test_snapshots(
+    renderer: &mut Renderer,
+    directory: &Path,
+    options: SnapshotOptions,
+) -> Result<SnapshotReport, TemplateError> {
+    let mut names = fs::read_dir(directory)
+        .map_err(TemplateError::IoError)?
+        .map(|entry| {
+            let entry = entry.map_err(TemplateError::IoError)?;
+            let is_file = entry.file_type().map_err(TemplateError::IoError)?.is_file();
+            Ok(is_file.then(|| entry.file_name().to_string_lossy().into_owned()))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>, TemplateError>>()?;
+    names.sort();
+    let names: Vec<&String> = names
+        .iter()
+        .filter(|name| !name.ends_with(&format!(".{}", EXPECTED_EXTENSION)))
+        .collect();
+    let (fixtures, templates): (Vec<&String>, Vec<&String>) = names.iter().partition(|name| {
+        name.strip_suffix(".json").is_some_and(|case| {
+            names
+                .iter()
+                .any(|other| other != *name && is_case_of(case, &file_stem(other)))
+        })
+    });
+    let mut cases = Vec::new();
+    // The template each fixture was matched with, to reject fixtures named after two.
+    let mut matched: HashMap<&String, String> = HashMap::new();
+    let mut stems: HashMap<String, &String> = HashMap::new();
+    for name in templates {
+        let path = Path::new(name);
+        let stem = file_stem(name);
+        if let Some(first) = stems.insert(stem.clone(), name) {
+            return Err(TemplateError::DuplicateTemplate {
+                name: stem,
+                paths: (PathBuf::from(first), PathBuf::from(name)),
+            });
+        }
+        let text = fs::read_to_string(directory.join(name)).map_err(TemplateError::IoError)?;
+        let added = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("mustache") => renderer.add_mustache_template(&stem, &text),
+            _ => renderer.add_template(&stem, &text),
+        };
+        added.map_err(|error| in_file(path, error))?;
+        for fixture in &fixtures {
+            let Some(case) = fixture
+                .strip_suffix(".json")
+                .filter(|case| is_case_of(case, &stem))
+            else {
+                continue;
+            };
+            if let Some(first) = matched.insert(fixture, stem.clone()) {
+                return Err(TemplateError::AmbiguousFixture {
+                    fixture: PathBuf::from(fixture),
+                    templates: (first, stem),
+                });
+            }
+            cases.push(Case {
+                template: stem.clone(),
+                fixture: PathBuf::from(fixture),
+                snapshot: PathBuf::from(format!("{}.{}", case, EXPECTED_EXTENSION)),
+            });
+        }
+    }
+    let mut report = SnapshotReport::default();
+    for case in cases {
+        let output = read_fixture(&directory.join(&case.fixture)).and_then(|context| {
+            renderer
+                .render_template(&case.template, &context)
+                .map_err(|error| in_file(&case.fixture, error))
+        });
+        let parsed_template = match output {
+            Ok(parsed_template) => parsed_template,
+            Err(error) => {
+                report.failures.push(SnapshotFailure {
+                    snapshot: case.snapshot,
+                    message: error.to_string(),
+                });
+                continue;
+            }
+        };
+        report.warnings.extend(
+            parsed_template
+                .warnings
+                .iter()
+                .map(|warning| format!("{}: {}", case.fixture.display(), warning)),
+        );
+        let snapshot = directory.join(&case.snapshot);
+        let expected = fs::read_to_string(&snapshot).ok();
+        if expected.as_deref() == Some(parsed_template.text.as_str()) {
+            report.passed.push(case.snapshot);
+        } else if options.update {
+            fs::write(&snapshot, &parsed_template.text).map_err(TemplateError::IoError)?;
+            report.updated.push(case.snapshot);
+        } else {
+            let label = case.snapshot.display().to_string();
+            let message = match expected {
+                Some(expected) => unified_diff(&expected, &parsed_template.text, &label),
+                None => unified_diff("", &parsed_template.text, "/dev/null"),
+            };
+            report.failures.push(SnapshotFailure {
+                snapshot: case.snapshot,
+                message,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Returns the name of a file without its extension.
+fn file_stem(name: &str) -> String {
+    let stem = Path::new(name).file_stem().unwrap_or_default();
+    stem.to_string_lossy().into_owned()
+}
+
+/// Whether a fixture named `case` with `.json` is a fixture of the template named `stem`.
+fn is_case_of(case: &str, stem: &str) -> bool {
+    case.strip_prefix(stem)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+fn read_fixture(path: &Path) -> Result<Value, TemplateError> {
+    let text = fs::read_to_string(path).map_err(TemplateError::IoError)?;
+    serde_json::from_str(&text).map_err(TemplateError::JsonError)
+}
+
+/// A line of a diff: unchanged, only in the expected text, or only in the actual one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Returns the unified diff from `expected`, labelled `label`, to the `actual` output.
+pub(crate) fn unified_diff(expected: &str, actual: &str, label: &str) -> String {
+    let old: Vec<&str> = expected.split_inclusive('\n').collect();
+    let new: Vec<&str> = actual.split_inclusive('\n').collect();
+    let edits = diff_lines(&old, &new);
+    let mut diff = format!("--- {}\n+++ output\n", label);
+    // The position in both texts before each edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        positions.push((old_line, new_line));
+        match edit {
+            Edit::Equal => (old_line, new_line) = (old_line + 1, new_line + 1),
+            Edit::Delete => old_line += 1,
+            Edit::Insert => new_line += 1,
+        }
+    }
+    positions.push((old_line, new_line));
+    let mut index = 0;
+    while let Some(change) = edits[index..].iter().position(|edit| *edit != Edit::Equal) {
+        let start = (index + change).saturating_sub(CONTEXT_LINES);
+        let mut end = index + change;
+        // Extends the hunk over the changes closer to each other than twice the context.
+        while let Some(next) = edits[end..]
+            .iter()
+            .position(|edit| *edit != Edit::Equal)
+            .filter(|next| *next <= 2 * CONTEXT_LINES)
+        {
+            end += next + 1;
+        }
+        end = (end + CONTEXT_LINES).min(edits.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+        for (edit, (old_line, new_line)) in edits[start..end].iter().zip(&positions[start..end]) {
+            let (sign, line) = match edit {
+                Edit::Equal => (' ', old[*old_line]),
+                Edit::Delete => ('-', old[*old_line]),
+                Edit::Insert => ('+', new[*new_line]),
+            };
+            diff.push(sign);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        index = end;
+    }
+    diff
+}
+
+/// Formats the start and the length of the lines of a hunk, counting lines from 1, or from the
+/// line before an empty range.
+fn hunk_range(start: usize, length: usize) -> String {
+    match length {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, length),
+    }
+}
+
+/// Aligns the lines of two texts along their longest common subsequence, after their common
+/// first and last lines.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let mut edits = vec![Edit::Equal; prefix];
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    match old_middle.len().saturating_mul(new_middle.len()) <= MAX_DIFF_CELLS {
+        true => edits.extend(align_lines(old_middle, new_middle)),
+        false => {
+            edits.extend(vec![Edit::Delete; old_middle.len()]);
+            edits.extend(vec![Edit::Insert; new_middle.len()]);
+        }
+    }
+    edits.extend(vec![Edit::Equal; suffix]);
+    edits
+}
+
+/// Aligns the lines of two texts along their longest common subsequence, with memory for each
+/// pair of lines.
+fn align_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // common[i][j] is the length of the longest common subsequence of old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = match old[i] == new[j] {
+                true => common[i + 1][j + 1] + 1,
+                false => common[i + 1][j].max(common[i][j + 1]),
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut edits = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push(Edit::Equal);
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            edits.push(Edit::Delete);
+            i += 1;
+        } else {
+            edits.push(Edit::Insert);
+            j += 1;
+        }
+    }
+    edits
+}
+
+fn in_file(path: &Path, error: TemplateError) -> TemplateError {
+    TemplateError::InFile {
+        path: path.to_path_buf(),
+        error: Box::new(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_two_texts_it_shows_each_change_with_three_lines_of_context() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let actual = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        assert_eq!(
+            unified_diff(expected, actual, "page.expected"),
+            "--- page.expected\n+++ output\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -10,4 +10,3 @@\n j\n k\n l\n-m\n"
+        );
+    }
+
+    #[test]
+    fn given_long_texts_with_few_common_lines_it_diffs_them_in_bounded_memory() {
+        let expected: String = (0..5000).map(|line| format!("old {}\n", line)).collect();
+        let actual: String = (0..5000).map(|line| format!("new {}\n", line)).collect();
+        let edits = diff_lines(
+            &expected.split_inclusive('\n').collect::<Vec<_>>(),
+            &actual.split_inclusive('\n').collect::<Vec<_>>(),
+        );
+        assert_eq!(edits.len(), 10_000);
+        assert!(edits[..5000].iter().all(|edit| *edit == Edit::Delete));
+        let diff = unified_diff(
+            &format!("head\n{}tail\n", expected),
+            &format!("head\n{}tail\n", actual),
+            "big.expected",
+        );
+        assert!(diff
+            .starts_with("--- big.expected\n+++ output\n@@ -1,5002 +1,5002 @@\n head\n-old 0\n"));
+        assert!(diff.ends_with("+new 4999\n tail\n"));
+    }
+
+    #[test]
+    fn given_a_missing_final_line_break_it_says_so() {
+        assert_eq!(
+            unified_diff("", "hi", "/dev/null"),
+            "--- /dev/null\n+++ output\n@@ -0,0 +1 @@\n+hi\n\\ No newline at end of file\n"
+        );
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Span;
+
+/// Links the byte ranges of a rendered text to the parts of the templates that produced them.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// The entries in the order of the rendered text, without gaps between them.
+    pub entries: Vec<SourceMapEntry>,
+}
+
+/// A byte range of a rendered text and the literal text or placeholder that produced it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMapEntry {
+    /// The byte range of the rendered text.
+    pub output: Span,
+    /// The name the template was registered under, or `None` for the template passed to
+    /// `Renderer::render_with_source_map`.
+    pub template: Option<String>,
+    /// The byte range of the literal text or the placeholder in that template.
+    pub span: Span,
+    /// The dotted paths into the context read by the placeholder, such as `items.2.title` for
+    /// `${item.title}` in the third iteration of `{% for item in items %}`. Empty for literal
+    /// text.
+    pub variables: Vec<String>,
+    /// Whether the placeholder reads a variable marked as sensitive, so that tools showing the
+    /// rendered text mask the range.
+    #[serde(default)]
+    pub sensitive: bool,
+}
+
+impl SourceMap {
+    /// Returns the entry whose output range contains the byte at `offset` of the rendered text.
+    pub fn find(&self, offset: usize) -> Option<&SourceMapEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.output.start <= offset && offset < entry.output.end)
+    }
+
+    /// Serializes the source map to JSON for debugging tools.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a source map is always valid JSON")
+    }
+}
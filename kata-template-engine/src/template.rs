@@ -0,0 +1,1007 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::escape::Escape;
+use crate::expression::{parse_expression, BinaryOperator, Expression};
+use crate::format_spec::FormatSpec;
+use crate::front_matter::{split_front_matter, FrontMatter};
+use crate::limits::MAX_NESTING_DEPTH;
+use crate::locale::PluralCategory;
+use crate::mustache::parse_mustache;
+use crate::{Span, TemplateError};
+
+/// A template text split into literal text, placeholders and blocks, ready to be rendered many
+/// times.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Template {
+    front_matter: FrontMatter,
+    nodes: Vec<Node>,
+    /// The line ending of the first line of the text, `None` if it has a single line.
+    line_ending: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Node {
+    Text(String, Span),
+    Placeholder {
+        expression: Expression,
+        /// The format specification after a `:`, as in `${price:.2}`.
+        spec: Option<FormatSpec>,
+        /// The escape of a placeholder ending with an `escape` filter, as in
+        /// `${path | escape("shell")}`, replacing the escape of the front matter.
+        escape: Option<Escape>,
+        /// Whether the placeholder ends with an `indent` filter, as in `${body | indent}`,
+        /// indenting the lines of its value after the first to the column of the placeholder.
+        indent: bool,
+        source: String,
+        span: Span,
+    },
+    If {
+        branches: Vec<Branch>,
+        otherwise: Option<Vec<Node>>,
+        span: Span,
+    },
+    For {
+        variable: String,
+        iterable: Expression,
+        nodes: Vec<Node>,
+        span: Span,
+    },
+    Include {
+        name: String,
+        span: Span,
+    },
+    /// An ICU `{variable, plural, ...}` or `{variable, select, ...}` message.
+    Choice {
+        variable: Expression,
+        kind: ChoiceKind,
+        cases: Vec<Case>,
+        span: Span,
+    },
+    /// A `{% message "key" %}` tag, rendering a message of the catalog of the locale.
+    Message {
+        key: String,
+        span: Span,
+    },
+    /// A Mustache `{{path}}` tag, or `{{{path}}}` and `{{& path}}` when the value is written
+    /// without HTML escaping. A missing value is written as an empty text.
+    Variable {
+        path: String,
+        raw: bool,
+        span: Span,
+    },
+    /// A Mustache `{{#path}}...{{/path}}` section, rendered once per element of an array or
+    /// once with any other value but `false` and `null` as the innermost context, or a
+    /// `{{^path}}...{{/path}}` section, rendered only when the other would not be.
+    Section {
+        path: String,
+        inverted: bool,
+        nodes: Vec<Node>,
+        span: Span,
+    },
+    /// A Mustache `{{> name}}` partial, with the indentation of its line when it stands alone on
+    /// it. A missing partial is rendered as an empty text.
+    Partial {
+        name: String,
+        indent: Option<String>,
+        span: Span,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ChoiceKind {
+    /// Chooses by the plural category of the number minus `offset`.
+    Plural { offset: i64 },
+    /// Chooses by the text of the value.
+    Select,
+}
+
+/// A case of an ICU message with the nodes rendered when it is chosen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Case {
+    pub(crate) selector: Selector,
+    pub(crate) nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Selector {
+    /// `=5`, chosen when the number is exactly 5, before looking at categories.
+    Exact(f64),
+    Category(PluralCategory),
+    /// A key of a `select` message.
+    Key(String),
+}
+
+/// A condition of an `{% if %}` or `{% elif %}` tag with the nodes rendered when it holds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Branch {
+    pub(crate) condition: Expression,
+    pub(crate) nodes: Vec<Node>,
+    /// From the start of its tag to the start of the next `elif`, `else` or `endif` tag.
+    pub(crate) span: Span,
+}
+
+/// A `{% keyword arguments %}` tag.
+struct Tag<'a> {
+    keyword: &'a str,
+    arguments: &'a str,
+    arguments_offset: usize,
+    span: Span,
+}
+
+impl Template {
+    /// Parses a template text.
+    ///
+    /// Placeholders are written as `${expression}`, blocks rendered only when a condition holds
+    /// as `{% if expression %}...{% elif expression %}...{% else %}...{% endif %}`, blocks
+    /// rendered once per element of an array as `{% for item in expression %}...{% endfor %}`,
+    /// and other templates of the renderer are inserted with `{% include "name" %}`.
+    /// Localized texts are written as ICU messages, such as
+    /// `{count, plural, =0 {no messages} one {# message} other {# messages}}` or
+    /// `{gender, select, female {her} male {his} other {their}}`, where `#` is the number of a
+    /// plural, and messages of the catalog of the locale are inserted with
+    /// `{% message "key" %}`.
+    /// Expressions are variables, dotted paths into variables, literals such as `"text"` or `42`,
+    /// function calls such as `now("%Y-%m-%d")`, and their combinations with arithmetic (`+`,
+    /// `-`, `*`, `/`, `%`), concatenation (`~`), comparison (`==`, `!=`, `<`, `<=`, `>`, `>=`)
+    /// and boolean (`and`, `or`, `not`) operators. Filters such as `total | currency("EUR")`
+    /// call a function with the value on their left as its first argument. A placeholder can end
+    /// with a format specification following the grammar of Rust's `format!`, such as
+    /// `${price:.2}`, `${id:>8}` or `${code:08}`, giving the fill, alignment, sign, width and
+    /// precision of the value, and with an `escape` filter, such as `${path | escape("shell")}`,
+    /// choosing how its value is escaped instead of the `escape` of the front matter. An
+    /// `indent` filter before it, as in `${body | indent}`, indents the lines of the value after
+    /// the first to the column of the placeholder, with the line endings of the template.
+    ///
+    /// The text can start with a YAML `FrontMatter` between two `---` lines, which is not part
+    /// of the output. A text starting with a `\---` line instead has no front matter, and its
+    /// output starts with a `---` line. Spans still count from the start of the whole text.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a `TemplateError::Syntax` if a placeholder, a tag or a block is not
+    /// closed, a tag is unknown or misplaced, or an expression or a format specification cannot
+    /// be parsed, and a `TemplateError::InvalidFrontMatter` if the front matter is not closed or
+    /// not valid. When the text has several errors, they are all returned at once in a
+    /// `TemplateError::Multiple`.
+    pub fn parse(template_text: &str) -> Result<Template, TemplateError> {
+        match Template::parse_with_recovery(template_text) {
+            (template, None) => Ok(template),
+            (_, Some(error)) => Err(error),
+        }
+    }
+
+    /// Parses a template text like `parse`, but goes on after each syntax error, so that tools
+    /// get every error of the text at once along with the template parsed from the rest of it.
+    ///
+    /// Invalid placeholders, messages and tags are left out of the template, a block whose tag
+    /// is invalid keeps its content, an unclosed block runs to the end of the text, and a front
+    /// matter that cannot be read leaves the template empty. The error is the single error of
+    /// the text, or a `TemplateError::Multiple` holding all of them in the order of the text.
+    pub fn parse_with_recovery(template_text: &str) -> (Template, Option<TemplateError>) {
+        let (front_matter, body_start, mut errors) = match split_front_matter(template_text) {
+            Ok((front_matter, body_start)) => (front_matter, body_start, Vec::new()),
+            Err(error) => (FrontMatter::default(), template_text.len(), vec![error]),
+        };
+        let mut parser = TemplateParser {
+            text: template_text,
+            position: body_start,
+            errors: Vec::new(),
+            depth: 0,
+            truncated: false,
+        };
+        let (nodes, _) = parser.parse_nodes(&[]);
+        errors.extend(parser.errors);
+        errors.sort_by_key(|error| match error {
+            TemplateError::Syntax { span, .. } => span.start,
+            _ => 0,
+        });
+        let template = Template {
+            front_matter,
+            nodes,
+            line_ending: line_ending(template_text),
+        };
+        let error = match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(TemplateError::Multiple { errors }),
+        };
+        (template, error)
+    }
+
+    /// Parses a Mustache template, with `{{variable}}` tags escaping HTML, `{{{raw}}}` and
+    /// `{{& raw}}` tags, `{{#section}}`, `{{^inverted}}` and `{{/section}}` tags, `{{! comments}}`,
+    /// `{{> partial}}` tags including templates of the renderer, and `{{=<% %>=}}` tags changing
+    /// the delimiters.
+    ///
+    /// # Errors
+    ///
+    /// This function returns a `TemplateError::Syntax` if a tag or a section is not closed, a
+    /// section is closed by a tag of another name, or a tag name or delimiters are not valid.
+    pub fn parse_mustache(template_text: &str) -> Result<Template, TemplateError> {
+        Ok(Template {
+            front_matter: FrontMatter::default(),
+            nodes: parse_mustache(template_text)?,
+            line_ending: line_ending(template_text),
+        })
+    }
+
+    /// Returns the settings declared in the front matter, or the default ones if the template
+    /// has none.
+    pub fn front_matter(&self) -> &FrontMatter {
+        &self.front_matter
+    }
+
+    /// Returns the names of the variables the template reads from its context, sorted and
+    /// without duplicates.
+    ///
+    /// Only the first segment of a dotted path is a variable, so `${user.name}` reads `user`.
+    /// The variables of `{% for %}` loops are not read from the context inside their block, and
+    /// the variables of included templates are not known until rendering.
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = BTreeSet::new();
+        collect_node_variables(&self.nodes, &mut Vec::new(), &mut variables);
+        variables.into_iter().collect()
+    }
+
+    /// Returns the names of the templates the template includes, with `{% include %}` tags or
+    /// `{{> name}}` partials, sorted and without duplicates. The templates they include in turn
+    /// are not known until rendering.
+    pub fn includes(&self) -> Vec<String> {
+        let mut includes = BTreeSet::new();
+        collect_node_includes(&self.nodes, &mut includes);
+        includes.into_iter().collect()
+    }
+
+    pub(crate) fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the template with `escape` as the escape of its values, unless its front matter
+    /// chooses one.
+    pub(crate) fn with_default_escape(mut self, escape: Escape) -> Template {
+        if self.front_matter.escape == Escape::None {
+            self.front_matter.escape = escape;
+        }
+        self
+    }
+
+    pub(crate) fn line_ending(&self) -> Option<&str> {
+        self.line_ending.as_deref()
+    }
+}
+
+fn line_ending(text: &str) -> Option<String> {
+    let end = text.find('\n')?;
+    let line_ending = match text[..end].ends_with('\r') {
+        true => "\r\n",
+        false => "\n",
+    };
+    Some(line_ending.to_string())
+}
+
+fn collect_node_includes(nodes: &[Node], includes: &mut BTreeSet<String>) {
+    for node in nodes {
+        match node {
+            Node::Include { name, .. } | Node::Partial { name, .. } => {
+                includes.insert(name.clone());
+            }
+            Node::Section { nodes, .. } | Node::For { nodes, .. } => {
+                collect_node_includes(nodes, includes)
+            }
+            Node::Choice { cases, .. } => {
+                for case in cases {
+                    collect_node_includes(&case.nodes, includes);
+                }
+            }
+            Node::If {
+                branches,
+                otherwise,
+                ..
+            } => {
+                for branch in branches {
+                    collect_node_includes(&branch.nodes, includes);
+                }
+                if let Some(otherwise) = otherwise {
+                    collect_node_includes(otherwise, includes);
+                }
+            }
+            Node::Text(..)
+            | Node::Placeholder { .. }
+            | Node::Message { .. }
+            | Node::Variable { .. } => {}
+        }
+    }
+}
+
+fn collect_node_variables<'n>(
+    nodes: &'n [Node],
+    bound: &mut Vec<&'n str>,
+    variables: &mut BTreeSet<String>,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(..) | Node::Include { .. } | Node::Message { .. } | Node::Partial { .. } => {
+            }
+            Node::Variable { path, .. } => collect_path_variable(path, bound, variables),
+            Node::Section {
+                path,
+                inverted,
+                nodes,
+                ..
+            } => {
+                collect_path_variable(path, bound, variables);
+                // The names inside a section may be keys of its value rather than variables.
+                if *inverted {
+                    collect_node_variables(nodes, bound, variables);
+                }
+            }
+            Node::Choice {
+                variable, cases, ..
+            } => {
+                collect_expression_variables(variable, bound, variables);
+                for case in cases {
+                    collect_node_variables(&case.nodes, bound, variables);
+                }
+            }
+            Node::Placeholder { expression, .. } => {
+                collect_expression_variables(expression, bound, variables)
+            }
+            Node::If {
+                branches,
+                otherwise,
+                ..
+            } => {
+                for branch in branches {
+                    collect_expression_variables(&branch.condition, bound, variables);
+                    collect_node_variables(&branch.nodes, bound, variables);
+                }
+                if let Some(otherwise) = otherwise {
+                    collect_node_variables(otherwise, bound, variables);
+                }
+            }
+            Node::For {
+                variable,
+                iterable,
+                nodes,
+                ..
+            } => {
+                collect_expression_variables(iterable, bound, variables);
+                bound.push(variable);
+                collect_node_variables(nodes, bound, variables);
+                bound.pop();
+            }
+        }
+    }
+}
+
+fn collect_expression_variables(
+    expression: &Expression,
+    bound: &[&str],
+    variables: &mut BTreeSet<String>,
+) {
+    for path in expression.variable_paths() {
+        collect_path_variable(path, bound, variables);
+    }
+}
+
+/// Collects the variable of a dotted path, which is empty for the `.` of Mustache templates.
+fn collect_path_variable(path: &str, bound: &[&str], variables: &mut BTreeSet<String>) {
+    let name = path.split('.').next().unwrap_or(path);
+    if !name.is_empty() && !bound.contains(&name) {
+        variables.insert(name.to_string());
+    }
+}
+
+struct TemplateParser<'a> {
+    text: &'a str,
+    position: usize,
+    /// The syntax errors found so far, each skipped to go on parsing the rest of the text.
+    errors: Vec<TemplateError>,
+    /// The number of blocks and messages being parsed within each other.
+    depth: usize,
+    /// Whether parsing stopped at a block nested too deeply, leaving the blocks around it
+    /// unclosed without errors of their own.
+    truncated: bool,
+}
+
+impl<'a> TemplateParser<'a> {
+    /// Parses nodes until the end of the text or until a tag with one of the `closing` keywords,
+    /// which is returned along with the nodes. Invalid placeholders, messages and tags are
+    /// recorded in `errors` and skipped.
+    fn parse_nodes(&mut self, closing: &[&str]) -> (Vec<Node>, Option<Tag<'a>>) {
+        let mut nodes = Vec::new();
+        loop {
+            let rest = &self.text[self.position..];
+            let next = [rest.find("${"), rest.find("{%"), find_choice(rest)]
+                .into_iter()
+                .flatten()
+                .min();
+            let Some(found) = next else {
+                if !rest.is_empty() {
+                    nodes.push(Node::Text(
+                        rest.to_string(),
+                        Span::new(self.position, self.text.len()),
+                    ));
+                }
+                self.position = self.text.len();
+                return (nodes, None);
+            };
+            let start = self.position + found;
+            if found > 0 {
+                nodes.push(Node::Text(
+                    rest[..found].to_string(),
+                    Span::new(self.position, start),
+                ));
+            }
+            if rest[found..].starts_with("${") {
+                let placeholder = self.parse_placeholder(start);
+                if placeholder.is_err() {
+                    self.position = find_closing(self.text, start + 2, "}")
+                        .map_or(self.text.len(), |end| end + 1);
+                }
+                self.recover(placeholder, &mut nodes);
+                continue;
+            }
+            if !rest[found..].starts_with("{%") {
+                let choice = self.parse_choice(start);
+                if choice.is_err() {
+                    self.position = find_matching_brace(self.text, start)
+                        .map_or(self.text.len(), |end| end + 1);
+                }
+                self.recover(choice, &mut nodes);
+                continue;
+            }
+            let tag = match self.parse_tag(start) {
+                Ok(tag) => tag,
+                Err(error) => {
+                    self.errors.push(error);
+                    self.position = self.text.len();
+                    return (nodes, None);
+                }
+            };
+            if closing.contains(&tag.keyword) {
+                return (nodes, Some(tag));
+            }
+            if matches!(tag.keyword, "if" | "for") && self.depth == MAX_NESTING_DEPTH {
+                self.errors.push(too_deep(tag.span));
+                self.position = self.text.len();
+                self.truncated = true;
+                return (nodes, None);
+            }
+            self.depth += 1;
+            match tag.keyword {
+                "if" => nodes.push(self.parse_if(tag)),
+                "for" => nodes.extend(self.parse_for(tag)),
+                "include" => self.recover(parse_include(&tag), &mut nodes),
+                "message" => self.recover(parse_message(&tag), &mut nodes),
+                "elif" | "else" | "endif" | "endfor" => self.errors.push(syntax_error(
+                    format!("unexpected `{}` tag", tag.keyword),
+                    tag.span,
+                )),
+                keyword => self
+                    .errors
+                    .push(syntax_error(format!("unknown tag `{}`", keyword), tag.span)),
+            }
+            self.depth -= 1;
+        }
+    }
+
+    /// Adds a parsed node to `nodes`, or records its error.
+    fn recover(&mut self, parsed: Result<Node, TemplateError>, nodes: &mut Vec<Node>) {
+        match parsed {
+            Ok(node) => nodes.push(node),
+            Err(error) => self.errors.push(error),
+        }
+    }
+
+    fn parse_placeholder(&mut self, start: usize) -> Result<Node, TemplateError> {
+        let end = find_closing(self.text, start + 2, "}").ok_or_else(|| {
+            syntax_error(
+                "unclosed placeholder".to_string(),
+                Span::new(start, self.text.len()),
+            )
+        })?;
+        let (expression_end, spec) = match find_closing(&self.text[..end], start + 2, ":") {
+            Some(colon) => (
+                colon,
+                Some(FormatSpec::parse(&self.text[colon + 1..end], colon + 1)?),
+            ),
+            None => (end, None),
+        };
+        let expression = parse_expression(&self.text[start + 2..expression_end], start + 2)?;
+        let (expression, escape) = split_escape(expression)?;
+        let (expression, indent) = split_indent(expression);
+        self.position = end + 1;
+        Ok(Node::Placeholder {
+            expression,
+            spec,
+            escape,
+            indent,
+            source: self.text[start..end + 1].to_string(),
+            span: Span::new(start, end + 1),
+        })
+    }
+
+    /// Parses an ICU message starting at `start`, where `find_choice` found its header.
+    fn parse_choice(&mut self, start: usize) -> Result<Node, TemplateError> {
+        let unclosed = || {
+            syntax_error(
+                "unclosed message".to_string(),
+                Span::new(start, self.text.len()),
+            )
+        };
+        let (variable, kind, header_length) =
+            choice_header(&self.text[start..]).ok_or_else(unclosed)?;
+        if self.depth == MAX_NESTING_DEPTH {
+            return Err(too_deep(Span::new(start, start + header_length)));
+        }
+        let variable_start = start + self.text[start..].find(variable).unwrap_or_default();
+        let variable = Expression::Variable(
+            variable.to_string(),
+            Span::new(variable_start, variable_start + variable.len()),
+        );
+        let mut kind = match kind {
+            "plural" => ChoiceKind::Plural { offset: 0 },
+            _ => ChoiceKind::Select,
+        };
+        let mut position = start + header_length;
+        let mut cases = Vec::new();
+        loop {
+            position = skip_whitespace(self.text, position);
+            let rest = &self.text[position..];
+            if rest.starts_with('}') {
+                position += 1;
+                break;
+            }
+            let length = rest
+                .find(|c: char| c.is_whitespace() || c == '{' || c == '}')
+                .unwrap_or(rest.len());
+            if length == 0 {
+                return Err(unclosed());
+            }
+            let selector_text = &rest[..length];
+            let selector_span = Span::new(position, position + length);
+            position = skip_whitespace(self.text, position + length);
+            if let (ChoiceKind::Plural { .. }, Some(offset), true) = (
+                kind,
+                selector_text.strip_prefix("offset:"),
+                cases.is_empty(),
+            ) {
+                let offset = offset.parse().map_err(|_| {
+                    syntax_error("expected an integer offset".to_string(), selector_span)
+                })?;
+                kind = ChoiceKind::Plural { offset };
+                continue;
+            }
+            let selector = parse_selector(selector_text, kind, selector_span)?;
+            if !self.text[position..].starts_with('{') {
+                return Err(syntax_error(
+                    format!("expected `{{` after `{}`", selector_text),
+                    selector_span,
+                ));
+            }
+            let end = find_matching_brace(self.text, position).ok_or_else(unclosed)?;
+            let mut parser = TemplateParser {
+                text: &self.text[..end],
+                position: position + 1,
+                errors: Vec::new(),
+                depth: self.depth + 1,
+                truncated: false,
+            };
+            let (mut nodes, _) = parser.parse_nodes(&[]);
+            self.errors.extend(parser.errors);
+            if let ChoiceKind::Plural { offset } = kind {
+                nodes = replace_number_signs(nodes, &variable, offset);
+            }
+            cases.push(Case { selector, nodes });
+            position = end + 1;
+        }
+        let span = Span::new(start, position);
+        let other = match kind {
+            ChoiceKind::Plural { .. } => Selector::Category(PluralCategory::Other),
+            ChoiceKind::Select => Selector::Key("other".to_string()),
+        };
+        if !cases.iter().any(|case| case.selector == other) {
+            return Err(syntax_error(
+                "the message has no `other` case".to_string(),
+                span,
+            ));
+        }
+        self.position = position;
+        Ok(Node::Choice {
+            variable,
+            kind,
+            cases,
+            span,
+        })
+    }
+
+    fn parse_tag(&mut self, start: usize) -> Result<Tag<'a>, TemplateError> {
+        let end = find_closing(self.text, start + 2, "%}").ok_or_else(|| {
+            syntax_error(
+                "unclosed tag".to_string(),
+                Span::new(start, self.text.len()),
+            )
+        })?;
+        self.position = end + 2;
+        let content = &self.text[start + 2..end];
+        let content_offset = start + 2 + (content.len() - content.trim_start().len());
+        let content = content.trim();
+        let keyword_length = content.find(char::is_whitespace).unwrap_or(content.len());
+        Ok(Tag {
+            keyword: &content[..keyword_length],
+            arguments: &content[keyword_length..],
+            arguments_offset: content_offset + keyword_length,
+            span: Span::new(start, end + 2),
+        })
+    }
+
+    fn parse_if(&mut self, tag: Tag<'a>) -> Node {
+        let start = tag.span.start;
+        let mut condition = self.parse_condition(&tag);
+        let mut branch_start = start;
+        let mut branches = Vec::new();
+        loop {
+            let (nodes, closing) = self.parse_nodes(&["elif", "else", "endif"]);
+            let Some(closing) = closing else {
+                let end = self.unclosed("if", tag.span);
+                branches.push(Branch {
+                    condition,
+                    nodes,
+                    span: Span::new(branch_start, end),
+                });
+                return Node::If {
+                    branches,
+                    otherwise: None,
+                    span: Span::new(start, end),
+                };
+            };
+            branches.push(Branch {
+                condition,
+                nodes,
+                span: Span::new(branch_start, closing.span.start),
+            });
+            match closing.keyword {
+                "elif" => {
+                    condition = self.parse_condition(&closing);
+                    branch_start = closing.span.start;
+                }
+                "else" => {
+                    let (nodes, endif) = self.parse_nodes(&["endif"]);
+                    let end = match endif {
+                        Some(endif) => endif.span.end,
+                        None => self.unclosed("if", tag.span),
+                    };
+                    return Node::If {
+                        branches,
+                        otherwise: Some(nodes),
+                        span: Span::new(start, end),
+                    };
+                }
+                _ => {
+                    return Node::If {
+                        branches,
+                        otherwise: None,
+                        span: Span::new(start, closing.span.end),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses a `{% for %}` block, or only records its errors if its tag is invalid.
+    fn parse_for(&mut self, tag: Tag<'a>) -> Option<Node> {
+        let header = self.parse_for_header(&tag);
+        let (nodes, endfor) = self.parse_nodes(&["endfor"]);
+        let end = match endfor {
+            Some(endfor) => endfor.span.end,
+            None => self.unclosed("for", tag.span),
+        };
+        match header {
+            Ok((variable, iterable)) => Some(Node::For {
+                variable,
+                iterable,
+                nodes,
+                span: Span::new(tag.span.start, end),
+            }),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        }
+    }
+
+    /// Parses the `variable in expression` arguments of a `{% for %}` tag.
+    fn parse_for_header(&self, tag: &Tag) -> Result<(String, Expression), TemplateError> {
+        let arguments = tag.arguments.trim_start();
+        let arguments_offset = tag.arguments_offset + (tag.arguments.len() - arguments.len());
+        let variable_length = arguments
+            .find(char::is_whitespace)
+            .unwrap_or(arguments.len());
+        let variable = &arguments[..variable_length];
+        let rest = arguments[variable_length..].trim_start();
+        let is_identifier = variable
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_alphabetic() || first == '_')
+            && variable.chars().all(|c| c.is_alphanumeric() || c == '_');
+        let iterable_source = rest
+            .strip_prefix("in")
+            .filter(|source| source.starts_with(char::is_whitespace));
+        let (true, Some(iterable_source)) = (is_identifier, iterable_source) else {
+            return Err(syntax_error(
+                "expected `for variable in expression`".to_string(),
+                tag.span,
+            ));
+        };
+        let iterable_offset = arguments_offset + (arguments.len() - rest.len()) + 2;
+        let iterable = parse_expression(iterable_source, iterable_offset)?;
+        Ok((variable.to_string(), iterable))
+    }
+
+    /// Parses the condition of an `{% if %}` or `{% elif %}` tag. An invalid condition is
+    /// recorded and taken as `false`.
+    fn parse_condition(&mut self, tag: &Tag) -> Expression {
+        let condition = match tag.arguments.trim().is_empty() {
+            true => Err(syntax_error(
+                format!("missing condition in `{}` tag", tag.keyword),
+                tag.span,
+            )),
+            false => parse_expression(tag.arguments, tag.arguments_offset),
+        };
+        condition.unwrap_or_else(|error| {
+            self.errors.push(error);
+            Expression::Literal(Value::Bool(false), tag.span)
+        })
+    }
+
+    /// Records that the block opened by `tag` is not closed, returning the end of the text
+    /// where the block ends.
+    fn unclosed(&mut self, keyword: &str, span: Span) -> usize {
+        if !self.truncated {
+            self.errors
+                .push(syntax_error(format!("unclosed `{}` block", keyword), span));
+        }
+        self.text.len()
+    }
+}
+
+fn parse_include(tag: &Tag) -> Result<Node, TemplateError> {
+    match parse_expression(tag.arguments, tag.arguments_offset) {
+        Ok(Expression::Literal(Value::String(name), _)) => Ok(Node::Include {
+            name,
+            span: tag.span,
+        }),
+        _ => Err(syntax_error(
+            "expected the name of a template as a string".to_string(),
+            tag.span,
+        )),
+    }
+}
+
+/// Parses the key of a `{% message "key" %}` tag, which must be a string literal.
+fn parse_message(tag: &Tag) -> Result<Node, TemplateError> {
+    match parse_expression(tag.arguments, tag.arguments_offset) {
+        Ok(Expression::Literal(Value::String(key), _)) => Ok(Node::Message {
+            key,
+            span: tag.span,
+        }),
+        _ => Err(syntax_error(
+            "expected the key of a message as a string".to_string(),
+            tag.span,
+        )),
+    }
+}
+
+/// Finds the start of the first ICU message in `text`, a `{` followed by a variable, a comma,
+/// `plural` or `select` and another comma. Any other `{` is literal text.
+fn find_choice(text: &str) -> Option<usize> {
+    text.match_indices('{')
+        .map(|(index, _)| index)
+        .find(|index| choice_header(&text[*index..]).is_some())
+}
+
+/// Reads the `{variable, kind,` header of an ICU message, returning the variable, the kind and
+/// the length of the header.
+fn choice_header(text: &str) -> Option<(&str, &str, usize)> {
+    let inner = text.strip_prefix('{')?.trim_start();
+    let length = inner
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or(inner.len());
+    let variable = &inner[..length];
+    if !variable.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return None;
+    }
+    let after_variable = inner[length..].trim_start().strip_prefix(',')?.trim_start();
+    let kind = ["plural", "select"]
+        .into_iter()
+        .find(|kind| after_variable.starts_with(kind))?;
+    let after_kind = after_variable[kind.len()..]
+        .trim_start()
+        .strip_prefix(',')?;
+    Some((variable, kind, text.len() - after_kind.len()))
+}
+
+fn parse_selector(text: &str, kind: ChoiceKind, span: Span) -> Result<Selector, TemplateError> {
+    match kind {
+        ChoiceKind::Plural { .. } => match text.strip_prefix('=') {
+            Some(number) => number.parse().map(Selector::Exact).map_err(|_| {
+                syntax_error(format!("expected a number after `=` in `{}`", text), span)
+            }),
+            None => PluralCategory::from_keyword(text)
+                .map(Selector::Category)
+                .ok_or_else(|| syntax_error(format!("unknown plural category `{}`", text), span)),
+        },
+        ChoiceKind::Select => Ok(Selector::Key(text.to_string())),
+    }
+}
+
+/// Splits the `escape("name")` filter ending the expression of a placeholder off it.
+fn split_escape(expression: Expression) -> Result<(Expression, Option<Escape>), TemplateError> {
+    match expression {
+        Expression::Call {
+            name,
+            mut arguments,
+            span,
+        } if name == "escape" && arguments.len() == 2 => match arguments.pop() {
+            Some(Expression::Literal(Value::String(escape), _)) => {
+                Ok((arguments.remove(0), Some(Escape::from(escape))))
+            }
+            _ => Err(syntax_error(
+                "the escape filter expects the name of an escaper as a string literal".to_string(),
+                span,
+            )),
+        },
+        expression => Ok((expression, None)),
+    }
+}
+
+/// Splits the `indent` filter ending the expression of a placeholder, before any `escape`
+/// filter, off it.
+fn split_indent(expression: Expression) -> (Expression, bool) {
+    match expression {
+        Expression::Call {
+            name,
+            mut arguments,
+            ..
+        } if name == "indent" && arguments.len() == 1 => (arguments.remove(0), true),
+        expression => (expression, false),
+    }
+}
+
+/// Replaces the `#` signs of the text of a plural case, outside nested messages, with
+/// placeholders of the number minus the offset.
+fn replace_number_signs(nodes: Vec<Node>, variable: &Expression, offset: i64) -> Vec<Node> {
+    let mut replaced = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Text(literal, span) => {
+                let mut position = span.start;
+                for (index, piece) in literal.split('#').enumerate() {
+                    if index > 0 {
+                        let sign = Span::new(position, position + 1);
+                        replaced.push(Node::Placeholder {
+                            expression: number_expression(variable, offset, sign),
+                            spec: None,
+                            escape: None,
+                            indent: false,
+                            source: "#".to_string(),
+                            span: sign,
+                        });
+                        position += 1;
+                    }
+                    if !piece.is_empty() {
+                        replaced.push(Node::Text(
+                            piece.to_string(),
+                            Span::new(position, position + piece.len()),
+                        ));
+                    }
+                    position += piece.len();
+                }
+            }
+            Node::If {
+                branches,
+                otherwise,
+                span,
+            } => replaced.push(Node::If {
+                branches: branches
+                    .into_iter()
+                    .map(|branch| Branch {
+                        condition: branch.condition,
+                        nodes: replace_number_signs(branch.nodes, variable, offset),
+                        span: branch.span,
+                    })
+                    .collect(),
+                otherwise: otherwise.map(|nodes| replace_number_signs(nodes, variable, offset)),
+                span,
+            }),
+            Node::For {
+                variable: item,
+                iterable,
+                nodes,
+                span,
+            } => replaced.push(Node::For {
+                variable: item,
+                iterable,
+                nodes: replace_number_signs(nodes, variable, offset),
+                span,
+            }),
+            node => replaced.push(node),
+        }
+    }
+    replaced
+}
+
+fn number_expression(variable: &Expression, offset: i64, span: Span) -> Expression {
+    let Expression::Variable(path, _) = variable else {
+        return variable.clone();
+    };
+    let number = Expression::Variable(path.clone(), span);
+    if offset == 0 {
+        return number;
+    }
+    Expression::Binary {
+        operator: BinaryOperator::Subtract,
+        left: Box::new(number),
+        right: Box::new(Expression::Literal(Value::from(offset), span)),
+        span,
+    }
+}
+
+fn skip_whitespace(text: &str, position: usize) -> usize {
+    let rest = &text[position..];
+    position + rest.len() - rest.trim_start().len()
+}
+
+/// Finds the `}` closing the `{` at `open`, counting the braces in between.
+fn find_matching_brace(text: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, character) in text[open..].char_indices() {
+        match character {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Finds the `closing` delimiter of a placeholder or tag, skipping the ones written inside
+/// string literals.
+fn find_closing(template_text: &str, from: usize, closing: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, character) in template_text[from..].char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if !in_string && template_text[from + index..].starts_with(closing) => {
+                return Some(from + index)
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The error of a block or message opened by the text at `span` within `MAX_NESTING_DEPTH`
+/// others.
+pub(crate) fn too_deep(span: Span) -> TemplateError {
+    syntax_error(
+        format!("blocks nested deeper than {} levels", MAX_NESTING_DEPTH),
+        span,
+    )
+}
+
+fn syntax_error(message: String, span: Span) -> TemplateError {
+    TemplateError::Syntax { message, span }
+}
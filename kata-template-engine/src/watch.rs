@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+
+use crate::snapshot::unified_diff;
+use crate::{Renderer, Template, TemplateError};
+
+/// How often the command line checks the watched files for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The modification time and the length of a file, or `None` if it does not exist.
+type Stamp = Option<(Option<SystemTime>, u64)>;
+
+/// The output of a render of `RenderWatch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchedRender {
+    pub text: String,
+    pub warnings: Vec<String>,
+    /// The unified diff from the output of the previous successful render, empty if the output
+    /// did not change, or `None` for the first one.
+    pub diff: Option<String>,
+}
+
+/// Renders a template file again whenever it, the templates it includes or its variables file
+/// change, so that templates can be designed without rerunning the renderer by hand.
+///
+/// The template `{% include "header" %}` is read from the file `header` with the extension of
+/// the including template, next to it. Changes are found by polling the modification times of
+/// the files read by the last render, including the missing ones, so that creating a missing
+/// include renders the template again.
+#[derive(Debug)]
+pub struct RenderWatch {
+    template: PathBuf,
+    variables: Option<PathBuf>,
+    /// The files read by the last render, with their stamps when they were read.
+    files: BTreeMap<PathBuf, Stamp>,
+    previous: Option<String>,
+}
+
+impl RenderWatch {
+    pub fn new(template: &Path, variables: Option<&Path>) -> RenderWatch {
+        RenderWatch {
+            template: template.to_path_buf(),
+            variables: variables.map(Path::to_path_buf),
+            files: BTreeMap::new(),
+            previous: None,
+        }
+    }
+
+    /// Reads the template, its includes and its variables file again and renders the template,
+    /// registering the includes on `renderer`. The files read are watched even if the render
+    /// fails.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if a file cannot be read or parsed, wrapped in
+    /// `TemplateError::InFile`, and the errors of `Renderer::render` if the template cannot be
+    /// rendered.
+    pub fn render(&mut self, renderer: &mut Renderer) -> Result<WatchedRender, TemplateError> {
+        self.files.clear();
+        let context = match self.variables.clone() {
+            Some(path) => {
+                let text = self.read(&path)?;
+                serde_json::from_str(&text)
+                    .map_err(|error| in_file(&path, TemplateError::JsonError(error)))?
+            }
+            None => Value::Object(Default::default()),
+        };
+        let template_path = self.template.clone();
+        let text = self.read(&template_path)?;
+        let template = Template::parse(&text).map_err(|error| in_file(&template_path, error))?;
+        let mut included = self.add_includes(renderer, &template)?;
+        while let Some(include) = included.pop() {
+            included.extend(self.add_includes(renderer, &include)?);
+        }
+        let parsed_template = renderer.render(&template, &context)?;
+        let diff =
+            self.previous
+                .replace(parsed_template.text.clone())
+                .map(|previous| match previous == parsed_template.text {
+                    true => String::new(),
+                    false => unified_diff(&previous, &parsed_template.text, "previous output"),
+                });
+        Ok(WatchedRender {
+            text: parsed_template.text,
+            warnings: parsed_template.warnings,
+            diff,
+        })
+    }
+
+    /// Returns the files read by the last render that were changed, created or removed since,
+    /// sorted.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|(path, read)| stamp(path) != **read)
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// Registers the templates included by `template` that were not read yet, and returns the
+    /// ones that exist. Missing ones are left for the renderer to report.
+    fn add_includes(
+        &mut self,
+        renderer: &mut Renderer,
+        template: &Template,
+    ) -> Result<Vec<Template>, TemplateError> {
+        let mut includes = Vec::new();
+        for name in template.includes() {
+            let path = include_path(&self.template, &name);
+            if self.files.contains_key(&path) {
+                continue;
+            }
+            let Ok(text) = self.read(&path) else {
+                continue;
+            };
+            let include = Template::parse(&text).map_err(|error| in_file(&path, error))?;
+            renderer.insert_template(&name, include.clone());
+            includes.push(include);
+        }
+        Ok(includes)
+    }
+
+    fn read(&mut self, path: &Path) -> Result<String, TemplateError> {
+        self.files.insert(path.to_path_buf(), stamp(path));
+        fs::read_to_string(path).map_err(|error| in_file(path, TemplateError::IoError(error)))
+    }
+}
+
+/// Returns the file of the template `name` included by the template file `template`.
+pub(crate) fn include_path(template: &Path, name: &str) -> PathBuf {
+    let path = template.with_file_name(name);
+    match template.extension() {
+        Some(extension) => path.with_extension(extension),
+        None => path,
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+fn in_file(path: &Path, error: TemplateError) -> TemplateError {
+    TemplateError::InFile {
+        path: path.to_path_buf(),
+        error: Box::new(error),
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+
+use kata_template_engine::batch::{render_batch, BatchOutput, RecordFormat, RecordIssue};
+use kata_template_engine::{Renderer, Template};
+
+#[test]
+fn given_json_lines_it_renders_one_output_per_record_to_a_stream() {
+    let template = Template::parse("Hello, ${name}!\n").unwrap();
+    let records = "{\"name\": \"John\"}\n\n{\"name\": \"Jane\"}\n";
+    let mut output = Vec::new();
+    let report = render_batch(
+        &Renderer::new(),
+        &template,
+        records.as_bytes(),
+        RecordFormat::JsonLines,
+        BatchOutput::Stream(&mut output),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Hello, John!\nHello, Jane!\n"
+    );
+    assert_eq!(report.rendered, 2);
+    assert!(report.failures.is_empty());
+}
+
+#[test]
+fn given_a_csv_file_it_uses_the_header_as_variable_names() {
+    let template = Template::parse("${greet}, ${name}. ").unwrap();
+    let records = "greet,name\nHello,John\nHi,\"Smith, Jane\"\n";
+    let mut output = Vec::new();
+    let report = render_batch(
+        &Renderer::new(),
+        &template,
+        records.as_bytes(),
+        RecordFormat::Csv,
+        BatchOutput::Stream(&mut output),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "Hello, John. Hi, Smith, Jane. "
+    );
+    assert_eq!(report.rendered, 2);
+}
+
+#[test]
+fn given_failing_records_it_reports_them_and_renders_the_rest() {
+    let template = Template::parse("${total / count} ${name}\n").unwrap();
+    let records = "{\"total\": 4, \"count\": 2, \"name\": \"a\"}\nnot json\n{\"total\": 1, \"count\": 0}\n{\"total\": 3, \"count\": 1}\n";
+    let mut output = Vec::new();
+    let report = render_batch(
+        &Renderer::new(),
+        &template,
+        records.as_bytes(),
+        RecordFormat::JsonLines,
+        BatchOutput::Stream(&mut output),
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "2 a\n3 ${name}\n");
+    assert_eq!(report.rendered, 2);
+    assert_eq!(
+        report.warnings,
+        vec![RecordIssue {
+            record: 4,
+            message: "Variable name not replaced".to_string()
+        }]
+    );
+    assert_eq!(report.failures.len(), 2);
+    assert_eq!(report.failures[0].record, 2);
+    assert!(report.failures[0].message.starts_with("JSON error"));
+    assert_eq!(
+        report.failures[1],
+        RecordIssue {
+            record: 3,
+            message: "Division by zero at 2..15".to_string()
+        }
+    );
+}
+
+#[test]
+fn given_a_path_template_it_writes_one_file_per_record() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = Template::parse("Dear ${name}").unwrap();
+    let records = "{\"id\": 1, \"name\": \"John\"}\n{\"id\": 2, \"name\": \"Jane\"}\n{\"id\": \"../x\", \"name\": \"Eve\"}\n";
+    let report = render_batch(
+        &Renderer::new(),
+        &template,
+        records.as_bytes(),
+        RecordFormat::JsonLines,
+        BatchOutput::Files {
+            directory: directory.path().to_path_buf(),
+            path: Template::parse("letters/${id}.txt").unwrap(),
+            force: false,
+        },
+    )
+    .unwrap();
+    let letters = directory.path().join("letters");
+    assert_eq!(
+        fs::read_to_string(letters.join("1.txt")).unwrap(),
+        "Dear John"
+    );
+    assert_eq!(
+        fs::read_to_string(letters.join("2.txt")).unwrap(),
+        "Dear Jane"
+    );
+    assert_eq!(report.rendered, 2);
+    assert_eq!(
+        report.failures,
+        vec![RecordIssue {
+            record: 3,
+            message: "Output path letters/../x.txt is outside the output directory".to_string()
+        }]
+    );
+}
@@ -0,0 +1,65 @@
+use std::fs;
+
+use kata_template_engine::cache::{TemplateCache, CACHE_FORMAT_VERSION};
+use kata_template_engine::{Renderer, Template};
+use serde_json::json;
+
+const INVOICE: &str = "---
+escape: html
+defaults:
+  currency: EUR
+---
+{% for item in items %}${item.name | upper:<8}${item.price * 1.2 | currency(currency)}
+{% endfor %}{count, plural, one {# item} other {# items}}";
+
+#[test]
+fn given_a_saved_cache_it_loads_the_same_compiled_templates() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("templates.cache");
+    let mut cache = TemplateCache::load(&path).unwrap();
+    let compiled = cache.compile("invoice", INVOICE).unwrap();
+    assert!(cache.is_modified());
+    cache.save(&path).unwrap();
+
+    let mut loaded = TemplateCache::load(&path).unwrap();
+    assert_eq!(loaded.compile("invoice", INVOICE).unwrap(), compiled);
+    assert_eq!(compiled, Template::parse(INVOICE).unwrap());
+    assert!(!loaded.is_modified());
+}
+
+#[test]
+fn given_a_changed_source_it_compiles_the_template_again() {
+    let mut cache = TemplateCache::new();
+    cache.compile("greeting", "Hello, ${name}").unwrap();
+    let mut loaded = TemplateCache::from_bytes(&cache.to_bytes()).unwrap();
+    let mut renderer = Renderer::new();
+    renderer
+        .add_cached_template("greeting", "Bye, ${name}", &mut loaded)
+        .unwrap();
+    assert!(loaded.is_modified());
+    let parsed_template = renderer
+        .render_template("greeting", &json!({"name": "Ada"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "Bye, Ada");
+}
+
+#[test]
+fn given_a_cache_of_another_version_or_a_damaged_one_it_starts_empty() {
+    let mut cache = TemplateCache::new();
+    cache.compile("greeting", "Hello, ${name}").unwrap();
+    let bytes = cache.to_bytes();
+    assert_eq!(&bytes[..4], b"KTPL");
+    assert_eq!(bytes[4..8], CACHE_FORMAT_VERSION.to_le_bytes());
+
+    let mut other_version = bytes.clone();
+    other_version[4..8].copy_from_slice(&(CACHE_FORMAT_VERSION + 1).to_le_bytes());
+    assert!(TemplateCache::from_bytes(&other_version).is_none());
+    assert!(TemplateCache::from_bytes(&bytes[..bytes.len() - 3]).is_none());
+
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("templates.cache");
+    fs::write(&path, &other_version).unwrap();
+    let mut loaded = TemplateCache::load(&path).unwrap();
+    loaded.compile("greeting", "Hello, ${name}").unwrap();
+    assert!(loaded.is_modified());
+}
@@ -0,0 +1,257 @@
+use std::fs;
+
+use kata_template_engine::cli::run;
+
+fn run_cli(args: &[&str]) -> (i32, String, String) {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let code = run(&args, &mut stdout, &mut stderr);
+    (
+        code,
+        String::from_utf8(stdout).unwrap(),
+        String::from_utf8(stderr).unwrap(),
+    )
+}
+
+#[test]
+fn given_a_template_and_a_variables_file_it_renders_the_template() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("greeting.txt");
+    let variables = directory.path().join("variables.json");
+    fs::write(&template, "${greet}, ${name}").unwrap();
+    fs::write(&variables, r#"{"greet": "Hello"}"#).unwrap();
+    let (code, stdout, stderr) = run_cli(&[
+        "render",
+        template.to_str().unwrap(),
+        variables.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "Hello, ${name}");
+    assert_eq!(stderr, "warning: Variable name not replaced\n");
+}
+
+#[test]
+fn given_a_csv_file_the_batch_command_reports_warnings_and_failures() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("letter.txt");
+    let records = directory.path().join("records.csv");
+    fs::write(&template, "${name}: ${len(items)} ${note}\n").unwrap();
+    fs::write(&records, "name,items\nJohn,abc\nJane\n").unwrap();
+    let (code, stdout, stderr) = run_cli(&[
+        "batch",
+        template.to_str().unwrap(),
+        records.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 1);
+    assert_eq!(stdout, "John: 3 ${note}\n");
+    assert_eq!(
+        stderr,
+        "warning: record 1: Variable note not replaced\n\
+         error: record 2: CSV error: record has 1 fields, but the header has 2\n\
+         Rendered 1 records, 1 warnings, 1 failures\n\
+         error: 1 records failed\n"
+    );
+}
+
+#[test]
+fn given_an_unknown_option_it_exits_with_a_usage_error() {
+    let (code, _, stderr) = run_cli(&["render", "page.txt", "--exapnd"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: Unknown option --exapnd\nUsage:"));
+    let (code, _, stderr) = run_cli(&["test", "templates", "--force"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: Unknown option --force\nUsage:"));
+}
+
+#[test]
+fn given_records_writing_the_same_file_the_batch_command_only_overwrites_with_force() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("letter.txt");
+    let records = directory.path().join("records.jsonl");
+    let output = directory.path().join("out");
+    fs::write(&template, "Dear ${name}").unwrap();
+    fs::write(
+        &records,
+        "{\"id\": 1, \"name\": \"John\"}\n{\"id\": 1, \"name\": \"Jane\"}\n",
+    )
+    .unwrap();
+    let batch = |force: bool| {
+        let mut args = vec![
+            "batch",
+            template.to_str().unwrap(),
+            records.to_str().unwrap(),
+            "--output-dir",
+            output.to_str().unwrap(),
+            "--output-path",
+            "${id}.txt",
+        ];
+        if force {
+            args.push("--force");
+        }
+        run_cli(&args)
+    };
+    let written = output.join("1.txt");
+    let (code, _, stderr) = batch(false);
+    assert_eq!(code, 1);
+    assert!(stderr.contains(&format!(
+        "error: record 2: Output file {} is already written by record 1\n",
+        written.display()
+    )));
+    assert_eq!(fs::read_to_string(&written).unwrap(), "Dear John");
+    fs::write(&written, "Draft").unwrap();
+    let (code, _, stderr) = batch(false);
+    assert_eq!(code, 1);
+    assert!(stderr.contains(&format!(
+        "error: record 1: Output file {} already exists\n",
+        written.display()
+    )));
+    assert_eq!(fs::read_to_string(&written).unwrap(), "Draft");
+    let (code, _, stderr) = batch(true);
+    assert_eq!(code, 1);
+    assert!(stderr.contains("error: record 2: Output file"));
+    assert_eq!(fs::read_to_string(&written).unwrap(), "Dear John");
+}
+
+#[test]
+fn given_an_unknown_command_it_exits_with_a_usage_error() {
+    let (code, _, stderr) = run_cli(&["frobnicate"]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: Unknown command frobnicate\nUsage:"));
+}
+
+#[test]
+fn given_the_watch_and_source_map_options_it_exits_with_a_usage_error() {
+    let (code, _, stderr) = run_cli(&[
+        "render",
+        "page.txt",
+        "--watch",
+        "--source-map",
+        "page.map.json",
+    ]);
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: --watch and --source-map cannot go together\nUsage:"));
+}
+
+#[test]
+fn given_a_template_directory_the_scaffold_command_lists_the_created_files() {
+    let source = tempfile::tempdir().unwrap();
+    let destination = tempfile::tempdir().unwrap();
+    let variables_directory = tempfile::tempdir().unwrap();
+    let variables = variables_directory.path().join("variables.json");
+    fs::create_dir_all(source.path().join("${name}")).unwrap();
+    fs::write(source.path().join("${name}/README.md"), "# ${name}").unwrap();
+    fs::write(&variables, r#"{"name": "billing"}"#).unwrap();
+    let args = [
+        "scaffold",
+        source.path().to_str().unwrap(),
+        destination.path().to_str().unwrap(),
+        variables.to_str().unwrap(),
+    ];
+    let (code, stdout, _) = run_cli(&args);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "billing/README.md\n");
+    let (code, _, stderr) = run_cli(&args);
+    assert_eq!(code, 1);
+    assert!(stderr.ends_with("already exists (use --force to overwrite)\n"));
+}
+
+#[test]
+fn given_the_source_map_option_the_render_command_writes_the_source_map() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("greeting.txt");
+    let variables = directory.path().join("variables.json");
+    let source_map = directory.path().join("greeting.map.json");
+    fs::write(&template, "Hello, ${name}").unwrap();
+    fs::write(&variables, r#"{"name": "John"}"#).unwrap();
+    let (code, stdout, _) = run_cli(&[
+        "render",
+        template.to_str().unwrap(),
+        variables.to_str().unwrap(),
+        "--source-map",
+        source_map.to_str().unwrap(),
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "Hello, John");
+    let source_map: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(source_map).unwrap()).unwrap();
+    assert_eq!(
+        source_map["entries"][1]["template"],
+        template.to_str().unwrap()
+    );
+    assert_eq!(source_map["entries"][1]["variables"][0], "name");
+}
+
+#[test]
+fn given_the_expand_flag_it_expands_the_placeholders_of_values() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("link.txt");
+    let variables = directory.path().join("variables.json");
+    fs::write(&template, "${url}").unwrap();
+    fs::write(
+        &variables,
+        r#"{"host": "example.com", "url": "https://${host}"}"#,
+    )
+    .unwrap();
+    let (code, stdout, _) = run_cli(&[
+        "render",
+        template.to_str().unwrap(),
+        variables.to_str().unwrap(),
+        "--expand",
+    ]);
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "https://example.com");
+}
+
+#[test]
+fn given_a_snapshot_mismatch_the_test_command_prints_the_diff_and_fails() {
+    let directory = tempfile::tempdir().unwrap();
+    fs::write(directory.path().join("greeting.txt"), "Hello, ${name}\n").unwrap();
+    fs::write(directory.path().join("greeting.json"), r#"{"name": "Ada"}"#).unwrap();
+    let path = directory.path().to_str().unwrap();
+    let (code, stdout, stderr) = run_cli(&["test", path]);
+    assert_eq!(code, 1);
+    assert_eq!(
+        stdout,
+        "FAILED greeting.expected\n--- /dev/null\n+++ output\n@@ -0,0 +1 @@\n+Hello, Ada\n\n"
+    );
+    assert!(stderr.contains("0 passed, 1 failed, 0 updated"));
+    let (code, stdout, _) = run_cli(&["test", path, "--update"]);
+    assert_eq!((code, stdout.as_str()), (0, "updated greeting.expected\n"));
+    let (code, _, stderr) = run_cli(&["test", path]);
+    assert_eq!(
+        (code, stderr.as_str()),
+        (0, "1 passed, 0 failed, 0 updated\n")
+    );
+}
+
+#[test]
+fn given_records_the_coverage_command_reports_the_regions_never_rendered() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("letter.txt");
+    let records = directory.path().join("records.jsonl");
+    fs::write(
+        &template,
+        "{% if vip %}Dear ${name}{% else %}Hello{% endif %}",
+    )
+    .unwrap();
+    fs::write(&records, "{\"vip\": false}\n{\"vip\": false}\n").unwrap();
+    let (code, stdout, stderr) = run_cli(&[
+        "coverage",
+        template.to_str().unwrap(),
+        records.to_str().unwrap(),
+        "--report",
+        "json",
+    ]);
+    assert_eq!((code, stderr.as_str()), (0, ""));
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["renders"], 2);
+    assert_eq!(report["files"][0]["covered"], 1);
+    let kinds: Vec<_> = report["files"][0]["uncovered"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|region| region["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, vec!["branch", "placeholder"]);
+}
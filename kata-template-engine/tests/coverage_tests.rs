@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fs;
+
+use kata_template_engine::coverage::{Coverage, RegionKind};
+use kata_template_engine::Renderer;
+use serde_json::json;
+
+#[test]
+fn given_several_contexts_it_reports_the_branches_and_loops_none_of_them_reached() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("invoice.txt");
+    fs::write(
+        &path,
+        "Dear ${name},\n\
+         {% if overdue %}Overdue!{% elif total > 100 %}Big invoice{% else %}Thanks{% endif %}\n\
+         {% for item in items %}- ${item}\n{% endfor %}",
+    )
+    .unwrap();
+    let mut renderer = Renderer::new();
+    let mut coverage = Coverage::new();
+    coverage
+        .add_template_file(&mut renderer, "invoice", &path)
+        .unwrap();
+
+    for context in [
+        json!({"name": "Ada", "overdue": true, "items": []}),
+        json!({"name": "Bob", "overdue": false, "total": 10}),
+    ] {
+        coverage
+            .render_template(&renderer, "invoice", &context)
+            .unwrap();
+    }
+
+    assert_eq!(coverage.renders(), 2);
+    let files = coverage.files();
+    let uncovered: Vec<_> = files[0]
+        .uncovered()
+        .map(|region| (region.kind.clone(), region.line, region.column))
+        .collect();
+    assert_eq!(
+        uncovered,
+        vec![
+            (RegionKind::Branch, 2, 25),
+            (RegionKind::Loop, 3, 1),
+            (RegionKind::Placeholder, 3, 26),
+        ]
+    );
+    let hits: Vec<_> = files[0].regions.iter().map(|region| region.hits).collect();
+    assert_eq!(hits, vec![2, 1, 0, 1, 0, 0]);
+}
+
+#[test]
+fn given_an_included_template_and_a_plural_message_it_reports_each_template_file() {
+    let directory = tempfile::tempdir().unwrap();
+    let page = directory.path().join("page.txt");
+    let footer = directory.path().join("footer.txt");
+    fs::write(
+        &page,
+        "{count, plural, one {One item} other {# items}}\n{% include \"footer\" %}",
+    )
+    .unwrap();
+    fs::write(&footer, "{% if signed %}-- ${sender}{% endif %}").unwrap();
+    let mut renderer = Renderer::new();
+    let mut coverage = Coverage::new();
+    let includes = coverage
+        .add_template_file(&mut renderer, "page", &page)
+        .unwrap();
+    assert_eq!(includes, vec!["footer"]);
+    coverage
+        .add_template_file(&mut renderer, "footer", &footer)
+        .unwrap();
+
+    coverage
+        .render_template(&renderer, "page", &json!({"count": 3, "signed": false}))
+        .unwrap();
+
+    let report = coverage.to_text();
+    let page = page.display();
+    let footer = footer.display();
+    assert_eq!(
+        report,
+        format!(
+            "Coverage of 1 renders\n\
+             {footer}: 0 of 2 regions covered (0%)\n  \
+             {footer}:1:1: branch never rendered: {{% if signed %}}-- ${{sender}}\n  \
+             {footer}:1:19: placeholder never rendered: ${{sender}}\n\
+             {page}: 2 of 3 regions covered (66%)\n  \
+             {page}:1:1: case `one` never rendered: {{count, plural, one {{One item}} other {{# ...\n"
+        )
+    );
+    let json = coverage.to_json();
+    assert_eq!(json["files"][1]["covered"], 2);
+    assert_eq!(
+        json["files"][0]["uncovered"][1],
+        json!({
+            "kind": "placeholder",
+            "line": 1,
+            "column": 19,
+            "span": {"start": 18, "end": 27},
+            "excerpt": "${sender}",
+        })
+    );
+}
+
+#[test]
+fn given_a_mustache_template_it_reports_its_sections_and_variables() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("list.mustache");
+    fs::write(
+        &path,
+        "{{#items}}{{name}}{{/items}}{{^items}}None{{/items}}",
+    )
+    .unwrap();
+    let mut renderer = Renderer::new();
+    let mut coverage = Coverage::new();
+    coverage
+        .add_template_file(&mut renderer, "list", &path)
+        .unwrap();
+
+    coverage
+        .render_template(&renderer, "list", &json!({"items": []}))
+        .unwrap();
+
+    let files = coverage.files();
+    let hits: Vec<_> = files[0]
+        .regions
+        .iter()
+        .map(|region| (region.kind.clone(), region.hits))
+        .collect();
+    assert_eq!(
+        hits,
+        vec![
+            (RegionKind::Section, 0),
+            (RegionKind::Placeholder, 0),
+            (RegionKind::Section, 1),
+        ]
+    );
+}
+
+#[test]
+fn given_an_expanded_value_it_does_not_count_its_placeholders_in_the_template_file() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("link.txt");
+    // `${name}` spans 21..28 both in the template and in the value of `url`.
+    fs::write(&path, "${url}\n{% if admin %}${name}{% endif %}").unwrap();
+    let mut renderer = Renderer::new();
+    renderer.set_recursive_expansion(true);
+    let mut coverage = Coverage::new();
+    coverage
+        .add_template_file(&mut renderer, "link", &path)
+        .unwrap();
+
+    let url = format!("{}${{name}}", "a".repeat(21));
+    coverage
+        .render_template(&renderer, "link", &json!({"url": url, "name": "Ada"}))
+        .unwrap();
+
+    let hits: Vec<_> = coverage.files()[0]
+        .regions
+        .iter()
+        .map(|region| region.hits)
+        .collect();
+    assert_eq!(hits, vec![1, 0, 0]);
+}
+
+#[test]
+fn given_a_message_keyed_like_a_template_file_it_does_not_count_it_in_the_file() {
+    let directory = tempfile::tempdir().unwrap();
+    let page = directory.path().join("page.txt");
+    let footer = directory.path().join("footer.txt");
+    fs::write(&page, "{% message \"footer\" %}").unwrap();
+    fs::write(&footer, "${sender}").unwrap();
+    let mut renderer = Renderer::new();
+    let messages = HashMap::from([("footer".to_string(), "${sender}".to_string())]);
+    renderer.add_catalog("en", &messages).unwrap();
+    let mut coverage = Coverage::new();
+    for (name, path) in [("page", &page), ("footer", &footer)] {
+        coverage
+            .add_template_file(&mut renderer, name, path)
+            .unwrap();
+    }
+
+    let parsed_template = coverage
+        .render_template(&renderer, "page", &json!({"sender": "Ada"}))
+        .unwrap();
+
+    assert_eq!(parsed_template.text, "Ada");
+    let footer = coverage.files()[0];
+    assert_eq!(
+        (footer.name.as_str(), footer.regions[0].hits),
+        ("footer", 0)
+    );
+}
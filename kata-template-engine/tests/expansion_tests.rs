@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use kata_template_engine::{parse_template, Limits, Renderer, Template};
+use serde_json::json;
+
+fn expanding() -> Renderer {
+    let mut renderer = Renderer::new();
+    renderer.set_recursive_expansion(true);
+    renderer
+}
+
+#[test]
+fn given_a_value_with_placeholders_it_expands_them_against_the_same_context() {
+    let template = Template::parse("Go to ${site.url}").unwrap();
+    let context = json!({
+        "host": "example.com",
+        "port": 8080,
+        "site": {"base": "https://${host}:${port}", "url": "${site.base}/home"},
+    });
+    let parsed_template = expanding().render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "Go to https://example.com:8080/home");
+    assert!(parsed_template.warnings.is_empty());
+}
+
+#[test]
+fn given_values_referencing_each_other_it_raises_an_error_naming_the_cycle() {
+    let template = Template::parse("${start}").unwrap();
+    let context = json!({"start": "${a}", "a": "x${b}", "b": "y${a}"});
+    let error = expanding().render(&template, &context).unwrap_err();
+    assert_eq!(error.to_string(), "Reference cycle a -> b -> a");
+}
+
+#[test]
+fn given_expansions_deeper_than_the_limit_it_raises_an_error() {
+    let mut renderer = expanding();
+    renderer.set_limits(Limits {
+        max_expansion_depth: Some(2),
+        ..Limits::default()
+    });
+    let template = Template::parse("${a}").unwrap();
+    let context = json!({"a": "${b}", "b": "${c}", "c": "${d}", "d": "end"});
+    let error = renderer.render(&template, &context).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expansions exceed the limit of 2 nested values"
+    );
+    let context = json!({"a": "${b}", "b": "${c}", "c": "end"});
+    assert_eq!(renderer.render(&template, &context).unwrap().text, "end");
+}
+
+#[test]
+fn given_an_expanded_value_it_escapes_the_whole_value_once() {
+    let template = Template::parse("${link | escape(\"html\")}").unwrap();
+    let context = json!({"name": "a&b", "link": "<${name}>"});
+    let parsed_template = expanding().render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "&lt;a&amp;b&gt;");
+}
+
+#[test]
+fn given_no_expansion_it_writes_values_as_they_are() {
+    let template = Template::parse("${url}").unwrap();
+    let context = json!({"host": "example.com", "url": "https://${host}"});
+    let parsed_template = Renderer::new().render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "https://${host}");
+}
+
+#[test]
+fn given_a_value_with_a_placeholder_parse_template_never_expands_it() {
+    let variables = HashMap::from([
+        ("first".to_string(), "${second}".to_string()),
+        ("second".to_string(), "2".to_string()),
+    ]);
+    let parsed_template = parse_template("${first} ${second}", variables).unwrap();
+    assert_eq!(parsed_template.text, "${second} 2");
+}
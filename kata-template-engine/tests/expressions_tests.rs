@@ -0,0 +1,161 @@
+use kata_template_engine::{Renderer, Template};
+use serde_json::{json, Value};
+
+fn render(text: &str, context: Value) -> String {
+    let template = Template::parse(text).unwrap();
+    Renderer::new().render(&template, &context).unwrap().text
+}
+
+fn render_error(text: &str, context: Value) -> String {
+    let template = Template::parse(text).unwrap();
+    Renderer::new()
+        .render(&template, &context)
+        .unwrap_err()
+        .to_string()
+}
+
+#[test]
+fn given_an_arithmetic_expression_it_renders_its_result() {
+    let context = json!({"price": 12, "quantity": 3});
+    assert_eq!(render("${price * quantity}", context.clone()), "36");
+    assert_eq!(render("${price + quantity * 2}", context.clone()), "18");
+    assert_eq!(render("${(price + quantity) * 2}", context.clone()), "30");
+    assert_eq!(render("${price / 8}", context.clone()), "1.5");
+    assert_eq!(render("${-price % 5}", context), "-2");
+}
+
+#[test]
+fn given_a_concatenation_it_renders_the_joined_text() {
+    let context = json!({"a": "Hello", "b": "John", "n": 3});
+    assert_eq!(render(r#"${a ~ " " ~ b ~ n}"#, context), "Hello John3");
+}
+
+#[test]
+fn given_a_dotted_path_it_renders_the_nested_value() {
+    let context = json!({"user": {"name": "John", "tags": ["admin", "dev"]}});
+    assert_eq!(
+        render("${user.name} is ${user.tags.0}", context),
+        "John is admin"
+    );
+}
+
+#[test]
+fn given_an_if_block_it_renders_the_first_branch_that_holds() {
+    let text = "{% if total > 100 and not vip %}fee{% elif vip %}vip{% else %}free{% endif %}";
+    assert_eq!(render(text, json!({"total": 150, "vip": false})), "fee");
+    assert_eq!(render(text, json!({"total": 150, "vip": true})), "vip");
+    assert_eq!(render(text, json!({"total": 50, "vip": false})), "free");
+}
+
+#[test]
+fn given_nested_if_blocks_it_renders_the_inner_branch() {
+    let text = "{% if a %}A{% if b == \"x\" %}X{% endif %}{% endif %}.";
+    assert_eq!(render(text, json!({"a": true, "b": "x"})), "AX.");
+    assert_eq!(render(text, json!({"a": [], "b": "x"})), ".");
+}
+
+#[test]
+fn given_a_missing_variable_in_a_condition_it_is_taken_as_null_and_warns() {
+    let template = Template::parse("{% if not vip %}regular{% endif %}").unwrap();
+    let parsed_template = Renderer::new().render(&template, &json!({})).unwrap();
+    assert_eq!(parsed_template.text, "regular");
+    assert_eq!(parsed_template.warnings, vec!["Variable vip not defined"]);
+}
+
+#[test]
+fn given_operands_of_the_wrong_type_it_raises_an_error() {
+    assert_eq!(
+        render_error("${name * 2}", json!({"name": "John"})),
+        "Operator * cannot be applied to string and integer at 2..10"
+    );
+    assert_eq!(
+        render_error("{% if total > \"100\" %}{% endif %}", json!({"total": 1})),
+        "Operator > cannot be applied to integer and string at 6..19"
+    );
+}
+
+#[test]
+fn given_a_division_by_zero_it_raises_an_error() {
+    assert_eq!(
+        render_error("${total / count}", json!({"total": 1, "count": 0})),
+        "Division by zero at 2..15"
+    );
+}
+
+#[test]
+fn given_integers_overflowing_a_division_it_falls_back_to_floats() {
+    let context = json!({"a": i64::MIN});
+    assert_eq!(render("${a / -1}", context.clone()), "9.223372036854776e18");
+    assert_eq!(render("${a % -1}", context), "0");
+}
+
+#[test]
+fn given_an_unclosed_if_block_it_raises_a_syntax_error() {
+    let error = Template::parse("{% if vip %}vip").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 0..12: unclosed `if` block"
+    );
+}
+
+#[test]
+fn given_an_unexpected_tag_it_raises_a_syntax_error() {
+    let error = Template::parse("vip{% endif %}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 3..14: unexpected `endif` tag"
+    );
+}
+
+#[test]
+fn given_an_incomplete_expression_it_raises_a_syntax_error() {
+    let error = Template::parse("${price *}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 9..9: unexpected end of expression"
+    );
+}
+
+#[test]
+fn given_several_syntax_errors_it_reports_them_all_at_once() {
+    let error = Template::parse(
+        "${price *} {% for in items %}${item.}{% endfor %}{% unknown %}{% if %}${ok}",
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "6 errors:\n\
+         Syntax error at 9..9: unexpected end of expression\n\
+         Syntax error at 11..29: expected `for variable in expression`\n\
+         Syntax error at 35..36: unexpected character `.`\n\
+         Syntax error at 49..62: unknown tag `unknown`\n\
+         Syntax error at 62..70: missing condition in `if` tag\n\
+         Syntax error at 62..70: unclosed `if` block"
+    );
+}
+
+#[test]
+fn given_syntax_errors_it_still_parses_the_rest_of_the_template() {
+    let (template, error) =
+        Template::parse_with_recovery("Hi ${name}{% if %}${total +}${vip}{% endif %}");
+    assert_eq!(
+        error.unwrap().to_string(),
+        "2 errors:\n\
+         Syntax error at 10..18: missing condition in `if` tag\n\
+         Syntax error at 27..27: unexpected end of expression"
+    );
+    assert_eq!(template.variables(), vec!["name", "vip"]);
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"name": "Ada"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "Hi Ada");
+}
+
+#[test]
+fn given_a_template_it_lists_the_variables_read_from_the_context() {
+    let template = Template::parse(
+        "${user.name} {% if len(items) > limit %}{% for item in items %}${item.price * rate}{% endfor %}{% endif %}",
+    )
+    .unwrap();
+    assert_eq!(template.variables(), vec!["items", "limit", "rate", "user"]);
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use kata_template_engine::{Template, MAX_EXTRACTION_STEPS};
+
+#[test]
+fn given_a_rendered_text_it_extracts_the_values_of_the_variables() {
+    let template = Template::parse("Dear ${name},\nyour order ${order} has shipped.").unwrap();
+    let values = template
+        .extract("Dear John Smith,\nyour order A-42 has shipped.")
+        .unwrap();
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["name"], "John Smith");
+    assert_eq!(values["order"], "A-42");
+}
+
+#[test]
+fn given_a_variable_used_twice_it_extracts_it_when_both_values_agree() {
+    let template = Template::parse("${name} and ${name}").unwrap();
+    assert_eq!(template.extract("John and John").unwrap()["name"], "John");
+    assert_eq!(
+        template.extract("John and Jane").unwrap_err().to_string(),
+        "Text does not match the template"
+    );
+}
+
+#[test]
+fn given_a_text_that_does_not_match_the_literals_it_raises_an_error() {
+    let template = Template::parse("Hello, ${name}!").unwrap();
+    let error = template.extract("Goodbye, John!").unwrap_err();
+    assert_eq!(error.to_string(), "Text does not match the template");
+}
+
+#[test]
+fn given_patterns_it_constrains_the_captured_values() {
+    let template = Template::parse("${code}${id} items").unwrap();
+    let patterns = HashMap::from([
+        ("code".to_string(), "[A-Z]+".to_string()),
+        ("id".to_string(), r"\d+".to_string()),
+    ]);
+    let values = template
+        .extract_with_patterns("ABC123 items", &patterns)
+        .unwrap();
+    assert_eq!(values["code"], "ABC");
+    assert_eq!(values["id"], "123");
+}
+
+#[test]
+fn given_an_invalid_pattern_it_raises_an_error() {
+    let template = Template::parse("${id}").unwrap();
+    let patterns = HashMap::from([("id".to_string(), "(".to_string())]);
+    let error = template.extract_with_patterns("1", &patterns).unwrap_err();
+    assert!(error
+        .to_string()
+        .starts_with("Invalid pattern for variable id: "));
+}
+
+#[test]
+fn given_a_template_with_blocks_it_raises_an_error() {
+    let template = Template::parse("{% if vip %}VIP{% endif %}${name}").unwrap();
+    let error = template.extract("VIPJohn").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot extract values through the block at 0..26"
+    );
+}
+
+#[test]
+fn given_a_repeated_variable_it_tries_longer_values_until_every_place_agrees() {
+    let template = Template::parse("${a}-${a}").unwrap();
+    assert_eq!(template.extract("x-y-x-y").unwrap()["a"], "x-y");
+
+    let template = Template::parse("${a}/${b}:${a}").unwrap();
+    let patterns = HashMap::from([("b".to_string(), r"\d+".to_string())]);
+    let values = template
+        .extract_with_patterns("x/y/12:x/y", &patterns)
+        .unwrap();
+    assert_eq!((values["a"].as_str(), values["b"].as_str()), ("x/y", "12"));
+    assert_eq!(
+        template
+            .extract_with_patterns("x/y/12:x/z", &patterns)
+            .unwrap_err()
+            .to_string(),
+        "Text does not match the template"
+    );
+}
+
+#[test]
+fn given_repeated_variables_and_a_long_text_it_finishes_within_the_step_limit() {
+    let template = Template::parse("${a}${b}${c}${d}${a}!").unwrap();
+    let text = "x".repeat(3_000);
+    let values = template.extract(&format!("{}!", text)).unwrap();
+    assert_eq!((values["a"].as_str(), values["d"].len()), ("", 3_000));
+    let error = template.extract(&text).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Extraction exceeds the limit of {} tries, the template repeats too many variables",
+            MAX_EXTRACTION_STEPS
+        )
+    );
+}
+
+#[test]
+fn given_a_placeholder_transforming_its_value_it_raises_an_error() {
+    for text in [
+        "Total: ${price:.2}",
+        "Total: ${price | upper}",
+        "Total: ${price | escape(\"html\")}",
+        "---\nescape: html\n---\nTotal: ${price}",
+    ] {
+        let template = Template::parse(text).unwrap();
+        let error = template.extract("Total: 3").unwrap_err().to_string();
+        assert!(
+            error.starts_with("Cannot extract values through the placeholder at "),
+            "{}",
+            error
+        );
+    }
+    let template = Template::parse("${price | escape(\"none\")} in ${now(\"%Y\")}").unwrap();
+    assert_eq!(template.extract("3 in 2026").unwrap()["price"], "3");
+}
@@ -0,0 +1,138 @@
+use kata_template_engine::{ArgType, Function, Renderer, Template};
+use serde_json::{json, Value};
+
+fn render_in(locale: &str, text: &str, context: Value) -> String {
+    let mut renderer = Renderer::new();
+    renderer.set_locale(locale).unwrap();
+    let template = Template::parse(text).unwrap();
+    renderer.render(&template, &context).unwrap().text
+}
+
+#[test]
+fn given_a_currency_filter_it_formats_the_amount_as_the_locale_does() {
+    let text = r#"${total | currency("EUR")} / ${fee | currency("JPY")}"#;
+    let context = json!({"total": 1234.5, "fee": 1500});
+    assert_eq!(
+        render_in("en-US", text, context.clone()),
+        "€1,234.50 / ¥1,500"
+    );
+    assert_eq!(
+        render_in("de-DE", text, context.clone()),
+        "1.234,50\u{a0}€ / 1.500\u{a0}¥"
+    );
+    assert_eq!(
+        render_in("fr", text, context),
+        "1\u{202f}234,50\u{a0}€ / 1\u{202f}500\u{a0}¥"
+    );
+}
+
+#[test]
+fn given_number_and_percent_filters_it_uses_the_separators_of_the_locale() {
+    let text = "${amount | number} ${(ratio * 2) | percent} ${(-1234567.891) | number}";
+    let context = json!({"amount": 9876.5, "ratio": 0.1234});
+    assert_eq!(
+        render_in("en", text, context.clone()),
+        "9,876.5 25% -1,234,567.891"
+    );
+    assert_eq!(
+        render_in("es", text, context),
+        "9876,5 25\u{a0}% -1.234.567,891"
+    );
+}
+
+#[test]
+fn given_a_date_filter_it_writes_the_iso_date_with_the_month_names_of_the_locale() {
+    let text = r#"${created_at | date("long")} (${created_at | date("short")})"#;
+    let context = json!({"created_at": "2024-03-05T17:45:00+01:00"});
+    assert_eq!(
+        render_in("en", text, context.clone()),
+        "March 5, 2024 (3/5/24)"
+    );
+    assert_eq!(
+        render_in("de", text, context.clone()),
+        "5. März 2024 (05.03.24)"
+    );
+    assert_eq!(
+        render_in("ru", text, context),
+        "5 марта 2024\u{a0}г. (05.03.2024)"
+    );
+}
+
+#[test]
+fn given_a_value_that_cannot_be_formatted_it_raises_an_error() {
+    let renderer = Renderer::new();
+    let template = Template::parse(r#"${due | date("long")}"#).unwrap();
+    let error = renderer
+        .render(&template, &json!({"due": "next monday"}))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function date failed at 2..20: next monday is not an ISO-8601 date"
+    );
+}
+
+#[test]
+fn given_a_locale_with_plural_rules_but_no_formatting_rules_it_refuses_it() {
+    let mut renderer = Renderer::new();
+    renderer.set_locale("de").unwrap();
+    assert_eq!(
+        renderer.set_locale("he").unwrap_err().to_string(),
+        "Unknown locale he"
+    );
+    let template = Template::parse("${total | number}").unwrap();
+    let parsed_template = renderer
+        .render(&template, &json!({"total": 1234.5}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "1.234,5");
+}
+
+#[test]
+fn given_a_registered_function_it_is_also_usable_as_a_filter_and_takes_precedence() {
+    struct Shout;
+    impl Function for Shout {
+        fn params(&self) -> &[ArgType] {
+            &[ArgType::Any]
+        }
+        fn call(&self, args: &[Value]) -> Result<Value, String> {
+            Ok(Value::String(format!("{}!", args[0])))
+        }
+    }
+    let mut renderer = Renderer::new();
+    renderer.register_function("number", Shout);
+    let template = Template::parse("${total | number} ${items | len}").unwrap();
+    let parsed_template = renderer
+        .render(&template, &json!({"total": 3, "items": [1, 2]}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "3! 2");
+}
+
+#[test]
+fn given_format_specifications_it_renders_column_aligned_rows() {
+    let template = Template::parse(
+        "{% for line in lines %}${line.id:>4} ${line.name:.<10} ${line.price:8.2} ${line.code:05}\n{% endfor %}",
+    )
+    .unwrap();
+    let context = json!({"lines": [
+        {"id": 7, "name": "Coffee", "price": 3.5, "code": 42},
+        {"id": 12, "name": "Chocolate cake", "price": 12, "code": -7},
+    ]});
+    let parsed_template = Renderer::new().render(&template, &context).unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "   7 Coffee....     3.50 00042\n  12 Chocolate cake    12.00 -0007\n"
+    );
+}
+
+#[test]
+fn given_an_invalid_format_specification_it_fails_when_parsing() {
+    let error = Template::parse("Total: ${total:>8q}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 15..18: invalid format specification `>8q`: unexpected `q`"
+    );
+    let template = Template::parse(r#"${":" ~ total:+}"#).unwrap();
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"total": 5}))
+        .unwrap();
+    assert_eq!(parsed_template.text, ":5");
+}
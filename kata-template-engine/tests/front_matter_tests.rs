@@ -0,0 +1,195 @@
+use kata_template_engine::{Escape, Escaper, FrontMatter, Renderer, Template};
+use serde_json::json;
+
+const REMINDER: &str = "---
+description: Reminder sent when an invoice is overdue
+required: [customer.name]
+defaults:
+  currency: EUR
+  customer:
+    title: customer
+metadata:
+  owner: billing
+---
+Dear ${customer.title} ${customer.name}, you owe ${total} ${currency}.";
+
+#[test]
+fn given_a_front_matter_it_is_not_rendered_and_its_defaults_fill_missing_variables() {
+    let template = Template::parse(REMINDER).unwrap();
+    let parsed_template = Renderer::new()
+        .render(
+            &template,
+            &json!({"customer": {"name": "Doe"}, "total": 10, "currency": "USD"}),
+        )
+        .unwrap();
+    assert_eq!(parsed_template.text, "Dear customer Doe, you owe 10 USD.");
+    assert!(parsed_template.warnings.is_empty());
+}
+
+#[test]
+fn given_a_required_variable_missing_from_the_context_it_raises_an_error() {
+    let template = Template::parse(REMINDER).unwrap();
+    let error = Renderer::new()
+        .render(&template, &json!({"customer": {}, "total": 10}))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Required variable customer.name is not defined"
+    );
+}
+
+#[test]
+fn given_html_escaping_it_escapes_the_values_of_the_template_but_not_its_text() {
+    let mut renderer = Renderer::new();
+    renderer.add_template("signature", "${author}").unwrap();
+    renderer
+        .add_template(
+            "page",
+            "---\nescape: html\n---\n<p>${comment}</p>{% include \"signature\" %}",
+        )
+        .unwrap();
+    let parsed_template = renderer
+        .render_template(
+            "page",
+            &json!({"comment": "<script>\"x\" & 'y'</script>", "author": "<b>Jo</b>"}),
+        )
+        .unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "<p>&lt;script&gt;&quot;x&quot; &amp; &#39;y&#39;&lt;/script&gt;</p><b>Jo</b>"
+    );
+}
+
+#[test]
+fn given_registered_templates_it_lists_them_with_their_front_matter() {
+    let mut renderer = Renderer::new();
+    renderer.add_template("reminder", REMINDER).unwrap();
+    renderer.add_template("blank", "Hello").unwrap();
+    let listed: Vec<_> = renderer
+        .templates()
+        .into_iter()
+        .map(|(name, template)| {
+            let front_matter = template.front_matter();
+            (
+                name,
+                front_matter.description.clone(),
+                front_matter.escape.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        listed,
+        vec![
+            ("blank", None, Escape::None),
+            (
+                "reminder",
+                Some("Reminder sent when an invoice is overdue".to_string()),
+                Escape::None
+            ),
+        ]
+    );
+    let reminder = renderer.templates()[1].1;
+    assert_eq!(reminder.front_matter().metadata["owner"], "billing");
+}
+
+#[test]
+fn given_a_template_starting_with_an_escaped_marker_it_renders_it_without_front_matter() {
+    let render = |text: &str, context| {
+        let template = Template::parse(text).unwrap();
+        assert_eq!(template.front_matter(), &FrontMatter::default());
+        Renderer::new().render(&template, &context).unwrap().text
+    };
+    let manifest = "\\---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: ${name}\n";
+    assert_eq!(
+        render(manifest, json!({"name": "web"})),
+        "---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: web\n"
+    );
+    let documents = "\\---\r\nname: ${name}\r\n---\r\nname: ${name}-backup\r\n";
+    assert_eq!(
+        render(documents, json!({"name": "db"})),
+        "---\r\nname: db\r\n---\r\nname: db-backup\r\n"
+    );
+    let error = Template::parse("\\---\nname: ${name\n").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 11..18: unclosed placeholder"
+    );
+}
+
+#[test]
+fn given_an_invalid_front_matter_it_raises_an_error() {
+    let unclosed = Template::parse("---\ndescription: x\nHello").unwrap_err();
+    assert_eq!(
+        unclosed.to_string(),
+        "Invalid front matter: missing closing `---` line"
+    );
+    let unknown = Template::parse("---\nrequried: [name]\n---\nHello").unwrap_err();
+    assert!(unknown
+        .to_string()
+        .starts_with("Invalid front matter: unknown field `requried`"));
+}
+
+#[test]
+fn given_an_escape_in_the_front_matter_it_quotes_values_for_that_language() {
+    let context = json!({"name": "O'Brien, \"Pat\"", "dir": "my files"});
+    let render = |text: &str| {
+        let template = Template::parse(text).unwrap();
+        Renderer::new().render(&template, &context).unwrap().text
+    };
+    assert_eq!(
+        render("---\nescape: sql\n---\nINSERT INTO users VALUES (${name});"),
+        "INSERT INTO users VALUES ('O''Brien, \"Pat\"');"
+    );
+    assert_eq!(
+        render("---\nescape: csv\n---\n${name},${dir}"),
+        "\"O'Brien, \"\"Pat\"\"\",my files"
+    );
+    assert_eq!(
+        render("---\nescape: shell\n---\ncd ${dir} && echo ${name}"),
+        "cd 'my files' && echo 'O'\\''Brien, \"Pat\"'"
+    );
+}
+
+#[test]
+fn given_an_escape_filter_it_replaces_the_escape_of_the_template_for_that_placeholder() {
+    let template = Template::parse(
+        "---\nescape: html\n---\n<a href=\"/search?q=${query | escape(\"url\")}\">${query}</a>\n\
+         <script>let q = \"${query | escape(\"json\")}\";</script>",
+    )
+    .unwrap();
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"query": "a&b \"c\""}))
+        .unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "<a href=\"/search?q=a%26b%20%22c%22\">a&amp;b &quot;c&quot;</a>\n\
+         <script>let q = \"a&b \\\"c\\\"\";</script>"
+    );
+    let error = Template::parse("${query | escape(kind)}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 2..22: the escape filter expects the name of an escaper as a string literal"
+    );
+}
+
+#[test]
+fn given_a_registered_escaper_templates_can_choose_it_by_name() {
+    struct LatexEscaper;
+    impl Escaper for LatexEscaper {
+        fn escape(&self, text: &str) -> String {
+            text.replace('%', "\\%").replace('&', "\\&")
+        }
+    }
+    let mut renderer = Renderer::new();
+    let template = Template::parse("---\nescape: latex\n---\n${rate} ${name}").unwrap();
+    let context = json!({"rate": "5%", "name": "Smith & Co"});
+    let error = renderer.render(&template, &context).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown escaper latex at 22..29");
+    renderer.register_escaper("latex", LatexEscaper);
+    let parsed_template = renderer.render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "5\\% Smith \\& Co");
+    assert_eq!(
+        template.front_matter().escape,
+        Escape::Custom("latex".to_string())
+    );
+}
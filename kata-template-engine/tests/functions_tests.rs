@@ -0,0 +1,124 @@
+use kata_template_engine::{ArgType, Function, Renderer, Template};
+use serde_json::{json, Value};
+
+fn render(text: &str, context: Value) -> String {
+    let template = Template::parse(text).unwrap();
+    Renderer::new().render(&template, &context).unwrap().text
+}
+
+#[test]
+fn given_a_call_to_now_it_renders_the_current_date() {
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    assert_eq!(
+        render(r#"Today is ${now("%Y-%m-%d")}"#, json!({})),
+        format!("Today is {}", today)
+    );
+}
+
+#[test]
+fn given_a_call_to_len_it_renders_the_length_of_a_variable() {
+    let context = json!({"items": ["a", "b", "c"], "name": "John"});
+    assert_eq!(
+        render("${len(items)} items, ${len(name)} letters", context),
+        "3 items, 4 letters"
+    );
+}
+
+#[test]
+fn given_a_renderer_with_env_enabled_it_renders_the_environment_variable() {
+    let path = std::env::var("PATH").unwrap();
+    let template = Template::parse(r#"${env("PATH")}"#).unwrap();
+    let mut renderer = Renderer::new();
+    renderer.enable_env();
+    assert_eq!(renderer.render(&template, &json!({})).unwrap().text, path);
+}
+
+#[test]
+fn given_a_new_renderer_it_does_not_know_env() {
+    let template = Template::parse(r#"${env("PATH")}"#).unwrap();
+    let error = Renderer::new().render(&template, &json!({})).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown function env at 2..13");
+}
+
+#[test]
+fn given_a_call_to_range_it_renders_the_integers_in_the_range() {
+    assert_eq!(render("${range(1, 5)}", json!({})), "[1,2,3,4]");
+}
+
+#[test]
+fn given_a_registered_function_it_can_be_called_from_a_placeholder() {
+    struct Upper;
+    impl Function for Upper {
+        fn params(&self) -> &[ArgType] {
+            &[ArgType::String]
+        }
+        fn call(&self, args: &[Value]) -> Result<Value, String> {
+            Ok(Value::from(args[0].as_str().unwrap().to_uppercase()))
+        }
+    }
+    let mut renderer = Renderer::new();
+    renderer.register_function("upper", Upper);
+    let template = Template::parse("${greet}, ${upper(name)}").unwrap();
+    let parsed_template = renderer
+        .render(&template, &json!({"greet": "Hello", "name": "John"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "Hello, JOHN");
+}
+
+#[test]
+fn given_a_call_with_a_missing_variable_it_leaves_the_placeholder_and_warns() {
+    let template = Template::parse("Hello, ${len(name)}").unwrap();
+    let parsed_template = Renderer::new().render(&template, &json!({})).unwrap();
+    assert_eq!(parsed_template.text, "Hello, ${len(name)}");
+    assert_eq!(parsed_template.warnings, vec!["Variable name not replaced"]);
+}
+
+#[test]
+fn given_a_call_to_an_unknown_function_it_raises_an_error() {
+    let template = Template::parse("Hello, ${upper(name)}").unwrap();
+    let error = Renderer::new()
+        .render(&template, &json!({"name": "John"}))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Unknown function upper at 9..20");
+}
+
+#[test]
+fn given_a_call_with_the_wrong_number_of_arguments_it_raises_an_error() {
+    let template = Template::parse("${range(1)}").unwrap();
+    let error = Renderer::new().render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function range expects 2 arguments but got 1 at 2..10"
+    );
+}
+
+#[test]
+fn given_a_call_with_an_argument_of_the_wrong_type_it_raises_an_error() {
+    let template = Template::parse(r#"${range(1, "5")}"#).unwrap();
+    let error = Renderer::new().render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function range expects an argument of type integer but got string at 11..14"
+    );
+}
+
+#[test]
+fn given_a_failing_function_it_raises_an_error() {
+    let template = Template::parse(r#"${env("KATA_TEMPLATE_ENGINE_UNSET")}"#).unwrap();
+    let mut renderer = Renderer::new();
+    renderer.enable_env();
+    let error = renderer.render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function env failed at 2..35: environment variable KATA_TEMPLATE_ENGINE_UNSET is not set"
+    );
+}
+
+#[test]
+fn given_an_unclosed_placeholder_it_raises_a_syntax_error() {
+    let error = Template::parse("Hello, ${name").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 7..13: unclosed placeholder"
+    );
+}
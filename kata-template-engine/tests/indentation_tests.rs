@@ -0,0 +1,44 @@
+use kata_template_engine::{Renderer, Template};
+use serde_json::json;
+
+#[test]
+fn given_an_indent_filter_it_indents_the_lines_of_the_value_to_the_column_of_the_placeholder() {
+    let template = Template::parse("def main():\n    ${body | indent}\n").unwrap();
+    let context = json!({"body": "x = 1\n\nif x:\n    print(x)"});
+    let parsed_template = Renderer::new().render(&template, &context).unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "def main():\n    x = 1\n\n    if x:\n        print(x)\n"
+    );
+}
+
+#[test]
+fn given_a_placeholder_after_text_it_indents_with_spaces_up_to_its_column() {
+    let template = Template::parse("key: ${value | indent | escape(\"none\")}\n").unwrap();
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"value": "|\n  a\n  b"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "key: |\n       a\n       b\n");
+}
+
+#[test]
+fn given_value_indentation_it_indents_every_placeholder_with_the_line_endings_of_the_template() {
+    let mut renderer = Renderer::new();
+    renderer.set_value_indentation(true);
+    let template = Template::parse("items:\r\n\t${first}\r\n\t${second}").unwrap();
+    let context = json!({"first": "a\nb", "second": "c\r\nd\n"});
+    let parsed_template = renderer.render(&template, &context).unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "items:\r\n\ta\r\n\tb\r\n\tc\r\n\td\r\n"
+    );
+}
+
+#[test]
+fn given_no_indentation_it_writes_the_lines_of_the_value_as_they_are() {
+    let template = Template::parse("  ${body}").unwrap();
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"body": "a\r\nb"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "  a\r\nb");
+}
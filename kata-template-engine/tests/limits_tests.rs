@@ -0,0 +1,161 @@
+use kata_template_engine::{Limits, Renderer, Template, MAX_NESTING_DEPTH};
+use serde_json::json;
+
+fn sandboxed(limits: Limits) -> Renderer {
+    let mut renderer = Renderer::new();
+    renderer.set_limits(limits);
+    renderer
+}
+
+#[test]
+fn given_a_for_block_it_renders_the_block_once_per_element() {
+    let template = Template::parse("{% for item in items %}${item.name};{% endfor %}").unwrap();
+    let context = json!({"items": [{"name": "a"}, {"name": "b"}]});
+    let parsed_template = Renderer::new().render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "a;b;");
+}
+
+#[test]
+fn given_an_include_it_renders_the_registered_template_with_the_same_context() {
+    let mut renderer = Renderer::new();
+    renderer.add_template("greeting", "Hello, ${name}").unwrap();
+    renderer
+        .add_template("letter", "{% include \"greeting\" %}!")
+        .unwrap();
+    let parsed_template = renderer
+        .render_template("letter", &json!({"name": "John"}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "Hello, John!");
+}
+
+#[test]
+fn given_an_include_of_an_unknown_template_it_raises_an_error() {
+    let template = Template::parse("{% include \"footer\" %}").unwrap();
+    let error = Renderer::new().render(&template, &json!({})).unwrap_err();
+    assert_eq!(error.to_string(), "Unknown template footer at 0..22");
+}
+
+#[test]
+fn given_a_format_width_or_precision_above_the_maximum_it_raises_a_syntax_error() {
+    for text in ["${x:99999999999}", "${x:.99999999999}", "${x:1025}"] {
+        let error = Template::parse(text).unwrap_err().to_string();
+        assert!(error.contains("is larger than 1024"), "{}: {}", text, error);
+    }
+    let template = Template::parse("${x:>1024.1024}").unwrap();
+    let renderer = sandboxed(Limits {
+        max_output_bytes: Some(100),
+        ..Limits::default()
+    });
+    assert!(renderer.render(&template, &json!({"x": 1.5})).is_err());
+}
+
+#[test]
+fn given_deeply_nested_expressions_or_blocks_it_raises_a_syntax_error_instead_of_overflowing() {
+    let depth = 10_000;
+    let parens = format!("${{{}x{}}}", "(".repeat(depth), ")".repeat(depth));
+    let negations = format!("${{{}x}}", "-".repeat(depth));
+    let ifs = format!(
+        "{}x{}",
+        "{% if a %}".repeat(depth),
+        "{% endif %}".repeat(depth)
+    );
+    let messages = format!(
+        "{}x{}",
+        "{n, select, other {".repeat(depth),
+        "}}".repeat(depth)
+    );
+    for text in [parens, negations, ifs, messages] {
+        let error = Template::parse(&text).unwrap_err().to_string();
+        assert!(error.starts_with("Syntax error at "), "{}", error);
+        assert!(error.contains("nested deeper than 64 levels"), "{}", error);
+    }
+    let sections = format!("{}x{}", "{{#a}}".repeat(depth), "{{/a}}".repeat(depth));
+    let error = Template::parse_mustache(&sections).unwrap_err();
+    assert!(error.to_string().contains("nested deeper than 64 levels"));
+
+    let depth = MAX_NESTING_DEPTH - 1;
+    let text = format!(
+        "{}${{{}1{}}}{}",
+        "{% if true %}".repeat(depth),
+        "(".repeat(depth),
+        ")".repeat(depth),
+        "{% endif %}".repeat(depth)
+    );
+    let template = Template::parse(&text).unwrap();
+    let parsed_template = Renderer::new().render(&template, &json!({})).unwrap();
+    assert_eq!(parsed_template.text, "1");
+}
+
+#[test]
+fn given_an_output_larger_than_the_limit_it_raises_an_error() {
+    let renderer = sandboxed(Limits {
+        max_output_bytes: Some(10),
+        ..Limits::default()
+    });
+    let template = Template::parse("{% for i in range(0, 5) %}abc{% endfor %}").unwrap();
+    let error = renderer.render(&template, &json!({})).unwrap_err();
+    assert_eq!(error.to_string(), "Output exceeds the limit of 10 bytes");
+}
+
+#[test]
+fn given_more_loop_iterations_than_the_limit_it_raises_an_error() {
+    let renderer = sandboxed(Limits {
+        max_loop_iterations: Some(6),
+        ..Limits::default()
+    });
+    let template = Template::parse(
+        "{% for i in range(0, 3) %}{% for j in range(0, 3) %}.{% endfor %}{% endfor %}",
+    )
+    .unwrap();
+    let error = renderer.render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Loops exceed the limit of 6 iterations at 26..65"
+    );
+}
+
+#[test]
+fn given_a_template_including_itself_it_raises_an_error_instead_of_overflowing() {
+    let mut renderer = Renderer::new();
+    renderer
+        .add_template("loop", "x{% include \"loop\" %}")
+        .unwrap();
+    let error = renderer.render_template("loop", &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Includes exceed the limit of 64 nested templates at 1..21"
+    );
+}
+
+#[test]
+fn given_more_steps_than_the_limit_it_raises_an_error() {
+    let renderer = sandboxed(Limits {
+        max_steps: Some(100),
+        ..Limits::default()
+    });
+    let template = Template::parse("{% for i in range(0, 1000) %}${i}{% endfor %}").unwrap();
+    let error = renderer.render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Rendering exceeds the limit of 100 steps"
+    );
+}
+
+#[test]
+fn given_a_huge_range_it_raises_an_error_instead_of_exhausting_memory() {
+    let template = Template::parse("{% for i in range(0, 1000000000000) %}{% endfor %}").unwrap();
+    let error = Renderer::new().render(&template, &json!({})).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function range failed at 12..35: ranges are limited to 1000000 elements"
+    );
+}
+
+#[test]
+fn given_a_loop_over_something_that_is_not_an_array_it_raises_an_error() {
+    let template = Template::parse("{% for c in name %}{% endfor %}").unwrap();
+    let error = Renderer::new()
+        .render(&template, &json!({"name": "John"}))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "Cannot loop over string at 12..16");
+}
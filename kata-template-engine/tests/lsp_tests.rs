@@ -0,0 +1,205 @@
+use std::fs;
+use std::io::Cursor;
+
+use kata_template_engine::lsp::serve;
+use serde_json::{json, Value};
+
+/// Sends `messages` to the server and returns the messages it writes back.
+fn exchange(messages: &[Value]) -> Vec<Value> {
+    let mut input = Vec::new();
+    for message in messages {
+        let body = message.to_string();
+        input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+    }
+    serve_bytes(input)
+}
+
+/// Sends the framed messages of `input` to the server and returns the messages it writes back.
+fn serve_bytes(input: Vec<u8>) -> Vec<Value> {
+    let mut output = Vec::new();
+    serve(&mut Cursor::new(input), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    output
+        .split("Content-Length: ")
+        .filter(|frame| !frame.is_empty())
+        .map(|frame| serde_json::from_str(frame.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+
+fn initialize(root: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 0,
+        "method": "initialize",
+        "params": {
+            "rootUri": format!("file://{}", root),
+            "initializationOptions": {"sample": "sample.json", "schema": "schema.json"},
+        },
+    })
+}
+
+fn open(uri: &str, text: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "languageId": "template", "version": 1, "text": text}},
+    })
+}
+
+fn request(id: u64, method: &str, uri: &str, line: u64, character: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": method,
+        "params": {
+            "textDocument": {"uri": uri},
+            "position": {"line": line, "character": character},
+        },
+    })
+}
+
+#[test]
+fn given_an_invalid_template_it_publishes_the_syntax_error_as_a_diagnostic() {
+    let responses = exchange(&[
+        open("file:///letter.txt", "Dear ${name},\nYou owe ${total +}."),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didChange",
+            "params": {
+                "textDocument": {"uri": "file:///letter.txt", "version": 2},
+                "contentChanges": [{"text": "Dear ${name},\nYou owe ${total}."}],
+            },
+        }),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(
+        responses[0]["params"]["diagnostics"],
+        json!([{
+            "range": {"start": {"line": 1, "character": 17}, "end": {"line": 1, "character": 17}},
+            "severity": 1,
+            "source": "kata-template-engine",
+            "message": "unexpected end of expression",
+        }])
+    );
+    assert_eq!(responses[1]["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn given_several_syntax_errors_it_publishes_a_diagnostic_for_each() {
+    let responses = exchange(&[open("file:///letter.txt", "${total +}\n{% endif %}")]);
+    let messages: Vec<&Value> = responses[0]["params"]["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|diagnostic| &diagnostic["message"])
+        .collect();
+    assert_eq!(
+        messages,
+        vec!["unexpected end of expression", "unexpected `endif` tag"]
+    );
+}
+
+#[test]
+fn given_a_sample_context_and_a_schema_it_completes_variables_and_shows_values_on_hover() {
+    let directory = tempfile::tempdir().unwrap();
+    let root = directory.path().to_str().unwrap();
+    fs::write(
+        directory.path().join("sample.json"),
+        r#"{"customer": {"name": "Ada", "vip": true}, "total": 42}"#,
+    )
+    .unwrap();
+    fs::write(
+        directory.path().join("schema.json"),
+        r#"{"properties": {"customer": {"type": "object", "properties": {"email": {"type": "string"}}}}}"#,
+    )
+    .unwrap();
+    let uri = "file:///invoice.txt";
+    let responses = exchange(&[
+        initialize(root),
+        open(uri, "Hi ${customer.}, you owe ${t"),
+        request(1, "textDocument/completion", uri, 0, 14),
+        request(2, "textDocument/completion", uri, 0, 28),
+        request(3, "textDocument/completion", uri, 0, 2),
+        request(4, "textDocument/hover", uri, 0, 7),
+        json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+    ]);
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["hoverProvider"],
+        true
+    );
+    let labels = |response: &Value| -> Vec<(String, String)> {
+        response["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["label"].as_str().unwrap().to_string(),
+                    item["detail"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(
+        labels(&responses[2]),
+        vec![
+            ("email".to_string(), "string".to_string()),
+            ("name".to_string(), "string".to_string()),
+            ("vip".to_string(), "bool".to_string()),
+        ]
+    );
+    assert_eq!(
+        labels(&responses[3]),
+        vec![("total".to_string(), "integer".to_string())]
+    );
+    assert_eq!(responses[4]["result"], json!([]));
+    assert_eq!(
+        responses[5]["result"]["contents"]["value"],
+        "```json\n{\n  \"name\": \"Ada\",\n  \"vip\": true\n}\n```"
+    );
+    assert_eq!(
+        responses[6],
+        json!({"jsonrpc": "2.0", "id": 5, "result": null})
+    );
+}
+
+#[test]
+fn given_an_include_tag_it_goes_to_the_included_template() {
+    let root = tempfile::tempdir().unwrap();
+    let directory = root.path().join("my letters #1");
+    fs::create_dir(&directory).unwrap();
+    fs::write(directory.join("signature.txt"), "Regards").unwrap();
+    let encoded = format!("file://{}/my%20letters%20%231", root.path().display());
+    let uri = format!("{}/letter.txt", encoded);
+    let responses = exchange(&[
+        open(&uri, "Hello\n{% include \"signature\" %}"),
+        request(1, "textDocument/definition", &uri, 1, 14),
+        request(2, "textDocument/definition", &uri, 0, 2),
+        request(3, "textDocument/formatting", &uri, 0, 0),
+    ]);
+    assert_eq!(
+        responses[1]["result"]["uri"],
+        format!("{}/signature.txt", encoded)
+    );
+    assert_eq!(responses[2]["result"], Value::Null);
+    assert_eq!(
+        responses[3]["error"],
+        json!({"code": -32601, "message": "Unknown method textDocument/formatting"})
+    );
+}
+
+#[test]
+fn given_a_message_that_is_not_json_it_answers_a_parse_error_and_keeps_serving() {
+    let mut input = b"Content-Length: 8\r\n\r\n{\"id\": 1".to_vec();
+    let body = json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}).to_string();
+    input.extend(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).into_bytes());
+    let responses = serve_bytes(input);
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], Value::Null);
+    assert_eq!(responses[0]["error"]["code"], -32700);
+    assert_eq!(
+        responses[1],
+        json!({"jsonrpc": "2.0", "id": 2, "result": null})
+    );
+}
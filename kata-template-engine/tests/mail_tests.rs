@@ -0,0 +1,110 @@
+use chrono::DateTime;
+use kata_template_engine::mail::{render_mail, MailHeaders, MailTemplate, Mailbox};
+use kata_template_engine::{Renderer, Template};
+use serde_json::json;
+
+const INVOICE: &str = "--- subject ---
+Facture n°${number}
+--- text ---
+Dear ${name},
+you owe ${total}.
+--- html ---
+<p>Dear ${name}, you owe <b>${total}</b>.</p>
+";
+
+fn headers() -> MailHeaders {
+    let mut headers = MailHeaders::new(
+        Mailbox::named("Billing, Inc.", "billing@example.com"),
+        vec![
+            Mailbox::named("Zoë", "zoe@example.com"),
+            Mailbox::new("ada@example.com"),
+        ],
+    );
+    headers.date = Some(DateTime::parse_from_rfc3339("2024-03-01T09:30:00+01:00").unwrap());
+    headers
+}
+
+#[test]
+fn given_a_mail_template_it_renders_a_multipart_message() {
+    let template = MailTemplate::parse(INVOICE).unwrap();
+    let context = json!({"number": 42, "name": "Tom & Jerry", "total": "10 €"});
+    let mail = render_mail(&Renderer::new(), &template, &context, &headers()).unwrap();
+    let message = String::from_utf8(mail.message).unwrap();
+    let boundary = message
+        .split("boundary=\"")
+        .nth(1)
+        .unwrap()
+        .split('"')
+        .next()
+        .unwrap();
+    assert_eq!(
+        message,
+        format!(
+            "Date: Fri, 1 Mar 2024 09:30:00 +0100\r\n\
+             From: \"Billing, Inc.\" <billing@example.com>\r\n\
+             To: =?utf-8?q?Zo=C3=AB?= <zoe@example.com>, ada@example.com\r\n\
+             Subject: =?utf-8?q?Facture_n=C2=B042?=\r\n\
+             MIME-Version: 1.0\r\n\
+             Content-Type: multipart/alternative; boundary=\"{0}\"\r\n\
+             \r\n\
+             --{0}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             Content-Transfer-Encoding: quoted-printable\r\n\
+             \r\n\
+             Dear Tom & Jerry,\r\nyou owe 10 =E2=82=AC.\r\n\r\n\
+             --{0}\r\n\
+             Content-Type: text/html; charset=utf-8\r\n\
+             Content-Transfer-Encoding: quoted-printable\r\n\
+             \r\n\
+             <p>Dear Tom &amp; Jerry, you owe <b>10 =E2=82=AC</b>.</p>\r\n\r\n\
+             --{0}--\r\n",
+            boundary
+        )
+    );
+    assert!(mail.warnings.is_empty());
+}
+
+#[test]
+fn given_a_template_pair_it_renders_a_single_part_message() {
+    let template = MailTemplate::from_templates(
+        Template::parse("Hello\n${name}").unwrap(),
+        None,
+        Some(Template::parse("<p>${name}</p>${missing}").unwrap()),
+    )
+    .unwrap();
+    let mail = render_mail(
+        &Renderer::new(),
+        &template,
+        &json!({"name": "<Ada>"}),
+        &headers(),
+    )
+    .unwrap();
+    let message = String::from_utf8(mail.message).unwrap();
+    assert!(message.contains("Subject: Hello <Ada>\r\nMIME-Version: 1.0\r\n"));
+    assert!(message.ends_with(
+        "Content-Type: text/html; charset=utf-8\r\n\
+         Content-Transfer-Encoding: quoted-printable\r\n\
+         \r\n\
+         <p>&lt;Ada&gt;</p>${missing}\r\n"
+    ));
+    assert_eq!(mail.warnings, vec!["Variable missing not replaced"]);
+}
+
+#[test]
+fn given_an_invalid_mail_template_or_header_it_raises_an_error() {
+    let error = MailTemplate::parse("--- text ---\nHi").unwrap_err();
+    assert_eq!(error.to_string(), "Invalid mail: missing subject section");
+    let error = MailTemplate::parse("--- subject ---\nHi\n--- text ---\n${name").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 32..38: unclosed placeholder"
+    );
+    let template = MailTemplate::parse("--- subject ---\nHi\n--- text ---\nHi").unwrap();
+    let mut headers = headers();
+    headers.to = vec![Mailbox::new("ada@example.com\r\nBcc: eve@example.com")];
+    let error = render_mail(&Renderer::new(), &template, &json!({}), &headers).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Invalid mail: invalid address `ada@example.com\r\nBcc: eve@example.com`"
+    );
+}
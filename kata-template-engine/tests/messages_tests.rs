@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use kata_template_engine::{Renderer, Template};
+use serde_json::{json, Value};
+
+const INBOX: &str =
+    "You have {count, plural, =0 {no messages} one {# message} other {# messages}}.";
+
+fn render_with(renderer: &Renderer, text: &str, context: Value) -> String {
+    let template = Template::parse(text).unwrap();
+    renderer.render(&template, &context).unwrap().text
+}
+
+fn render(text: &str, context: Value) -> String {
+    render_with(&Renderer::new(), text, context)
+}
+
+#[test]
+fn given_a_plural_message_it_chooses_the_case_of_the_number() {
+    assert_eq!(render(INBOX, json!({"count": 0})), "You have no messages.");
+    assert_eq!(render(INBOX, json!({"count": 1})), "You have 1 message.");
+    assert_eq!(render(INBOX, json!({"count": "5"})), "You have 5 messages.");
+}
+
+#[test]
+fn given_a_locale_it_uses_its_plural_rules() {
+    let mut renderer = Renderer::new();
+    renderer.set_locale("ru-RU").unwrap();
+    let text = "{n, plural, one {# файл} few {# файла} many {# файлов} other {# файла}}";
+    let rendered: Vec<_> = [1, 3, 5, 21, 112]
+        .into_iter()
+        .map(|n| render_with(&renderer, text, json!({ "n": n })))
+        .collect();
+    assert_eq!(
+        rendered,
+        vec!["1 файл", "3 файла", "5 файлов", "21 файл", "112 файлов"]
+    );
+}
+
+#[test]
+fn given_a_select_message_it_chooses_the_case_of_the_value_or_other() {
+    let text = "{gender, select, female {She} male {He} other {They}} replied \
+                {count, plural, offset:1 =1 {alone} one {with # other} other {with # others}}.";
+    assert_eq!(
+        render(text, json!({"gender": "female", "count": 2})),
+        "She replied with 1 other."
+    );
+    assert_eq!(
+        render(text, json!({"gender": null, "count": 4})),
+        "They replied with 3 others."
+    );
+    assert_eq!(
+        render(text, json!({"gender": "male", "count": 1})),
+        "He replied alone."
+    );
+}
+
+#[test]
+fn given_catalogs_it_renders_the_message_of_the_locale_falling_back_to_the_language() {
+    let mut renderer = Renderer::new();
+    let portuguese = HashMap::from([(
+        "inbox".to_string(),
+        "Você tem {count, plural, one {# mensagem} other {# mensagens}}".to_string(),
+    )]);
+    renderer.add_catalog("pt", &portuguese).unwrap();
+    renderer.set_locale("pt_BR").unwrap();
+    let template = "{% message \"inbox\" %}, ${name}";
+    assert_eq!(
+        render_with(&renderer, template, json!({"count": 0, "name": "Ana"})),
+        "Você tem 0 mensagem, Ana"
+    );
+    renderer.set_locale("en").unwrap();
+    let error = renderer
+        .render(&Template::parse(template).unwrap(), &json!({"count": 0}))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Unknown message inbox for locale en at 0..21"
+    );
+    assert_eq!(
+        renderer.set_locale("tlh").unwrap_err().to_string(),
+        "Unknown locale tlh"
+    );
+}
+
+#[test]
+fn given_a_message_without_an_other_case_it_raises_a_syntax_error() {
+    let error = Template::parse("{count, plural, one {# item}}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 0..29: the message has no `other` case"
+    );
+}
+
+#[test]
+fn given_braces_that_do_not_start_a_message_it_keeps_them_as_text() {
+    assert_eq!(
+        render(
+            r#"{"name": "${name}", "tags": {a, b}}"#,
+            json!({"name": "Jo"})
+        ),
+        r#"{"name": "Jo", "tags": {a, b}}"#
+    );
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::path::Path;
+
+use kata_template_engine::{Renderer, Template};
+use serde_json::{json, Value};
+
+/// Runs the cases of a file of the Mustache spec (https://github.com/mustache/spec), kept in
+/// `tests/fixtures/mustache` in the JSON format of the spec, and returns the failed ones.
+fn run_spec(file: &str) -> Vec<String> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/mustache")
+        .join(file);
+    let spec: Value = serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    let mut failures = Vec::new();
+    for case in spec["tests"].as_array().unwrap() {
+        let name = case["name"].as_str().unwrap();
+        let mut renderer = Renderer::new();
+        if let Some(partials) = case["partials"].as_object() {
+            for (partial, text) in partials {
+                renderer
+                    .add_mustache_template(partial, text.as_str().unwrap())
+                    .unwrap();
+            }
+        }
+        let rendered = Template::parse_mustache(case["template"].as_str().unwrap())
+            .and_then(|template| renderer.render(&template, &case["data"]))
+            .map(|parsed_template| parsed_template.text);
+        match rendered {
+            Ok(text) if text == case["expected"] => {}
+            rendered => failures.push(format!("{}: {:?}", name, rendered)),
+        }
+    }
+    failures
+}
+
+#[test]
+fn given_the_comments_spec_it_passes_every_case() {
+    assert_eq!(run_spec("comments.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_the_delimiters_spec_it_passes_every_case() {
+    assert_eq!(run_spec("delimiters.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_the_interpolation_spec_it_passes_every_case() {
+    assert_eq!(run_spec("interpolation.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_the_inverted_spec_it_passes_every_case() {
+    assert_eq!(run_spec("inverted.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_the_partials_spec_it_passes_every_case() {
+    assert_eq!(run_spec("partials.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_the_sections_spec_it_passes_every_case() {
+    assert_eq!(run_spec("sections.json"), Vec::<String>::new());
+}
+
+#[test]
+fn given_a_missing_variable_it_renders_nothing_but_warns_about_it() {
+    let template =
+        Template::parse_mustache("Hello, {{name}}{{#user}} {{title}}{{/user}}!").unwrap();
+    let parsed_template = Renderer::new()
+        .render(&template, &json!({"user": {"name": "Jo"}}))
+        .unwrap();
+    assert_eq!(parsed_template.text, "Hello,  !");
+    assert_eq!(
+        parsed_template.warnings,
+        vec!["Variable name not replaced", "Variable title not replaced"]
+    );
+    assert_eq!(template.variables(), vec!["name", "user"]);
+}
+
+#[test]
+fn given_a_native_template_it_can_include_a_mustache_template_and_back() {
+    let mut renderer = Renderer::new();
+    renderer
+        .add_mustache_template("item", "{{#item}}<li>{{name}}</li>{{/item}}")
+        .unwrap();
+    renderer
+        .add_template(
+            "list",
+            "{% for item in items %}{% include \"item\" %}{% endfor %}",
+        )
+        .unwrap();
+    let template = Template::parse_mustache("<ul>{{> list}}</ul>").unwrap();
+    let parsed_template = renderer
+        .render(
+            &template,
+            &json!({"items": [{"name": "a&b"}, {"name": "c"}]}),
+        )
+        .unwrap();
+    assert_eq!(parsed_template.text, "<ul><li>a&amp;b</li><li>c</li></ul>");
+}
+
+#[test]
+fn given_a_section_closed_by_another_name_it_raises_a_syntax_error() {
+    let error = Template::parse_mustache("{{#items}}{{name}}{{/item}}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 18..27: unexpected closing tag `item`, expected `items`"
+    );
+    let error = Template::parse_mustache("{{#items}}{{name}}").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 18..18: unclosed section `items`"
+    );
+}
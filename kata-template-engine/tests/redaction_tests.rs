@@ -0,0 +1,126 @@
+use kata_template_engine::{ArgType, Function, Renderer, Span, Template};
+use serde_json::{json, Value};
+
+fn renderer_with_secrets() -> Renderer {
+    let mut renderer = Renderer::new();
+    renderer.mark_sensitive("api_key");
+    renderer.mark_sensitive("users.password");
+    renderer
+}
+
+#[test]
+fn given_a_sensitive_variable_it_renders_it_but_masks_it_in_debug_output() {
+    let template = Template::parse("key=${api_key} user=${user}").unwrap();
+    let context = json!({"api_key": "s3cr3t", "user": "john"});
+    let parsed_template = renderer_with_secrets().render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "key=s3cr3t user=john");
+    assert_eq!(parsed_template.sensitive, vec![Span::new(4, 10)]);
+    let debug = format!("{:?}", parsed_template);
+    assert!(debug.contains("key=[REDACTED] user=john"));
+    assert!(!debug.contains("s3cr3t"));
+}
+
+#[test]
+fn given_a_function_failing_with_a_sensitive_value_it_masks_it_in_the_error() {
+    struct Parse;
+    impl Function for Parse {
+        fn params(&self) -> &[ArgType] {
+            &[ArgType::String]
+        }
+        fn call(&self, args: &[Value]) -> Result<Value, String> {
+            Err(format!(
+                "{} is not a number",
+                args[0].as_str().unwrap_or_default()
+            ))
+        }
+    }
+    let mut renderer = renderer_with_secrets();
+    renderer.register_function("parse", Parse);
+    let template = Template::parse("${parse(api_key)}").unwrap();
+    let error = renderer
+        .render(&template, &json!({"api_key": "s3cr3t"}))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function parse failed at 2..16: [REDACTED] is not a number"
+    );
+}
+
+#[test]
+fn given_a_loop_over_sensitive_entries_it_flags_them_in_the_source_map() {
+    let mut renderer = Renderer::new();
+    renderer.mark_sensitive("users");
+    let template =
+        Template::parse("{% for user in users %}${user.name}:${len(user.password)} {% endfor %}")
+            .unwrap();
+    let context = json!({"users": [{"name": "jo", "password": "hunter2"}]});
+    let (parsed_template, source_map) = renderer
+        .render_with_source_map(&template, &context)
+        .unwrap();
+    assert_eq!(parsed_template.text, "jo:7 ");
+    let flags: Vec<_> = source_map
+        .entries
+        .iter()
+        .map(|entry| (entry.variables.clone(), entry.sensitive))
+        .collect();
+    assert_eq!(
+        flags,
+        vec![
+            (vec!["users.0.name".to_string()], true),
+            (vec![], false),
+            (vec!["users.0.password".to_string()], true),
+            (vec![], false),
+        ]
+    );
+}
+
+#[test]
+fn given_a_value_expanding_a_sensitive_one_it_masks_the_whole_value() {
+    let mut renderer = renderer_with_secrets();
+    renderer.set_recursive_expansion(true);
+    let template = Template::parse("url=${url}").unwrap();
+    let context = json!({"api_key": "s3cr3t", "url": "https://${api_key}@example.com"});
+    let parsed_template = renderer.render(&template, &context).unwrap();
+    assert_eq!(parsed_template.text, "url=https://s3cr3t@example.com");
+    assert_eq!(parsed_template.sensitive, vec![Span::new(4, 30)]);
+    let debug = format!("{:?}", parsed_template);
+    assert!(debug.contains("url=[REDACTED]"));
+    assert!(!debug.contains("s3cr3t"));
+}
+
+#[test]
+fn given_a_sensitive_value_failing_to_expand_it_masks_it_in_the_error() {
+    let mut renderer = renderer_with_secrets();
+    renderer.set_recursive_expansion(true);
+    let template = Template::parse("key=${api_key}").unwrap();
+    let error = renderer
+        .render(&template, &json!({"api_key": "s3${cr3t"}))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Syntax error at 4..14: invalid expanded value [REDACTED]"
+    );
+}
+
+#[test]
+fn given_a_sensitive_object_it_masks_each_of_its_values_in_errors() {
+    struct Connect;
+    impl Function for Connect {
+        fn params(&self) -> &[ArgType] {
+            &[ArgType::String, ArgType::Number]
+        }
+        fn call(&self, args: &[Value]) -> Result<Value, String> {
+            Err(format!("cannot reach {}:{}", args[0], args[1]))
+        }
+    }
+    let mut renderer = Renderer::new();
+    renderer.mark_sensitive("database");
+    renderer.register_function("connect", Connect);
+    let template = Template::parse("${connect(database.host, database.port)}").unwrap();
+    let context = json!({"database": {"host": "db.internal", "port": 6543}});
+    let error = renderer.render(&template, &context).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Function connect failed at 2..39: cannot reach \"[REDACTED]\":[REDACTED]"
+    );
+}
@@ -0,0 +1,156 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use kata_template_engine::scaffold::{scaffold, ScaffoldOptions};
+use kata_template_engine::Renderer;
+use serde_json::json;
+
+fn write(root: &Path, path: &str, contents: &[u8]) {
+    let file = root.join(path);
+    fs::create_dir_all(file.parent().unwrap()).unwrap();
+    fs::write(file, contents).unwrap();
+}
+
+fn service_template() -> tempfile::TempDir {
+    let source = tempfile::tempdir().unwrap();
+    write(
+        source.path(),
+        "${name}/Cargo.toml",
+        b"[package]\nname = \"${name}\"\n",
+    );
+    write(
+        source.path(),
+        "${name}/src/main.rs",
+        b"// ${name} on port ${port}\n",
+    );
+    write(source.path(), "${name}/Dockerfile", b"EXPOSE ${port}\n");
+    write(
+        source.path(),
+        "${name}/logo.png",
+        b"\x89PNG\r\n\x1a\n\0\0${name}",
+    );
+    write(
+        source.path(),
+        "scaffold.json",
+        br#"{"conditional": {"${name}/Dockerfile": "docker"}}"#,
+    );
+    source
+}
+
+#[test]
+fn given_a_template_directory_it_renders_every_path_and_file() {
+    let source = service_template();
+    let destination = tempfile::tempdir().unwrap();
+    let context = json!({"name": "billing", "port": 8080, "docker": true});
+    let report = scaffold(
+        &Renderer::new(),
+        source.path(),
+        destination.path(),
+        &context,
+        ScaffoldOptions::default(),
+    )
+    .unwrap();
+    let service = destination.path().join("billing");
+    assert_eq!(
+        fs::read_to_string(service.join("Cargo.toml")).unwrap(),
+        "[package]\nname = \"billing\"\n"
+    );
+    assert_eq!(
+        fs::read_to_string(service.join("src/main.rs")).unwrap(),
+        "// billing on port 8080\n"
+    );
+    assert_eq!(
+        fs::read_to_string(service.join("Dockerfile")).unwrap(),
+        "EXPOSE 8080\n"
+    );
+    assert_eq!(
+        fs::read(service.join("logo.png")).unwrap(),
+        b"\x89PNG\r\n\x1a\n\0\0${name}"
+    );
+    assert_eq!(report.copied, vec![PathBuf::from("billing/logo.png")]);
+    assert_eq!(report.rendered.len(), 3);
+    assert!(!destination.path().join("scaffold.json").exists());
+}
+
+#[test]
+fn given_a_false_condition_in_the_manifest_it_skips_the_file() {
+    let source = service_template();
+    let destination = tempfile::tempdir().unwrap();
+    let context = json!({"name": "billing", "port": 8080});
+    let options = ScaffoldOptions {
+        skip_binary_files: true,
+        ..ScaffoldOptions::default()
+    };
+    let report = scaffold(
+        &Renderer::new(),
+        source.path(),
+        destination.path(),
+        &context,
+        options,
+    )
+    .unwrap();
+    assert!(!destination.path().join("billing/Dockerfile").exists());
+    assert!(!destination.path().join("billing/logo.png").exists());
+    assert_eq!(
+        report.skipped,
+        vec![
+            PathBuf::from("${name}/Dockerfile"),
+            PathBuf::from("${name}/logo.png")
+        ]
+    );
+}
+
+#[test]
+fn given_existing_files_it_refuses_to_overwrite_them_unless_forced() {
+    let source = service_template();
+    let destination = tempfile::tempdir().unwrap();
+    write(destination.path(), "billing/Cargo.toml", b"keep me");
+    let context = json!({"name": "billing", "port": 8080});
+    let error = scaffold(
+        &Renderer::new(),
+        source.path(),
+        destination.path(),
+        &context,
+        ScaffoldOptions::default(),
+    )
+    .unwrap_err();
+    let existing = destination.path().join("billing/Cargo.toml");
+    assert_eq!(
+        error.to_string(),
+        format!("Output file {} already exists", existing.display())
+    );
+    assert_eq!(fs::read_to_string(&existing).unwrap(), "keep me");
+    assert!(!destination.path().join("billing/src/main.rs").exists());
+
+    let options = ScaffoldOptions {
+        force: true,
+        ..ScaffoldOptions::default()
+    };
+    scaffold(
+        &Renderer::new(),
+        source.path(),
+        destination.path(),
+        &context,
+        options,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(&existing).unwrap(),
+        "[package]\nname = \"billing\"\n"
+    );
+}
+
+#[test]
+fn given_a_path_with_a_missing_variable_it_raises_an_error() {
+    let source = service_template();
+    let destination = tempfile::tempdir().unwrap();
+    let error = scaffold(
+        &Renderer::new(),
+        source.path(),
+        destination.path(),
+        &json!({"port": 8080}),
+        ScaffoldOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(error.to_string(), "Invalid output path ${name}/Cargo.toml");
+}
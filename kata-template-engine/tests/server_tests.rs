@@ -0,0 +1,200 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use kata_template_engine::server::{load_templates, serve, ServerOptions, MAX_HEAD_LENGTH};
+use kata_template_engine::Renderer;
+use serde_json::{json, Value};
+
+/// Starts a server on a free port of localhost with the templates of a directory, rendering
+/// inline sources.
+fn start(templates: &[(&str, &str)]) -> SocketAddr {
+    start_with_options(
+        templates,
+        ServerOptions {
+            inline_sources: true,
+        },
+    )
+}
+
+fn start_with_options(templates: &[(&str, &str)], options: ServerOptions) -> SocketAddr {
+    let directory = tempfile::tempdir().unwrap();
+    for (name, text) in templates {
+        fs::write(directory.path().join(name), text).unwrap();
+    }
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut renderer = Renderer::new();
+        load_templates(&mut renderer, directory.path()).unwrap();
+        serve(&listener, &renderer, &options);
+    });
+    address
+}
+
+/// Sends a request and returns the status and the JSON body of the response.
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn given_a_template_directory_it_lists_the_templates() {
+    let address = start(&[
+        ("greeting.txt", "Hello, ${name}"),
+        (
+            "invoice.txt",
+            "---\ndescription: Invoice of a customer\n---\n${customer.name} owes ${total}",
+        ),
+    ]);
+    let (status, body) = request(address, "GET", "/templates", "");
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        json!({"templates": [
+            {"name": "greeting", "description": null, "variables": ["name"], "metadata": {}},
+            {
+                "name": "invoice",
+                "description": "Invoice of a customer",
+                "variables": ["customer", "total"],
+                "metadata": {},
+            },
+        ]})
+    );
+}
+
+#[test]
+fn given_two_files_with_the_same_name_it_refuses_to_load_them() {
+    let directory = tempfile::tempdir().unwrap();
+    fs::write(directory.path().join("invoice.txt"), "${total}").unwrap();
+    fs::write(directory.path().join("invoice.html"), "<p>${total}</p>").unwrap();
+    let error = load_templates(&mut Renderer::new(), directory.path()).unwrap_err();
+    let html = directory.path().join("invoice.html");
+    let txt = directory.path().join("invoice.txt");
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Files {} and {} both define the template invoice",
+            html.display(),
+            txt.display()
+        )
+    );
+}
+
+#[test]
+fn given_a_template_name_and_a_context_it_renders_the_template_with_its_warnings() {
+    let address = start(&[("greeting.txt", "${greet}, ${name}")]);
+    let (status, body) = request(
+        address,
+        "POST",
+        "/render",
+        r#"{"template": "greeting", "context": {"greet": "Hello"}}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        json!({
+            "text": "Hello, ${name}",
+            "warnings": [{"message": "Variable name not replaced"}],
+        })
+    );
+}
+
+#[test]
+fn given_an_inline_template_it_renders_it_with_the_registered_templates() {
+    let address = start(&[("signature.txt", "-- ${sender}")]);
+    let (status, body) = request(
+        address,
+        "POST",
+        "/render",
+        r#"{"source": "Hi\n{% include \"signature\" %}", "context": {"sender": "Ada"}}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body, json!({"text": "Hi\n-- Ada", "warnings": []}));
+}
+
+#[test]
+fn given_inline_sources_disabled_it_refuses_them_but_renders_registered_templates() {
+    let address = start_with_options(&[("greeting.txt", "Hello")], ServerOptions::default());
+    let (status, body) = request(address, "POST", "/render", r#"{"source": "Hi"}"#);
+    assert_eq!(status, 403);
+    assert_eq!(body, json!({"error": "Inline sources are disabled"}));
+    let (status, body) = request(address, "POST", "/render", r#"{"template": "greeting"}"#);
+    assert_eq!((status, body["text"].as_str()), (200, Some("Hello")));
+}
+
+#[test]
+fn given_a_client_still_sending_its_request_it_serves_other_connections() {
+    let address = start(&[("greeting.txt", "Hello")]);
+    let mut slow = TcpStream::connect(address).unwrap();
+    write!(slow, "POST /render HTTP/1.1\r\nContent-Length: 30\r\n").unwrap();
+
+    let (status, body) = request(address, "GET", "/templates", "");
+    assert_eq!(
+        (status, body["templates"][0]["name"].as_str()),
+        (200, Some("greeting"))
+    );
+}
+
+#[test]
+fn given_headers_beyond_the_maximum_length_it_answers_431() {
+    let address = start(&[]);
+    let mut stream = TcpStream::connect(address).unwrap();
+    let header = "a".repeat(MAX_HEAD_LENGTH as usize);
+    write!(
+        stream,
+        "GET /templates HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+        header
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+}
+
+#[test]
+fn given_a_deeply_nested_inline_template_it_answers_with_a_syntax_error() {
+    let address = start(&[]);
+    let source = format!("${{{}x{}}}", "(".repeat(5_000), ")".repeat(5_000));
+    let body = json!({ "source": source }).to_string();
+    let (status, body) = request(address, "POST", "/render", &body);
+    assert_eq!(status, 422);
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("nested deeper than 64 levels"));
+    let (status, _) = request(address, "GET", "/templates", "");
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn given_failing_requests_it_answers_with_an_error_status() {
+    let address = start(&[]);
+    let (status, body) = request(address, "POST", "/render", r#"{"template": "missing"}"#);
+    assert_eq!((status, body["error"].is_string()), (404, true));
+
+    let (status, body) = request(address, "POST", "/render", r#"{"source": "${name"}"#);
+    assert_eq!(status, 422);
+    assert_eq!(body["span"], json!({"start": 0, "end": 6}));
+
+    let (status, _) = request(address, "POST", "/render", "not json");
+    assert_eq!(status, 400);
+    let (status, _) = request(address, "GET", "/render", "");
+    assert_eq!(status, 405);
+    let (status, _) = request(address, "GET", "/health", "");
+    assert_eq!(status, 404);
+}
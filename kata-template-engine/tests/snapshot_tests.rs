@@ -0,0 +1,171 @@
+use std::fs;
+use std::path::PathBuf;
+
+use kata_template_engine::snapshot::{test_snapshots, SnapshotOptions};
+use kata_template_engine::Renderer;
+
+fn write_files(directory: &tempfile::TempDir, files: &[(&str, &str)]) {
+    for (name, text) in files {
+        fs::write(directory.path().join(name), text).unwrap();
+    }
+}
+
+#[test]
+fn given_matching_snapshots_it_passes_every_fixture_of_every_template() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("letter.txt", "Dear ${name},\n{% include \"signature\" %}\n"),
+            ("signature.txt", "Regards"),
+            ("letter.json", r#"{"name": "Ada"}"#),
+            ("letter.expected", "Dear Ada,\nRegards\n"),
+            ("letter.formal.json", r#"{"name": "Ms Lovelace"}"#),
+            ("letter.formal.expected", "Dear Ms Lovelace,\nRegards\n"),
+        ],
+    );
+    let report = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        report.passed,
+        vec![
+            PathBuf::from("letter.formal.expected"),
+            PathBuf::from("letter.expected")
+        ]
+    );
+    assert!(report.failures.is_empty());
+}
+
+#[test]
+fn given_a_changed_output_it_fails_with_a_unified_diff() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("greeting.txt", "Hello,\n${name}!\nBye\n"),
+            ("greeting.json", r#"{"name": "Ada"}"#),
+            ("greeting.expected", "Hello,\nAda.\nBye\n"),
+            ("broken.txt", "${missing | upper}"),
+            ("broken.json", "{"),
+        ],
+    );
+    let report = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(report.failures.len(), 2);
+    assert_eq!(
+        report.failures[0].snapshot,
+        PathBuf::from("broken.expected")
+    );
+    assert!(report.failures[0].message.contains("JSON error"));
+    assert_eq!(
+        report.failures[1].message,
+        "--- greeting.expected\n+++ output\n@@ -1,3 +1,3 @@\n Hello,\n-Ada.\n+Ada!\n Bye\n"
+    );
+}
+
+#[test]
+fn given_the_update_option_it_rewrites_the_missing_and_changed_snapshots() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("greeting.txt", "Hello, ${name}"),
+            ("greeting.json", r#"{"name": "Ada"}"#),
+            ("greeting.expected", "Hello, Bob"),
+            ("greeting.new.json", r#"{"name": "Cy"}"#),
+        ],
+    );
+    let report = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions { update: true },
+    )
+    .unwrap();
+    assert_eq!(
+        report.updated,
+        vec![
+            PathBuf::from("greeting.expected"),
+            PathBuf::from("greeting.new.expected")
+        ]
+    );
+    let read = |name: &str| fs::read_to_string(directory.path().join(name)).unwrap();
+    assert_eq!(read("greeting.expected"), "Hello, Ada");
+    assert_eq!(read("greeting.new.expected"), "Hello, Cy");
+}
+
+#[test]
+fn given_two_templates_with_the_same_name_it_raises_an_error() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("invoice.html", "<p>${total}</p>"),
+            ("invoice.txt", "${total}"),
+        ],
+    );
+    let error = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Files invoice.html and invoice.txt both define the template invoice"
+    );
+}
+
+#[test]
+fn given_a_json_template_it_renders_it_with_the_fixtures_named_after_it() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("config.json", "{\"port\": ${port}}"),
+            ("config.default.json", r#"{"port": 8080}"#),
+            ("config.default.expected", "{\"port\": 8080}"),
+        ],
+    );
+    let report = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        report.passed,
+        vec![PathBuf::from("config.default.expected")]
+    );
+    assert!(report.failures.is_empty());
+}
+
+#[test]
+fn given_a_fixture_named_after_two_templates_it_raises_an_error() {
+    let directory = tempfile::tempdir().unwrap();
+    write_files(
+        &directory,
+        &[
+            ("invoice.txt", "${total}"),
+            ("invoice.overdue.txt", "Overdue: ${total}"),
+            ("invoice.overdue.json", r#"{"total": 3}"#),
+        ],
+    );
+    let error = test_snapshots(
+        &mut Renderer::new(),
+        directory.path(),
+        SnapshotOptions::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Fixture invoice.overdue.json is named after both templates invoice.overdue and invoice"
+    );
+}
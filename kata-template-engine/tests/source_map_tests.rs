@@ -0,0 +1,116 @@
+use kata_template_engine::{Renderer, SourceMapEntry, Span, Template};
+use serde_json::json;
+
+fn entry(
+    output: (usize, usize),
+    template: Option<&str>,
+    span: (usize, usize),
+    variables: &[&str],
+) -> SourceMapEntry {
+    SourceMapEntry {
+        output: Span::new(output.0, output.1),
+        template: template.map(str::to_string),
+        span: Span::new(span.0, span.1),
+        variables: variables
+            .iter()
+            .map(|variable| variable.to_string())
+            .collect(),
+        sensitive: false,
+    }
+}
+
+#[test]
+fn given_text_and_placeholders_it_maps_each_output_range_to_its_template_span() {
+    let template = Template::parse("Dear ${user.name}, you owe ${total * 2}.").unwrap();
+    let context = json!({"user": {"name": "John"}, "total": 21});
+    let (parsed_template, source_map) = Renderer::new()
+        .render_with_source_map(&template, &context)
+        .unwrap();
+    assert_eq!(parsed_template.text, "Dear John, you owe 42.");
+    assert_eq!(
+        source_map.entries,
+        vec![
+            entry((0, 5), None, (0, 5), &[]),
+            entry((5, 9), None, (5, 17), &["user.name"]),
+            entry((9, 19), None, (17, 27), &[]),
+            entry((19, 21), None, (27, 39), &["total"]),
+            entry((21, 22), None, (39, 40), &[]),
+        ]
+    );
+    assert_eq!(source_map.find(6), Some(&source_map.entries[1]));
+    assert_eq!(source_map.find(22), None);
+}
+
+#[test]
+fn given_loops_and_includes_it_maps_to_the_included_template_and_the_element_path() {
+    let mut renderer = Renderer::new();
+    renderer.add_template("line", "${line.title}\n").unwrap();
+    renderer
+        .add_template(
+            "invoice",
+            "{% for line in lines %}{% include \"line\" %}{% endfor %}",
+        )
+        .unwrap();
+    let context = json!({"lines": [{"title": "Book"}, {"title": "Pen"}]});
+    let (parsed_template, source_map) = renderer
+        .render_template_with_source_map("invoice", &context)
+        .unwrap();
+    assert_eq!(parsed_template.text, "Book\nPen\n");
+    assert_eq!(
+        source_map.entries,
+        vec![
+            entry((0, 4), Some("line"), (0, 13), &["lines.0.title"]),
+            entry((4, 5), Some("line"), (13, 14), &[]),
+            entry((5, 8), Some("line"), (0, 13), &["lines.1.title"]),
+            entry((8, 9), Some("line"), (13, 14), &[]),
+        ]
+    );
+}
+
+#[test]
+fn given_a_source_map_it_serializes_it_to_json() {
+    let template = Template::parse("Hi ${name}").unwrap();
+    let (_, source_map) = Renderer::new()
+        .render_with_source_map(&template, &json!({"name": "Jo"}))
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_str(&source_map.to_json()).unwrap();
+    assert_eq!(
+        json,
+        json!({"entries": [
+            {"output": {"start": 0, "end": 3}, "template": null, "span": {"start": 0, "end": 3}, "variables": [], "sensitive": false},
+            {"output": {"start": 3, "end": 5}, "template": null, "span": {"start": 3, "end": 10}, "variables": ["name"], "sensitive": false},
+        ]})
+    );
+}
+
+#[test]
+fn given_indented_partials_it_maps_every_byte_of_the_output() {
+    let mut renderer = Renderer::new();
+    renderer.mark_sensitive("token");
+    renderer
+        .add_mustache_template("item", "{{name}}: {{token}}\n{{#tags}}{{.}}\n{{/tags}}")
+        .unwrap();
+    renderer
+        .add_mustache_template("list", "items:\n  {{> item}}\nend\n")
+        .unwrap();
+    let context = json!({"name": "pen", "token": "s3cr3t", "tags": ["blue", "red"]});
+    let (parsed_template, source_map) = renderer
+        .render_template_with_source_map("list", &context)
+        .unwrap();
+    assert_eq!(
+        parsed_template.text,
+        "items:\n  pen: s3cr3t\n  blue\n  red\nend\n"
+    );
+    let mut end = 0;
+    for entry in &source_map.entries {
+        assert_eq!(entry.output.start, end);
+        end = entry.output.end;
+    }
+    assert_eq!(end, parsed_template.text.len());
+    let indentation = source_map.find(22).unwrap();
+    assert_eq!(indentation.output, Span::new(21, 23));
+    assert_eq!(indentation.template.as_deref(), Some("item"));
+    assert!(!indentation.sensitive);
+    assert_eq!(source_map.find(16).unwrap().output, Span::new(14, 20));
+    assert_eq!(parsed_template.sensitive, vec![Span::new(14, 20)]);
+}
@@ -0,0 +1,79 @@
+use std::fs;
+
+use kata_template_engine::watch::RenderWatch;
+use kata_template_engine::Renderer;
+
+#[test]
+fn given_a_changed_include_it_renders_again_and_shows_the_diff() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("page.txt");
+    let header = directory.path().join("header.txt");
+    let variables = directory.path().join("variables.json");
+    fs::write(&template, "{% include \"header\" %}\nBody of ${name}\n").unwrap();
+    fs::write(&header, "Header").unwrap();
+    fs::write(&variables, r#"{"name": "Ada"}"#).unwrap();
+    let mut watch = RenderWatch::new(&template, Some(&variables));
+
+    let first = watch.render(&mut Renderer::new()).unwrap();
+    assert_eq!(first.text, "Header\nBody of Ada\n");
+    assert_eq!(first.diff, None);
+    assert!(watch.changed().is_empty());
+
+    fs::write(&header, "New header").unwrap();
+    assert_eq!(watch.changed(), vec![header]);
+    let second = watch.render(&mut Renderer::new()).unwrap();
+    assert_eq!(
+        second.diff.unwrap(),
+        "--- previous output\n+++ output\n@@ -1,2 +1,2 @@\n-Header\n+New header\n Body of Ada\n"
+    );
+}
+
+#[test]
+fn given_a_changed_variables_file_it_reports_the_warnings_of_the_new_render() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("greeting.txt");
+    let variables = directory.path().join("variables.json");
+    fs::write(&template, "Hello, ${name}").unwrap();
+    fs::write(&variables, r#"{"name": "Ada"}"#).unwrap();
+    let mut watch = RenderWatch::new(&template, Some(&variables));
+    assert!(watch
+        .render(&mut Renderer::new())
+        .unwrap()
+        .warnings
+        .is_empty());
+
+    fs::write(&variables, r#"{"user": "Ada"}"#).unwrap();
+    assert_eq!(watch.changed(), vec![variables]);
+    let render = watch.render(&mut Renderer::new()).unwrap();
+    assert_eq!(render.text, "Hello, ${name}");
+    assert_eq!(render.warnings, vec!["Variable name not replaced"]);
+}
+
+#[test]
+fn given_a_missing_include_it_fails_and_renders_again_once_it_is_created() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("page.html");
+    let footer = directory.path().join("footer.html");
+    fs::write(&template, "<p>Page</p>{% include \"footer\" %}").unwrap();
+    let mut watch = RenderWatch::new(&template, None);
+    assert!(watch.render(&mut Renderer::new()).is_err());
+    assert!(watch.changed().is_empty());
+
+    fs::write(&footer, "<footer></footer>").unwrap();
+    assert_eq!(watch.changed(), vec![footer]);
+    let render = watch.render(&mut Renderer::new()).unwrap();
+    assert_eq!(render.text, "<p>Page</p><footer></footer>");
+}
+
+#[test]
+fn given_an_unchanged_output_it_gives_an_empty_diff() {
+    let directory = tempfile::tempdir().unwrap();
+    let template = directory.path().join("note.txt");
+    fs::write(&template, "Note").unwrap();
+    let mut watch = RenderWatch::new(&template, None);
+    watch.render(&mut Renderer::new()).unwrap();
+
+    fs::write(&template, "{% if true %}Note{% endif %}").unwrap();
+    let render = watch.render(&mut Renderer::new()).unwrap();
+    assert_eq!(render.diff, Some(String::new()));
+}